@@ -0,0 +1,78 @@
+//! Reports the allocations made by `handle_file` and by the zero-copy `handle_bytes` path.
+//!
+//! The counting allocator is installed in this target only, so that it doesn't weigh on the
+//! timings of the criterion benchmarks.
+
+use decoder::{handle_bytes, handle_file, read_file_bytes};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Global allocator counting allocations and allocated bytes, to compare decoding paths.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn benchmark_files() -> Vec<PathBuf> {
+    let files = fs::read_dir("tests/benchmark_files").expect("Failed to read dir");
+    files
+        .map(|file| file.expect("Failed to get path").path())
+        .filter(|path| path.extension().map(|ext| ext == "dbin").unwrap_or(false))
+        .collect()
+}
+
+/// Returns the number of allocations and allocated bytes made by `f`.
+fn count_allocations<F: FnOnce()>(f: F) -> (usize, usize) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    f();
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes,
+    )
+}
+
+/// Prints the allocations of each decoding path to stderr, away from the output of criterion.
+fn main() {
+    let (mut reader_allocations, mut reader_bytes) = (0, 0);
+    let (mut bytes_allocations, mut bytes_bytes) = (0, 0);
+    for path in benchmark_files() {
+        let (allocations, bytes) = count_allocations(|| {
+            handle_file(&path, None, None, None).expect("Failed to handle file");
+        });
+        reader_allocations += allocations;
+        reader_bytes += bytes;
+
+        let (allocations, bytes) = count_allocations(|| {
+            let buf = read_file_bytes(&path).expect("Failed to read file");
+            handle_bytes(buf, None).expect("Failed to handle bytes");
+        });
+        bytes_allocations += allocations;
+        bytes_bytes += bytes;
+    }
+    eprintln!(
+        "handle-flat-file:       {} allocations, {} bytes",
+        reader_allocations, reader_bytes
+    );
+    eprintln!(
+        "handle-flat-file-bytes: {} allocations, {} bytes",
+        bytes_allocations, bytes_bytes
+    );
+}
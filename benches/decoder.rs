@@ -1,32 +1,45 @@
 extern crate rand;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use decoder::handle_file;
+use decoder::{extract_blocks_mmap, handle_bytes, handle_file, read_file_bytes};
 use std::fs;
+use std::path::PathBuf;
 
 const ITERS_PER_FILE: usize = 10;
 
+fn benchmark_files() -> Vec<PathBuf> {
+    let files = fs::read_dir("tests/benchmark_files").expect("Failed to read dir");
+    files
+        .map(|file| file.expect("Failed to get path").path())
+        .filter(|path| path.extension().map(|ext| ext == "dbin").unwrap_or(false))
+        .collect()
+}
+
 fn bench(c: &mut Criterion) {
     let mut group = c.benchmark_group("handle-flat-files");
     group.sample_size(ITERS_PER_FILE);
 
     group.bench_function("handle-flat-file", |b| {
-        let files = fs::read_dir("tests/benchmark_files").expect("Failed to read dir");
-        for file in files {
-            let path = file.expect("Failed to get path").path();
-            match path.extension() {
-                None => continue,
-                Some(ext) => {
-                    if ext != "dbin" {
-                        continue;
-                    }
-                }
-            }
-
+        for path in benchmark_files() {
             b.iter(|| handle_file(black_box(&path), None, None, None));
         }
     });
 
+    group.bench_function("handle-flat-file-bytes", |b| {
+        for path in benchmark_files() {
+            b.iter(|| {
+                let bytes = read_file_bytes(black_box(&path)).expect("Failed to read file");
+                handle_bytes(bytes, None)
+            });
+        }
+    });
+
+    group.bench_function("extract-blocks-mmap", |b| {
+        for path in benchmark_files() {
+            b.iter(|| extract_blocks_mmap(black_box(&path)));
+        }
+    });
+
     group.finish();
 }
 
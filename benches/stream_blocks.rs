@@ -5,7 +5,7 @@ use std::{
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use decoder::{
-    dbin::{error::DbinFileError, DbinFile},
+    dbin::{error::DbinFileError, DbinReader},
     receipts::check_receipt_root,
     transactions::check_transaction_root,
 };
@@ -31,16 +31,15 @@ fn read_decode_check_bench(c: &mut Criterion) {
                 }
             }
             let file = File::open(&path).expect("Failed to open file");
-            let mut reader = BufReader::new(file);
-            let mut message: Result<Vec<u8>, decoder::dbin::error::DbinFileError> =
-                Err(DbinFileError::InvalidDBINBytes);
+            let mut reader = DbinReader::new(BufReader::new(file));
+            let mut message: Result<Option<Vec<u8>>, DbinFileError> = Ok(None);
             loop {
                 b.iter(|| {
-                    message = black_box(DbinFile::read_message_stream(&mut reader));
+                    message = black_box(reader.next_message());
                 });
                 match message {
-                    Ok(_) => continue,
-                    Err(_) => {
+                    Ok(Some(_)) => continue,
+                    _ => {
                         break;
                     }
                 }
@@ -61,11 +60,11 @@ fn read_decode_check_bench(c: &mut Criterion) {
                 }
             }
             let file = File::open(&path).expect("Failed to open file");
-            let mut reader = BufReader::new(file);
+            let mut reader = DbinReader::new(BufReader::new(file));
             loop {
-                let message = match DbinFile::read_message_stream(&mut reader) {
-                    Ok(message) => message,
-                    Err(_) => {
+                let message = match reader.next_message() {
+                    Ok(Some(message)) => message,
+                    _ => {
                         break;
                     }
                 };
@@ -89,11 +88,11 @@ fn read_decode_check_bench(c: &mut Criterion) {
                 }
             }
             let file = File::open(&path).expect("Failed to open file");
-            let mut reader = BufReader::new(file);
+            let mut reader = DbinReader::new(BufReader::new(file));
             loop {
-                let message = match DbinFile::read_message_stream(&mut reader) {
-                    Ok(message) => message,
-                    Err(_) => {
+                let message = match reader.next_message() {
+                    Ok(Some(message)) => message,
+                    _ => {
                         break;
                     }
                 };
@@ -122,11 +121,11 @@ fn read_decode_check_bench(c: &mut Criterion) {
                 }
             }
             let file = File::open(&path).expect("Failed to open file");
-            let mut reader = BufReader::new(file);
+            let mut reader = DbinReader::new(BufReader::new(file));
             loop {
-                let message = match DbinFile::read_message_stream(&mut reader) {
-                    Ok(message) => message,
-                    Err(_) => {
+                let message = match reader.next_message() {
+                    Ok(Some(message)) => message,
+                    _ => {
                         break;
                     }
                 };
@@ -156,11 +155,11 @@ fn read_decode_check_bench(c: &mut Criterion) {
                 }
             }
             let file = File::open(&path).expect("Failed to open file");
-            let mut reader = BufReader::new(file);
+            let mut reader = DbinReader::new(BufReader::new(file));
             loop {
-                let message = match DbinFile::read_message_stream(&mut reader) {
-                    Ok(message) => message,
-                    Err(_) => {
+                let message = match reader.next_message() {
+                    Ok(Some(message)) => message,
+                    _ => {
                         break;
                     }
                 };
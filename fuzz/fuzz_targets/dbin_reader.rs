@@ -0,0 +1,9 @@
+#![no_main]
+
+use decoder::dbin::DbinReader;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = DbinReader::new(data);
+    while let Ok(Some(_)) = reader.next_message() {}
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use decoder::dbin::DbinFile;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = DbinFile::try_from_read(&mut &data[..]);
+    let _ = DbinFile::try_from_bytes(data.to_vec().into());
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use decoder::compression::Compression;
+use decoder::handle_buf;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Decompression is skipped, it would mostly exercise the codecs rather than the decoder.
+    let _ = handle_buf(data, Some(Compression::None));
+});
@@ -0,0 +1,24 @@
+use crate::headers::error::BlockHeaderError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AccumulatorError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid header: {0}")]
+    BlockHeaderError(#[from] BlockHeaderError),
+    #[error("Invalid SSZ encoding: {0}")]
+    InvalidSsz(String),
+    #[error("Accumulator root {found} doesn't match the mainnet accumulator root {expected}")]
+    MismatchedAccumulatorRoot { expected: String, found: String },
+    #[error("Header hash {0} doesn't match block hash {1}")]
+    MismatchedHash(String, String),
+    #[error("Block {0} is after the merge, the accumulator only holds pre-merge blocks")]
+    PostMerge(u64),
+    #[error("Expected block {expected}, found block {found}")]
+    NonContiguous { expected: u64, found: u64 },
+    #[error("Epoch {0} is not part of the accumulator")]
+    UnknownEpoch(u64),
+    #[error("Root of epoch {epoch} computed from {records} blocks doesn't match the accumulator")]
+    MismatchedEpochRoot { epoch: u64, records: usize },
+}
@@ -0,0 +1,427 @@
+pub mod error;
+
+use crate::accumulator::error::AccumulatorError;
+use crate::error::DecodeError;
+use crate::headers::block_header_to_header;
+use crate::headers::error::BlockHeaderError;
+use crate::{handle_file, is_flat_file, MERGE_BLOCK};
+use alloy_primitives::b256;
+use reth_primitives::{hex, B256, U256};
+use sf_protos::ethereum::r#type::v2::Block;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of blocks in an epoch, and so in an epoch accumulator.
+pub const EPOCH_SIZE: usize = 8192;
+
+/// Maximum number of epochs of the pre-merge accumulator.
+pub const MAX_HISTORICAL_EPOCHS: usize = 2048;
+
+/// `hash_tree_root` of the mainnet pre-merge accumulator, frozen at the merge.
+///
+/// Its epoch roots are read from the SSZ encoded accumulator distributed by Portal network
+/// clients, which is only trusted once its root matches this one, see [`PreMergeAccumulator::load`].
+pub const MAINNET_ACCUMULATOR_ROOT: B256 =
+    b256!("8eac399e24480dce3cfe06f4bdecba51c6e5d0c46200e3e8611a0b44a3a69ff9");
+
+/// SSZ encoded mainnet accumulator embedded in the crate, the `merge_macc.bin` asset of Portal
+/// network clients, copied to `assets/merge_macc.bin` before building
+#[cfg(feature = "mainnet-accumulator")]
+const MAINNET_ACCUMULATOR: &[u8] = include_bytes!("../../assets/merge_macc.bin");
+
+/// `HeaderRecord` is an entry of an epoch accumulator, as defined by the
+/// [Portal history network](https://github.com/ethereum/portal-network-specs/blob/master/history/history-network.md#the-header-accumulator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderRecord {
+    pub block_hash: B256,
+    pub total_difficulty: U256,
+}
+
+impl HeaderRecord {
+    /// SSZ `hash_tree_root` of the record, a container of a `Bytes32` and a `uint256`
+    pub fn hash_tree_root(&self) -> B256 {
+        hash_pair(
+            self.block_hash,
+            B256::from(self.total_difficulty.to_le_bytes::<32>()),
+        )
+    }
+}
+
+impl TryFrom<&Block> for HeaderRecord {
+    type Error = AccumulatorError;
+
+    /// Takes the hash and total difficulty of a block, checking that its header hashes to the block hash
+    fn try_from(block: &Block) -> Result<Self, Self::Error> {
+        let block_header = block
+            .header
+            .as_ref()
+            .ok_or(BlockHeaderError::MissingHeader)?;
+        let hash = block_header_to_header(block_header)?.hash_slow();
+        if hash.as_slice() != block.hash.as_slice() {
+            return Err(AccumulatorError::MismatchedHash(
+                hex::encode(hash),
+                hex::encode(&block.hash),
+            ));
+        }
+
+        let total_difficulty = block_header
+            .total_difficulty
+            .as_ref()
+            .and_then(|total_difficulty| U256::try_from_be_slice(&total_difficulty.bytes))
+            .ok_or(BlockHeaderError::InvalidTotalDifficulty)?;
+
+        Ok(Self {
+            block_hash: hash,
+            total_difficulty,
+        })
+    }
+}
+
+/// `PreMergeAccumulator` holds the roots of the epoch accumulators of all pre-merge blocks, as defined by the
+/// [Portal history network](https://github.com/ethereum/portal-network-specs/blob/master/history/history-network.md#the-header-accumulator).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreMergeAccumulator {
+    pub historical_epochs: Vec<B256>,
+    /// Records of the epoch being built, empty once the accumulator is frozen at the merge
+    pub current_epoch: Vec<HeaderRecord>,
+}
+
+impl PreMergeAccumulator {
+    /// Reads the SSZ encoded mainnet accumulator from `path`, e.g. the `merge_macc.bin` asset of
+    /// Portal network clients, and checks it against [`MAINNET_ACCUMULATOR_ROOT`]
+    pub fn load(path: &Path) -> Result<Self, AccumulatorError> {
+        Self::from_mainnet_ssz(&fs::read(path)?)
+    }
+
+    /// The mainnet accumulator embedded in the crate, checked against [`MAINNET_ACCUMULATOR_ROOT`].
+    /// Needs the mainnet-accumulator feature
+    #[cfg(feature = "mainnet-accumulator")]
+    pub fn mainnet() -> Result<Self, AccumulatorError> {
+        Self::from_mainnet_ssz(MAINNET_ACCUMULATOR)
+    }
+
+    fn from_mainnet_ssz(bytes: &[u8]) -> Result<Self, AccumulatorError> {
+        let accumulator = Self::from_ssz(bytes)?;
+        let root = accumulator.hash_tree_root();
+        if root != MAINNET_ACCUMULATOR_ROOT {
+            return Err(AccumulatorError::MismatchedAccumulatorRoot {
+                expected: MAINNET_ACCUMULATOR_ROOT.to_string(),
+                found: root.to_string(),
+            });
+        }
+        Ok(accumulator)
+    }
+
+    /// Decodes the SSZ container `(historical_epochs: List[Bytes32], current_epoch: List[HeaderRecord])`
+    pub fn from_ssz(bytes: &[u8]) -> Result<Self, AccumulatorError> {
+        let offset = |index: usize| -> Result<usize, AccumulatorError> {
+            let bytes: [u8; 4] = bytes
+                .get(index * 4..index * 4 + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| AccumulatorError::InvalidSsz("missing offset".to_string()))?;
+            Ok(u32::from_le_bytes(bytes) as usize)
+        };
+        let (epochs_offset, current_offset) = (offset(0)?, offset(1)?);
+        if epochs_offset != 8 || current_offset < epochs_offset || current_offset > bytes.len() {
+            return Err(AccumulatorError::InvalidSsz("invalid offsets".to_string()));
+        }
+
+        let epochs = &bytes[epochs_offset..current_offset];
+        let current = &bytes[current_offset..];
+        if !epochs.len().is_multiple_of(32) || epochs.len() / 32 > MAX_HISTORICAL_EPOCHS {
+            return Err(AccumulatorError::InvalidSsz(
+                "invalid historical epochs".to_string(),
+            ));
+        }
+        if !current.len().is_multiple_of(64) || current.len() / 64 > EPOCH_SIZE {
+            return Err(AccumulatorError::InvalidSsz(
+                "invalid current epoch".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            historical_epochs: epochs.chunks(32).map(B256::from_slice).collect(),
+            current_epoch: current
+                .chunks(64)
+                .map(|record| HeaderRecord {
+                    block_hash: B256::from_slice(&record[..32]),
+                    total_difficulty: U256::from_le_slice(&record[32..]),
+                })
+                .collect(),
+        })
+    }
+
+    /// Encodes the accumulator with SSZ, the reverse of [`PreMergeAccumulator::from_ssz`]
+    pub fn to_ssz(&self) -> Vec<u8> {
+        let current_offset = 8 + 32 * self.historical_epochs.len();
+        let mut bytes = Vec::with_capacity(current_offset + 64 * self.current_epoch.len());
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&(current_offset as u32).to_le_bytes());
+        for root in &self.historical_epochs {
+            bytes.extend_from_slice(root.as_slice());
+        }
+        for record in &self.current_epoch {
+            bytes.extend_from_slice(record.block_hash.as_slice());
+            bytes.extend_from_slice(&record.total_difficulty.to_le_bytes::<32>());
+        }
+        bytes
+    }
+
+    /// SSZ `hash_tree_root` of the accumulator
+    pub fn hash_tree_root(&self) -> B256 {
+        let epochs_root = mix_in_length(
+            merkleize(self.historical_epochs.clone(), MAX_HISTORICAL_EPOCHS),
+            self.historical_epochs.len(),
+        );
+        hash_pair(epochs_root, epoch_accumulator_root(&self.current_epoch))
+    }
+
+    /// Checks that `records`, all the records of `epoch` in order, have the epoch root of the accumulator
+    pub fn verify_epoch(
+        &self,
+        epoch: u64,
+        records: &[HeaderRecord],
+    ) -> Result<(), AccumulatorError> {
+        let expected = self
+            .historical_epochs
+            .get(epoch as usize)
+            .ok_or(AccumulatorError::UnknownEpoch(epoch))?;
+        if epoch_accumulator_root(records) != *expected {
+            return Err(AccumulatorError::MismatchedEpochRoot {
+                epoch,
+                records: records.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// `AccumulatorVerifier` builds the epoch accumulators of blocks given in order, and checks each
+/// one against a [`PreMergeAccumulator`] once all its blocks are given.
+///
+/// Epochs must be given whole, starting from their first block, as a partial epoch can't match
+/// its root. The last pre-merge epoch ends at the merge.
+pub struct AccumulatorVerifier<'a> {
+    accumulator: &'a PreMergeAccumulator,
+    epoch: Option<(u64, Vec<HeaderRecord>)>,
+    verified_epochs: Vec<u64>,
+}
+
+impl<'a> AccumulatorVerifier<'a> {
+    pub fn new(accumulator: &'a PreMergeAccumulator) -> Self {
+        Self {
+            accumulator,
+            epoch: None,
+            verified_epochs: Vec::new(),
+        }
+    }
+
+    /// Adds the header record of `block` to its epoch, verifying the previous epoch if it is the
+    /// first block of a new one
+    pub fn push_block(&mut self, block: &Block) -> Result<(), AccumulatorError> {
+        if block.number > MERGE_BLOCK as u64 {
+            return Err(AccumulatorError::PostMerge(block.number));
+        }
+
+        let epoch = block.number / EPOCH_SIZE as u64;
+        if self.epoch.as_ref().map(|(current, _)| *current) != Some(epoch) {
+            self.verify_current()?;
+        }
+
+        let (_, records) = self
+            .epoch
+            .get_or_insert_with(|| (epoch, Vec::with_capacity(EPOCH_SIZE)));
+        let expected = epoch * EPOCH_SIZE as u64 + records.len() as u64;
+        if block.number != expected {
+            return Err(AccumulatorError::NonContiguous {
+                expected,
+                found: block.number,
+            });
+        }
+        records.push(HeaderRecord::try_from(block)?);
+        Ok(())
+    }
+
+    /// Verifies the last epoch, and returns all the epochs verified
+    pub fn finish(mut self) -> Result<Vec<u64>, AccumulatorError> {
+        self.verify_current()?;
+        Ok(self.verified_epochs)
+    }
+
+    fn verify_current(&mut self) -> Result<(), AccumulatorError> {
+        if let Some((epoch, records)) = self.epoch.take() {
+            self.accumulator.verify_epoch(epoch, &records)?;
+            self.verified_epochs.push(epoch);
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the flat files of `dir` in order, and verifies their blocks against `accumulator`.
+///
+/// Besides the checks of the transaction and receipt roots made while decoding, each header is
+/// checked against its block hash, so the verified epochs prove the flat files hold canonical blocks.
+/// Returns the epochs verified.
+///
+/// # Arguments
+///
+/// * `dir`: folder of the flat files, covering whole epochs
+/// * `accumulator`: the trusted accumulator, see [`PreMergeAccumulator::load`]
+pub fn verify_dir(dir: &Path, accumulator: &PreMergeAccumulator) -> Result<Vec<u64>, DecodeError> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|path| is_flat_file(path));
+    paths.sort();
+
+    let mut verifier = AccumulatorVerifier::new(accumulator);
+    for path in paths {
+        for block in handle_file(&path, None, None, None)? {
+            verifier.push_block(&block)?;
+        }
+    }
+    Ok(verifier.finish()?)
+}
+
+/// Computes the SSZ `hash_tree_root` of an epoch accumulator, a `List[HeaderRecord, EPOCH_SIZE]`.
+///
+/// This is the root era1 files store in their accumulator record. `records` can hold fewer
+/// than [`EPOCH_SIZE`] records, as the last pre-merge epoch does.
+pub fn epoch_accumulator_root(records: &[HeaderRecord]) -> B256 {
+    let leaves = records.iter().map(HeaderRecord::hash_tree_root).collect();
+    mix_in_length(merkleize(leaves, EPOCH_SIZE), records.len())
+}
+
+/// Merkleizes `leaves` into a tree of `limit` leaves, padded with zero hashes
+fn merkleize(mut layer: Vec<B256>, limit: usize) -> B256 {
+    let depth = limit.next_power_of_two().trailing_zeros();
+    let mut zero_hash = B256::ZERO;
+    for _ in 0..depth {
+        if layer.len() % 2 == 1 {
+            layer.push(zero_hash);
+        }
+        layer = layer
+            .chunks(2)
+            .map(|pair| hash_pair(pair[0], pair[1]))
+            .collect();
+        zero_hash = hash_pair(zero_hash, zero_hash);
+    }
+    layer.first().copied().unwrap_or(zero_hash)
+}
+
+fn mix_in_length(root: B256, length: usize) -> B256 {
+    hash_pair(root, B256::from(U256::from(length).to_le_bytes::<32>()))
+}
+
+fn hash_pair(left: B256, right: B256) -> B256 {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    B256::from_slice(&hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error::AccumulatorError;
+    use super::{
+        epoch_accumulator_root, AccumulatorVerifier, HeaderRecord, PreMergeAccumulator,
+        MAINNET_ACCUMULATOR_ROOT,
+    };
+    use crate::handle_file;
+    use reth_primitives::{B256, U256};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_header_record() {
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let genesis = HeaderRecord::try_from(&blocks[0]).unwrap();
+        assert_eq!(genesis.block_hash.as_slice(), blocks[0].hash.as_slice());
+        assert_eq!(genesis.total_difficulty, U256::from(17179869184u64));
+
+        let mut block = blocks[1].clone();
+        block.hash = blocks[2].hash.clone();
+        assert!(matches!(
+            HeaderRecord::try_from(&block),
+            Err(AccumulatorError::MismatchedHash(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_accumulator_ssz() {
+        let accumulator = PreMergeAccumulator {
+            historical_epochs: vec![B256::repeat_byte(1), B256::repeat_byte(2)],
+            current_epoch: vec![HeaderRecord {
+                block_hash: B256::repeat_byte(3),
+                total_difficulty: U256::from(4),
+            }],
+        };
+
+        let decoded = PreMergeAccumulator::from_ssz(&accumulator.to_ssz()).unwrap();
+        assert_eq!(decoded, accumulator);
+        assert_ne!(decoded.hash_tree_root(), MAINNET_ACCUMULATOR_ROOT);
+        assert!(PreMergeAccumulator::from_ssz(&accumulator.to_ssz()[..40]).is_err());
+    }
+
+    #[cfg(feature = "mainnet-accumulator")]
+    #[test]
+    fn test_mainnet_accumulator() {
+        let accumulator = PreMergeAccumulator::mainnet().unwrap();
+        assert_eq!(accumulator.hash_tree_root(), MAINNET_ACCUMULATOR_ROOT);
+        assert_eq!(accumulator.historical_epochs.len(), 1897);
+        assert!(accumulator.current_epoch.is_empty());
+    }
+
+    #[test]
+    fn test_accumulator_verifier() {
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let records: Vec<HeaderRecord> = blocks
+            .iter()
+            .map(|block| HeaderRecord::try_from(block).unwrap())
+            .collect();
+        let accumulator = PreMergeAccumulator {
+            historical_epochs: vec![epoch_accumulator_root(&records)],
+            current_epoch: Vec::new(),
+        };
+
+        let mut verifier = AccumulatorVerifier::new(&accumulator);
+        for block in &blocks {
+            verifier.push_block(block).unwrap();
+        }
+        assert_eq!(verifier.finish().unwrap(), vec![0]);
+
+        let mut verifier = AccumulatorVerifier::new(&accumulator);
+        for block in &blocks[..50] {
+            verifier.push_block(block).unwrap();
+        }
+        assert!(matches!(
+            verifier.finish(),
+            Err(AccumulatorError::MismatchedEpochRoot {
+                epoch: 0,
+                records: 50
+            })
+        ));
+
+        let mut verifier = AccumulatorVerifier::new(&accumulator);
+        assert!(matches!(
+            verifier.push_block(&blocks[1]),
+            Err(AccumulatorError::NonContiguous {
+                expected: 0,
+                found: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+use crate::beacon::BeaconFork;
+use crate::headers::error::BlockHeaderError;
+use crate::transactions::error::TransactionError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BeaconError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Invalid SSZ encoding: {0}")]
+    InvalidSsz(String),
+    #[error("Unknown fork: {0}")]
+    UnknownFork(String),
+    #[error("The fork of SSZ encoded beacon blocks must be given")]
+    MissingFork,
+    #[error("Verifying {0} blocks isn't supported: {1}")]
+    UnsupportedFork(BeaconFork, &'static str),
+    #[error("Invalid header: {0}")]
+    BlockHeaderError(#[from] BlockHeaderError),
+    #[error("Invalid transaction: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Header hash {0} doesn't match block hash {1}")]
+    MismatchedHash(String, String),
+    #[error("Execution payload block hash {payload} doesn't match block hash {block}")]
+    MismatchedBlockHash { payload: String, block: String },
+    #[error("Execution payload has {payload} transactions, block has {block}")]
+    TransactionCount { payload: usize, block: usize },
+    #[error("Transaction {0} doesn't match the execution payload")]
+    MismatchedTransaction(usize),
+    #[error("Withdrawals root {payload} of the execution payload doesn't match header withdrawals root {header}")]
+    MismatchedWithdrawalsRoot { payload: String, header: String },
+}
@@ -0,0 +1,574 @@
+pub mod error;
+mod ssz;
+
+use crate::beacon::error::BeaconError;
+use crate::error::DecodeError;
+use crate::headers::block_header_to_header;
+use crate::headers::error::BlockHeaderError;
+use crate::transactions::trace_to_signed;
+use crate::transactions::tx_type::map_tx_type;
+use crate::{handle_file, is_flat_file};
+use reth_primitives::proofs::calculate_withdrawals_root;
+use reth_primitives::{hex, Address, Bytes, TxType, Withdrawal, B256};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+use sf_protos::ethereum::r#type::v2::Block;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// `BeaconFork` is a consensus layer fork with an execution payload, which sets the layout of
+/// SSZ encoded beacon blocks.
+///
+/// Execution payloads of every fork are decoded, but blocks can only be partly verified from
+/// Deneb on, see [`verify_block`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BeaconFork {
+    Bellatrix,
+    /// Adds withdrawals to the execution payload
+    Capella,
+    /// Adds blob gas to the execution payload, and blob transactions
+    Deneb,
+    /// Adds execution requests to the beacon block body, committed to by the execution header
+    Electra,
+}
+
+impl fmt::Display for BeaconFork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BeaconFork::Bellatrix => "bellatrix",
+            BeaconFork::Capella => "capella",
+            BeaconFork::Deneb => "deneb",
+            BeaconFork::Electra => "electra",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BeaconFork {
+    type Err = BeaconError;
+
+    /// Parses the fork names of the `version` field and `Eth-Consensus-Version` header of the beacon API
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bellatrix" => Ok(BeaconFork::Bellatrix),
+            "capella" => Ok(BeaconFork::Capella),
+            "deneb" => Ok(BeaconFork::Deneb),
+            "electra" => Ok(BeaconFork::Electra),
+            _ => Err(BeaconError::UnknownFork(s.to_string())),
+        }
+    }
+}
+
+/// `ExecutionPayload` holds the fields of the execution payload of a beacon block needed to
+/// verify the execution block it commits to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPayload {
+    /// Fork of the beacon block holding the payload
+    pub fork: BeaconFork,
+    pub parent_hash: B256,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// Transactions in their EIP-2718 encoding
+    pub transactions: Vec<Bytes>,
+    /// Withdrawals, from the Capella fork on
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl ExecutionPayload {
+    /// Reads the execution payload of a beacon block stored in `path`.
+    ///
+    /// `.json` files hold either a `/eth/v2/beacon/blocks/{block_id}` response or a bare
+    /// `SignedBeaconBlock`, any other file is read as an SSZ encoded `SignedBeaconBlock` of `fork`.
+    pub fn read(path: &Path, fork: Option<BeaconFork>) -> Result<Self, BeaconError> {
+        let bytes = fs::read(path)?;
+        if path.extension() == Some("json".as_ref()) {
+            Self::from_json(&bytes)
+        } else {
+            Self::from_ssz(&bytes, fork.ok_or(BeaconError::MissingFork)?)
+        }
+    }
+
+    /// Decodes the execution payload of a JSON beacon block
+    pub fn from_json(bytes: &[u8]) -> Result<Self, BeaconError> {
+        let signed_block = match serde_json::from_slice::<JsonBeaconBlock>(bytes)? {
+            JsonBeaconBlock::Response { data } => data,
+            JsonBeaconBlock::Signed(signed_block) => signed_block,
+        };
+        Ok(signed_block.message.body.into())
+    }
+
+    /// Decodes the execution payload of an SSZ encoded `SignedBeaconBlock` of `fork`
+    pub fn from_ssz(bytes: &[u8], fork: BeaconFork) -> Result<Self, BeaconError> {
+        ssz::decode_signed_beacon_block(bytes, fork)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonBeaconBlock {
+    Response { data: JsonSignedBeaconBlock },
+    Signed(JsonSignedBeaconBlock),
+}
+
+#[derive(Deserialize)]
+struct JsonSignedBeaconBlock {
+    message: JsonMessage,
+}
+
+#[derive(Deserialize)]
+struct JsonMessage {
+    body: JsonBody,
+}
+
+#[derive(Deserialize)]
+struct JsonBody {
+    execution_payload: JsonExecutionPayload,
+    /// From the Electra fork on
+    execution_requests: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct JsonExecutionPayload {
+    parent_hash: B256,
+    #[serde(deserialize_with = "quoted")]
+    block_number: u64,
+    block_hash: B256,
+    transactions: Vec<Bytes>,
+    withdrawals: Option<Vec<JsonWithdrawal>>,
+    /// From the Deneb fork on
+    blob_gas_used: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct JsonWithdrawal {
+    #[serde(deserialize_with = "quoted")]
+    index: u64,
+    #[serde(deserialize_with = "quoted")]
+    validator_index: u64,
+    address: Address,
+    #[serde(deserialize_with = "quoted")]
+    amount: u64,
+}
+
+impl From<JsonBody> for ExecutionPayload {
+    /// The fork of the block is told by the fields its body and payload hold
+    fn from(body: JsonBody) -> Self {
+        let payload = body.execution_payload;
+        let fork = if body.execution_requests.is_some() {
+            BeaconFork::Electra
+        } else if payload.blob_gas_used.is_some() {
+            BeaconFork::Deneb
+        } else if payload.withdrawals.is_some() {
+            BeaconFork::Capella
+        } else {
+            BeaconFork::Bellatrix
+        };
+        Self {
+            fork,
+            parent_hash: payload.parent_hash,
+            block_number: payload.block_number,
+            block_hash: payload.block_hash,
+            transactions: payload.transactions,
+            withdrawals: payload.withdrawals.map(|withdrawals| {
+                withdrawals
+                    .into_iter()
+                    .map(|withdrawal| Withdrawal {
+                        index: withdrawal.index,
+                        validator_index: withdrawal.validator_index,
+                        address: withdrawal.address,
+                        amount: withdrawal.amount,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// The beacon API encodes integers as decimal strings
+fn quoted<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+/// Verifies a flat file block against the execution payload of a trusted beacon block.
+///
+/// The header must hash to the block hash, which must be the payload's block hash. Transactions
+/// must match the payload's one by one, and the withdrawals root of the header must be the root
+/// of the payload's withdrawals, as flat files don't hold withdrawals themselves.
+///
+/// Blocks from the Electra fork on, whose header commits to execution requests Firehose headers
+/// don't hold, and blocks with blob transactions, which aren't decoded, fail with
+/// [`BeaconError::UnsupportedFork`].
+pub fn verify_block(payload: &ExecutionPayload, block: &Block) -> Result<(), BeaconError> {
+    if payload.fork == BeaconFork::Electra {
+        return Err(BeaconError::UnsupportedFork(
+            payload.fork,
+            "headers don't hold the execution requests hash",
+        ));
+    }
+    if block
+        .transaction_traces
+        .iter()
+        .any(|trace| matches!(map_tx_type(&trace.r#type), Ok(TxType::Eip4844)))
+    {
+        return Err(BeaconError::UnsupportedFork(
+            payload.fork,
+            "blob transactions aren't decoded",
+        ));
+    }
+
+    let block_header = block
+        .header
+        .as_ref()
+        .ok_or(BlockHeaderError::MissingHeader)?;
+    let header = block_header_to_header(block_header)?;
+    let hash = header.hash_slow();
+    if hash.as_slice() != block.hash.as_slice() {
+        return Err(BeaconError::MismatchedHash(
+            hex::encode(hash),
+            hex::encode(&block.hash),
+        ));
+    }
+    if payload.block_hash != hash {
+        return Err(BeaconError::MismatchedBlockHash {
+            payload: payload.block_hash.to_string(),
+            block: hash.to_string(),
+        });
+    }
+
+    if payload.transactions.len() != block.transaction_traces.len() {
+        return Err(BeaconError::TransactionCount {
+            payload: payload.transactions.len(),
+            block: block.transaction_traces.len(),
+        });
+    }
+    for (index, (encoded, trace)) in payload
+        .transactions
+        .iter()
+        .zip(&block.transaction_traces)
+        .enumerate()
+    {
+        if trace_to_signed(trace)?.envelope_encoded() != *encoded {
+            return Err(BeaconError::MismatchedTransaction(index));
+        }
+    }
+
+    let withdrawals_root = payload
+        .withdrawals
+        .as_deref()
+        .map(calculate_withdrawals_root);
+    if withdrawals_root != header.withdrawals_root {
+        return Err(BeaconError::MismatchedWithdrawalsRoot {
+            payload: format!("{:?}", withdrawals_root),
+            header: format!("{:?}", header.withdrawals_root),
+        });
+    }
+
+    Ok(())
+}
+
+/// `BeaconReport` lists the results of verifying flat files against beacon blocks.
+#[derive(Debug, Default)]
+pub struct BeaconReport {
+    /// Number of blocks verified
+    pub blocks: usize,
+    /// Blocks without a beacon block to verify them with
+    pub missing: Vec<u64>,
+    /// Blocks which failed verification
+    pub failures: Vec<(u64, BeaconError)>,
+}
+
+impl BeaconReport {
+    /// Whether every block had a beacon block and matched it
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.failures.is_empty()
+    }
+}
+
+/// Verifies the blocks of the flat files of `flat_dir` against the beacon blocks of `beacon_dir`.
+///
+/// Beacon blocks are matched to flat file blocks by the block number of their execution payload.
+///
+/// # Arguments
+///
+/// * `beacon_dir`: folder of trusted beacon blocks, see [`ExecutionPayload::read`]
+/// * `flat_dir`: folder of the flat files to verify
+/// * `fork`: fork of the SSZ encoded beacon blocks, if any
+pub fn verify_dir(
+    beacon_dir: &Path,
+    flat_dir: &Path,
+    fork: Option<BeaconFork>,
+) -> Result<BeaconReport, DecodeError> {
+    let mut payloads = HashMap::new();
+    for entry in fs::read_dir(beacon_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let payload = ExecutionPayload::read(&path, fork)?;
+        payloads.insert(payload.block_number, payload);
+    }
+
+    let mut flat_paths = fs::read_dir(flat_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    flat_paths.retain(|path| is_flat_file(path));
+    flat_paths.sort();
+
+    let mut report = BeaconReport::default();
+    for path in flat_paths {
+        for block in handle_file(&path, None, None, None)? {
+            report.blocks += 1;
+            match payloads.get(&block.number) {
+                Some(payload) => {
+                    if let Err(err) = verify_block(payload, &block) {
+                        report.failures.push((block.number, err));
+                    }
+                }
+                None => report.missing.push(block.number),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error::BeaconError;
+    use super::{verify_block, BeaconFork, ExecutionPayload};
+    use crate::handle_file;
+    use crate::transactions::trace_to_signed;
+    use reth_primitives::{Address, Bytes, Withdrawal, B256};
+    use sf_protos::ethereum::r#type::v2::Block;
+    use std::path::PathBuf;
+
+    fn example_block() -> Block {
+        handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None)
+            .unwrap()
+            .remove(0)
+    }
+
+    fn payload_of(block: &Block) -> ExecutionPayload {
+        let header = block.header.as_ref().unwrap();
+        ExecutionPayload {
+            fork: BeaconFork::Capella,
+            parent_hash: B256::from_slice(&header.parent_hash),
+            block_number: block.number,
+            block_hash: B256::from_slice(&block.hash),
+            transactions: block
+                .transaction_traces
+                .iter()
+                .map(|trace| trace_to_signed(trace).unwrap().envelope_encoded())
+                .collect(),
+            withdrawals: Some(Vec::new()),
+        }
+    }
+
+    /// Encodes a Capella or Deneb `SignedBeaconBlock`, following the fork of `payload`, with an
+    /// empty body besides its execution payload
+    fn signed_block_ssz(payload: &ExecutionPayload) -> Vec<u8> {
+        // Deneb adds blob gas used and excess blob gas to the fixed part of the payload, and the
+        // offset of the blob KZG commitments to the body
+        let (payload_size, body_size) = match payload.fork {
+            BeaconFork::Capella => (512, 388),
+            BeaconFork::Deneb => (528, 392),
+            fork => panic!("Unsupported fork {}", fork),
+        };
+
+        let mut transactions = Vec::new();
+        let mut offset = 4 * payload.transactions.len();
+        for transaction in &payload.transactions {
+            transactions.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += transaction.len();
+        }
+        for transaction in &payload.transactions {
+            transactions.extend_from_slice(transaction);
+        }
+        let mut withdrawals = Vec::new();
+        for withdrawal in payload.withdrawals.as_deref().unwrap_or_default() {
+            withdrawals.extend_from_slice(&withdrawal.index.to_le_bytes());
+            withdrawals.extend_from_slice(&withdrawal.validator_index.to_le_bytes());
+            withdrawals.extend_from_slice(withdrawal.address.as_slice());
+            withdrawals.extend_from_slice(&withdrawal.amount.to_le_bytes());
+        }
+
+        let mut execution_payload = vec![0u8; payload_size];
+        execution_payload[..32].copy_from_slice(payload.parent_hash.as_slice());
+        execution_payload[404..412].copy_from_slice(&payload.block_number.to_le_bytes());
+        execution_payload[436..440].copy_from_slice(&(payload_size as u32).to_le_bytes());
+        execution_payload[472..504].copy_from_slice(payload.block_hash.as_slice());
+        execution_payload[504..508].copy_from_slice(&(payload_size as u32).to_le_bytes());
+        let withdrawals_offset = (payload_size + transactions.len()) as u32;
+        execution_payload[508..512].copy_from_slice(&withdrawals_offset.to_le_bytes());
+        if payload.fork == BeaconFork::Deneb {
+            execution_payload[512..520].copy_from_slice(&131072u64.to_le_bytes());
+            execution_payload[520..528].copy_from_slice(&262144u64.to_le_bytes());
+        }
+        execution_payload.extend_from_slice(&transactions);
+        execution_payload.extend_from_slice(&withdrawals);
+
+        let mut body = vec![0u8; body_size];
+        for position in (200..220).step_by(4) {
+            body[position..position + 4].copy_from_slice(&(body_size as u32).to_le_bytes());
+        }
+        body[380..384].copy_from_slice(&(body_size as u32).to_le_bytes());
+        let changes_offset = (body_size + execution_payload.len()) as u32;
+        body[384..388].copy_from_slice(&changes_offset.to_le_bytes());
+        if payload.fork == BeaconFork::Deneb {
+            // no BLS to execution changes, the commitments start where they would
+            body[388..392].copy_from_slice(&changes_offset.to_le_bytes());
+        }
+        body.extend_from_slice(&execution_payload);
+
+        let mut message = vec![0u8; 84];
+        message[80..84].copy_from_slice(&84u32.to_le_bytes());
+        message.extend_from_slice(&body);
+
+        let mut signed_block = vec![0u8; 100];
+        signed_block[..4].copy_from_slice(&100u32.to_le_bytes());
+        signed_block.extend_from_slice(&message);
+        signed_block
+    }
+
+    #[test]
+    fn test_payload_ssz() {
+        let mut payload = payload_of(&example_block());
+        payload.withdrawals = Some(vec![Withdrawal {
+            index: 1,
+            validator_index: 2,
+            address: Address::repeat_byte(3),
+            amount: 4,
+        }]);
+
+        let decoded = ExecutionPayload::from_ssz(&signed_block_ssz(&payload), BeaconFork::Capella);
+        assert_eq!(decoded.unwrap(), payload);
+        assert!(ExecutionPayload::from_ssz(
+            &signed_block_ssz(&payload)[..600],
+            BeaconFork::Capella
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_payload_ssz_deneb() {
+        let mut payload = payload_of(&example_block());
+        payload.fork = BeaconFork::Deneb;
+        payload.withdrawals = Some(vec![Withdrawal {
+            index: 1,
+            validator_index: 2,
+            address: Address::repeat_byte(3),
+            amount: 4,
+        }]);
+
+        // the blob gas fields after the withdrawals offset are skipped, transactions and
+        // withdrawals being read from their offsets
+        let decoded = ExecutionPayload::from_ssz(&signed_block_ssz(&payload), BeaconFork::Deneb);
+        assert_eq!(decoded.unwrap(), payload);
+    }
+
+    #[test]
+    fn test_payload_json() {
+        let json = r#"{
+            "version": "capella",
+            "data": {
+                "message": {
+                    "slot": "1",
+                    "body": {
+                        "execution_payload": {
+                            "parent_hash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+                            "block_number": "17686312",
+                            "block_hash": "0x0202020202020202020202020202020202020202020202020202020202020202",
+                            "transactions": ["0x02f0"],
+                            "withdrawals": [{
+                                "index": "1",
+                                "validator_index": "2",
+                                "address": "0x0303030303030303030303030303030303030303",
+                                "amount": "4"
+                            }]
+                        }
+                    }
+                },
+                "signature": "0x"
+            }
+        }"#;
+
+        let payload = ExecutionPayload::from_json(json.as_bytes()).unwrap();
+        assert_eq!(payload.fork, BeaconFork::Capella);
+        assert_eq!(payload.block_number, 17686312);
+        assert_eq!(payload.block_hash, B256::repeat_byte(2));
+        assert_eq!(payload.transactions, vec![Bytes::from(vec![0x02, 0xf0])]);
+        assert_eq!(
+            payload.withdrawals.unwrap()[0].address,
+            Address::repeat_byte(3)
+        );
+    }
+
+    #[test]
+    fn test_payload_json_fork() {
+        let signed_block = |execution_requests: &str| {
+            format!(
+                r#"{{
+                    "message": {{
+                        "body": {{
+                            "execution_payload": {{
+                                "parent_hash": "0x0101010101010101010101010101010101010101010101010101010101010101",
+                                "block_number": "19426587",
+                                "block_hash": "0x0202020202020202020202020202020202020202020202020202020202020202",
+                                "transactions": [],
+                                "withdrawals": [],
+                                "blob_gas_used": "131072",
+                                "excess_blob_gas": "0"
+                            }}{}
+                        }}
+                    }}
+                }}"#,
+                execution_requests
+            )
+        };
+
+        let deneb = ExecutionPayload::from_json(signed_block("").as_bytes()).unwrap();
+        assert_eq!(deneb.fork, BeaconFork::Deneb);
+        let electra = signed_block(
+            r#", "execution_requests": {"deposits": [], "withdrawals": [], "consolidations": []}"#,
+        );
+        let electra = ExecutionPayload::from_json(electra.as_bytes()).unwrap();
+        assert_eq!(electra.fork, BeaconFork::Electra);
+    }
+
+    #[test]
+    fn test_verify_block() {
+        let block = example_block();
+        let payload = payload_of(&block);
+
+        // the example block has withdrawals, flat files only hold their root
+        assert!(matches!(
+            verify_block(&payload, &block),
+            Err(BeaconError::MismatchedWithdrawalsRoot { .. })
+        ));
+
+        let mut wrong_transaction = payload.clone();
+        wrong_transaction.transactions[0] = Bytes::from(vec![0x02]);
+        assert!(matches!(
+            verify_block(&wrong_transaction, &block),
+            Err(BeaconError::MismatchedTransaction(0))
+        ));
+
+        let mut wrong_hash = payload.clone();
+        wrong_hash.block_hash = B256::ZERO;
+        assert!(matches!(
+            verify_block(&wrong_hash, &block),
+            Err(BeaconError::MismatchedBlockHash { .. })
+        ));
+
+        let mut electra = payload;
+        electra.fork = BeaconFork::Electra;
+        assert!(matches!(
+            verify_block(&electra, &block),
+            Err(BeaconError::UnsupportedFork(BeaconFork::Electra, _))
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+//! Decoding of the execution payload of SSZ encoded `SignedBeaconBlock`s, as defined by the
+//! [consensus specs](https://github.com/ethereum/consensus-specs). Only the fields needed to
+//! verify execution blocks are read, everything else is skipped over with the SSZ offsets.
+
+use crate::beacon::error::BeaconError;
+use crate::beacon::{BeaconFork, ExecutionPayload};
+use reth_primitives::{Address, Bytes, Withdrawal, B256};
+
+/// Offset of `SignedBeaconBlock.message`, followed by the 96 bytes signature
+const MESSAGE_OFFSET: usize = 0;
+/// Offset of `BeaconBlock.body`, after slot, proposer index, parent root and state root
+const BODY_OFFSET: usize = 80;
+/// Offset of `BeaconBlockBody.execution_payload`, after randao reveal, eth1 data, graffiti,
+/// the offsets of the five operation lists and the sync aggregate
+const PAYLOAD_OFFSET: usize = 380;
+
+const PARENT_HASH: usize = 0;
+const BLOCK_NUMBER: usize = 404;
+const BLOCK_HASH: usize = 472;
+const TRANSACTIONS_OFFSET: usize = 504;
+/// From Deneb on, the fixed blob gas used and excess blob gas follow the withdrawals offset,
+/// and withdrawals remain the last variable sized field of the payload
+const WITHDRAWALS_OFFSET: usize = 508;
+
+/// Size of an SSZ `Withdrawal`: index, validator index, address and amount
+const WITHDRAWAL_SIZE: usize = 44;
+
+pub(crate) fn decode_signed_beacon_block(
+    bytes: &[u8],
+    fork: BeaconFork,
+) -> Result<ExecutionPayload, BeaconError> {
+    let message = slice(bytes, offset(bytes, MESSAGE_OFFSET)?, bytes.len())?;
+    let body = slice(message, offset(message, BODY_OFFSET)?, message.len())?;
+
+    let payload_start = offset(body, PAYLOAD_OFFSET)?;
+    // from Capella on, the BLS to execution changes follow the execution payload, then
+    // the blob KZG commitments from Deneb on and the execution requests from Electra on
+    let payload_end = match fork {
+        BeaconFork::Bellatrix => body.len(),
+        _ => offset(body, PAYLOAD_OFFSET + 4)?,
+    };
+    let payload = slice(body, payload_start, payload_end)?;
+
+    let transactions_start = offset(payload, TRANSACTIONS_OFFSET)?;
+    let (transactions_end, withdrawals) = match fork {
+        BeaconFork::Bellatrix => (payload.len(), None),
+        _ => {
+            let withdrawals_start = offset(payload, WITHDRAWALS_OFFSET)?;
+            let withdrawals = slice(payload, withdrawals_start, payload.len())?;
+            (withdrawals_start, Some(decode_withdrawals(withdrawals)?))
+        }
+    };
+    let transactions = decode_transactions(slice(payload, transactions_start, transactions_end)?)?;
+
+    Ok(ExecutionPayload {
+        fork,
+        parent_hash: B256::from_slice(slice(payload, PARENT_HASH, PARENT_HASH + 32)?),
+        block_number: u64::from_le_bytes(fixed(payload, BLOCK_NUMBER)?),
+        block_hash: B256::from_slice(slice(payload, BLOCK_HASH, BLOCK_HASH + 32)?),
+        transactions,
+        withdrawals,
+    })
+}
+
+/// Decodes a `List[Transaction]`, transactions being variable sized byte lists
+fn decode_transactions(bytes: &[u8]) -> Result<Vec<Bytes>, BeaconError> {
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let first = offset(bytes, 0)?;
+    if !first.is_multiple_of(4) || first == 0 {
+        return Err(BeaconError::InvalidSsz(
+            "invalid transactions offset".to_string(),
+        ));
+    }
+
+    let offsets = (0..first / 4)
+        .map(|index| offset(bytes, index * 4))
+        .chain(std::iter::once(Ok(bytes.len())))
+        .collect::<Result<Vec<usize>, _>>()?;
+    offsets
+        .windows(2)
+        .map(|window| slice(bytes, window[0], window[1]).map(Bytes::copy_from_slice))
+        .collect()
+}
+
+fn decode_withdrawals(bytes: &[u8]) -> Result<Vec<Withdrawal>, BeaconError> {
+    if !bytes.len().is_multiple_of(WITHDRAWAL_SIZE) {
+        return Err(BeaconError::InvalidSsz("invalid withdrawals".to_string()));
+    }
+    bytes
+        .chunks(WITHDRAWAL_SIZE)
+        .map(|withdrawal| {
+            Ok(Withdrawal {
+                index: u64::from_le_bytes(fixed(withdrawal, 0)?),
+                validator_index: u64::from_le_bytes(fixed(withdrawal, 8)?),
+                address: Address::from_slice(slice(withdrawal, 16, 36)?),
+                amount: u64::from_le_bytes(fixed(withdrawal, 36)?),
+            })
+        })
+        .collect()
+}
+
+/// Reads the 4 bytes offset of a variable sized field at `position`
+fn offset(bytes: &[u8], position: usize) -> Result<usize, BeaconError> {
+    Ok(u32::from_le_bytes(fixed(bytes, position)?) as usize)
+}
+
+fn fixed<const N: usize>(bytes: &[u8], position: usize) -> Result<[u8; N], BeaconError> {
+    slice(bytes, position, position + N)?
+        .try_into()
+        .map_err(|_| BeaconError::InvalidSsz(format!("expected {} bytes", N)))
+}
+
+fn slice(bytes: &[u8], start: usize, end: usize) -> Result<&[u8], BeaconError> {
+    bytes.get(start..end).ok_or_else(|| {
+        BeaconError::InvalidSsz(format!(
+            "range {}..{} out of {} bytes",
+            start,
+            end,
+            bytes.len()
+        ))
+    })
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EnvelopeError {
+    #[error("Mismatched block number: envelope {0} != block {1}")]
+    MismatchedNumber(u64, u64),
+    #[error("Mismatched block id: envelope {0} != block {1}")]
+    MismatchedId(String, String),
+    #[error("Mismatched parent id: envelope {0} != block {1}")]
+    MismatchedParentId(String, String),
+    #[error("Missing header")]
+    MissingHeader,
+}
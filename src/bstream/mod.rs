@@ -0,0 +1,149 @@
+pub mod error;
+
+use crate::bstream::error::EnvelopeError;
+use crate::dbin::DbinReader;
+use crate::error::DecodeError;
+use bytes::Bytes;
+use prost::Message;
+use prost_types::Timestamp;
+use reth_primitives::hex;
+use sf_protos::ethereum::r#type::v2::Block;
+use std::io::Read;
+
+/// `BlockEnvelope` is the `sf.bstream.v1.Block` message that wraps every block stored in a flat file.
+///
+/// It mirrors [`sf_protos::bstream::v1::Block`], but keeps `payload_buffer` as [`Bytes`] so that,
+/// when decoded from a [`Bytes`] buffer, the payload is a view into that buffer instead of a copy.
+/// Fields not needed by the decoder are skipped while decoding.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockEnvelope {
+    #[prost(uint64, tag = "1")]
+    pub number: u64,
+    /// Block hash, hex encoded without `0x` prefix
+    #[prost(string, tag = "2")]
+    pub id: String,
+    /// Parent block hash, hex encoded without `0x` prefix
+    #[prost(string, tag = "3")]
+    pub previous_id: String,
+    #[prost(message, optional, tag = "4")]
+    pub timestamp: Option<Timestamp>,
+    /// Last irreversible block number when the block was produced
+    #[prost(uint64, tag = "5")]
+    pub lib_num: u64,
+    /// `sf.bstream.v1.Protocol` of the payload, `ETH` is 1
+    #[prost(int32, tag = "6")]
+    pub payload_kind: i32,
+    #[prost(int32, tag = "7")]
+    pub payload_version: i32,
+    /// Serialized chain-specific block, e.g. [`sf_protos::ethereum::r#type::v2::Block`]
+    #[prost(bytes = "bytes", tag = "8")]
+    pub payload_buffer: Bytes,
+    /// Firehose v2 payload, superseding `payload_kind` and `payload_buffer` when set
+    #[prost(message, optional, tag = "11")]
+    pub payload: Option<EnvelopePayload>,
+}
+
+/// `EnvelopePayload` is the `google.protobuf.Any` payload of a Firehose v2 [`BlockEnvelope`],
+/// with its value kept as [`Bytes`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EnvelopePayload {
+    /// Content type of the payload, like `type.googleapis.com/sf.ethereum.type.v2.Block`
+    #[prost(string, tag = "1")]
+    pub type_url: String,
+    #[prost(bytes = "bytes", tag = "2")]
+    pub value: Bytes,
+}
+
+impl BlockEnvelope {
+    /// Takes the serialized chain-specific block out of the envelope, leaving it empty.
+    ///
+    /// Reads the Firehose v2 `payload` if present, `payload_buffer` otherwise.
+    pub fn take_payload(&mut self) -> Bytes {
+        match self.payload.as_mut() {
+            Some(payload) => std::mem::take(&mut payload.value),
+            None => std::mem::take(&mut self.payload_buffer),
+        }
+    }
+
+    /// Checks that the envelope's number, id and parent id match the decoded payload.
+    ///
+    /// A mismatch means the envelope and its payload come from different blocks,
+    /// which happens when flat files were merged incorrectly.
+    pub fn validate(&self, block: &Block) -> Result<(), EnvelopeError> {
+        if self.number != block.number {
+            return Err(EnvelopeError::MismatchedNumber(self.number, block.number));
+        }
+
+        let block_id = hex::encode(&block.hash);
+        if !same_id(&self.id, &block_id) {
+            return Err(EnvelopeError::MismatchedId(self.id.clone(), block_id));
+        }
+
+        let header = block.header.as_ref().ok_or(EnvelopeError::MissingHeader)?;
+        let parent_id = hex::encode(&header.parent_hash);
+        if !same_id(&self.previous_id, &parent_id) {
+            return Err(EnvelopeError::MismatchedParentId(
+                self.previous_id.clone(),
+                parent_id,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn same_id(envelope_id: &str, block_id: &str) -> bool {
+    let envelope_id = envelope_id.strip_prefix("0x").unwrap_or(envelope_id);
+    envelope_id.eq_ignore_ascii_case(block_id)
+}
+
+/// `DecodedBlock` is a block decoded from a flat file along with its bstream envelope.
+///
+/// The envelope's payload is emptied once it is decoded into `block`, see [`BlockEnvelope::take_payload`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedBlock {
+    pub envelope: BlockEnvelope,
+    pub block: Block,
+}
+
+/// `RawBlocks` iterates over the bstream envelopes of a stream of flat files without decoding their payloads.
+///
+/// It accepts flat files of any content type, the payload of each block is left in the envelope.
+/// Created with [`read_raw_blocks`].
+pub struct RawBlocks<R> {
+    reader: DbinReader<R>,
+    done: bool,
+}
+
+/// Iterates over the raw bstream blocks of one or more concatenated flat files read from `reader`.
+pub fn read_raw_blocks<R: Read>(reader: R) -> RawBlocks<R> {
+    RawBlocks {
+        reader: DbinReader::new(reader),
+        done: false,
+    }
+}
+
+impl<R: Read> Iterator for RawBlocks<R> {
+    type Item = Result<BlockEnvelope, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.reader.next_message() {
+            Ok(Some(message)) => Some(
+                BlockEnvelope::decode(Bytes::from(message))
+                    .map_err(|err| DecodeError::ProtobufError(err.to_string())),
+            ),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err.into()))
+            }
+        }
+    }
+}
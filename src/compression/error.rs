@@ -0,0 +1,12 @@
+use crate::compression::Compression;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    #[error("Read error")]
+    ReadError(#[from] std::io::Error),
+    #[error("Compression {0} is not enabled, rebuild with the `{0}` feature")]
+    Unsupported(Compression),
+    #[error("Unknown compression: {0}")]
+    UnknownCompression(String),
+}
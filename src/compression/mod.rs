@@ -0,0 +1,259 @@
+pub mod error;
+
+use crate::compression::error::CompressionError;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::str::FromStr;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const XZ_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const LZ4_MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+/// `Compression` is the codec a flat file, or a stream of flat files, is compressed with.
+///
+/// `Zstd` is always available. `Gzip`, `Xz` and `Lz4` are only available when the crate is
+/// built with the `gzip`, `xz` and `lz4` features respectively.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain `.dbin` bytes
+    #[default]
+    None,
+    Zstd,
+    Gzip,
+    Xz,
+    Lz4,
+}
+
+impl Compression {
+    /// Detects the compression of a buffer by its leading magic bytes.
+    ///
+    /// Anything that doesn't start with a known magic, including a plain `dbin` header,
+    /// is assumed to be uncompressed.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(&XZ_MAGIC) {
+            Compression::Xz
+        } else if bytes.starts_with(&LZ4_MAGIC) {
+            Compression::Lz4
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Detects the compression of a reader by peeking at its buffer, without consuming it.
+    pub fn detect_from<R: BufRead>(reader: &mut R) -> Result<Self, CompressionError> {
+        let buf = reader.fill_buf()?;
+        Ok(Self::detect(buf))
+    }
+
+    /// File extension conventionally appended to `.dbin` for this compression, e.g. `zst`.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Zstd => Some("zst"),
+            Compression::Gzip => Some("gz"),
+            Compression::Xz => Some("xz"),
+            Compression::Lz4 => Some("lz4"),
+        }
+    }
+
+    /// Wraps a reader with the decoder for this compression.
+    ///
+    /// Multi-frame (or multi-member) inputs, such as concatenated compressed flat files,
+    /// are decoded as a single continuous stream.
+    pub fn decoder<'a, R: BufRead + 'a>(
+        self,
+        reader: R,
+    ) -> Result<Box<dyn Read + 'a>, CompressionError> {
+        match self {
+            Compression::None => Ok(Box::new(reader)),
+            Compression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::with_buffer(reader)?)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(flate2::bufread::MultiGzDecoder::new(reader))),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(Box::new(lz4_flex::frame::FrameDecoder::new(reader))),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(CompressionError::Unsupported(unsupported)),
+        }
+    }
+
+    /// Wraps a writer with the encoder for this compression.
+    ///
+    /// The compressed stream must be ended with [`Encoder::finish`].
+    pub fn encoder<W: Write>(self, writer: W) -> Result<Encoder<W>, CompressionError> {
+        match self {
+            Compression::None => Ok(Encoder::None(writer)),
+            Compression::Zstd => Ok(Encoder::Zstd(zstd::stream::write::Encoder::new(writer, 0)?)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Encoder::Xz(xz2::write::XzEncoder::new(writer, 6))),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(Encoder::Lz4(lz4_flex::frame::FrameEncoder::new(writer))),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(CompressionError::Unsupported(unsupported)),
+        }
+    }
+}
+
+/// `Encoder` compresses the bytes written to it into an inner writer, see [`Compression::encoder`].
+///
+/// The end of the compressed stream is only written by [`Encoder::finish`], so that its errors
+/// are reported. An encoder dropped without being finished leaves a truncated stream behind.
+pub enum Encoder<W: Write> {
+    None(W),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<W>),
+    #[cfg(feature = "lz4")]
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the compressed stream and flushes the inner writer, returning it
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            Encoder::None(writer) => writer,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.finish()?,
+            #[cfg(feature = "lz4")]
+            Encoder::Lz4(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.write(buf),
+            #[cfg(feature = "lz4")]
+            Encoder::Lz4(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.flush(),
+            #[cfg(feature = "lz4")]
+            Encoder::Lz4(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Lz4 => "lz4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Compression {
+    type Err = CompressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "xz" => Ok(Compression::Xz),
+            "lz4" => Ok(Compression::Lz4),
+            _ => Err(CompressionError::UnknownCompression(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use std::fs::File;
+    use std::io::{BufReader, Read, Write};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::detect(b"dbin\x00ETH00"), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+
+        let mut file = BufReader::new(File::open("tests/0000000000.dbin.zst").unwrap());
+        assert_eq!(
+            Compression::detect_from(&mut file).unwrap(),
+            Compression::Zstd
+        );
+    }
+
+    /// Compresses an example flat file, then detects its compression and decompresses it back
+    fn check_round_trip(compression: Compression) {
+        let mut original = Vec::new();
+        File::open("example0017686312.dbin")
+            .unwrap()
+            .read_to_end(&mut original)
+            .unwrap();
+
+        let mut encoder = compression.encoder(Vec::new()).unwrap();
+        encoder.write_all(&original).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(Compression::detect(&compressed), compression);
+
+        let mut decompressed = Vec::new();
+        Compression::detect(&compressed)
+            .decoder(compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, original);
+    }
+
+    #[test]
+    fn test_zstd_round_trip() {
+        check_round_trip(Compression::Zstd);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_round_trip() {
+        check_round_trip(Compression::Gzip);
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_xz_round_trip() {
+        check_round_trip(Compression::Xz);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_round_trip() {
+        check_round_trip(Compression::Lz4);
+    }
+}
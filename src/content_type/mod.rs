@@ -0,0 +1,191 @@
+use crate::bstream::BlockEnvelope;
+use crate::error::DecodeError;
+use crate::verify_block;
+use bytes::Bytes;
+use prost::Message;
+use sf_protos::ethereum::r#type::v2::Block;
+use std::collections::HashMap;
+
+/// Content type of Ethereum flat files with a version 0 dbin header
+pub const ETH_CONTENT_TYPE: &str = "ETH";
+/// Content type of Ethereum flat files with a version 1 dbin header, as written by Firehose v2
+pub const ETH_TYPE_URL: &str = "type.googleapis.com/sf.ethereum.type.v2.Block";
+
+/// Whether a dbin content type holds Ethereum blocks
+pub fn is_ethereum(content_type: &str) -> bool {
+    content_type == ETH_CONTENT_TYPE || content_type == ETH_TYPE_URL
+}
+
+/// `Payload` is the chain-specific block carried by a bstream envelope, as returned by a [`PayloadDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Ethereum(Box<Block>),
+    /// Payload left undecoded
+    Raw(Bytes),
+}
+
+/// `DecodedPayload` is a block envelope along with its payload decoded by a [`PayloadDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedPayload {
+    pub envelope: BlockEnvelope,
+    pub payload: Payload,
+}
+
+/// Decodes, and optionally verifies, the payloads of one content type.
+pub trait PayloadDecoder: Send + Sync {
+    fn decode(&self, envelope: &BlockEnvelope, payload: Bytes) -> Result<Payload, DecodeError>;
+}
+
+/// Decodes Ethereum blocks and checks them against their envelope, receipt root and transaction root.
+pub struct EthereumDecoder;
+
+impl PayloadDecoder for EthereumDecoder {
+    fn decode(&self, envelope: &BlockEnvelope, payload: Bytes) -> Result<Payload, DecodeError> {
+        let block =
+            Block::decode(payload).map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+        envelope.validate(&block)?;
+        let block = verify_block(block, None)?;
+
+        Ok(Payload::Ethereum(Box::new(block)))
+    }
+}
+
+/// Decodes blocks of chains reusing the Ethereum block proto, like Arbitrum or Optimism.
+///
+/// Blocks are only checked against their envelope: their chain-specific transaction
+/// types can't be mapped to compute the receipt and transaction roots.
+pub struct EthereumLikeDecoder;
+
+impl PayloadDecoder for EthereumLikeDecoder {
+    fn decode(&self, envelope: &BlockEnvelope, payload: Bytes) -> Result<Payload, DecodeError> {
+        let block =
+            Block::decode(payload).map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+        envelope.validate(&block)?;
+
+        Ok(Payload::Ethereum(Box::new(block)))
+    }
+}
+
+/// Leaves payloads undecoded.
+pub struct PassthroughDecoder;
+
+impl PayloadDecoder for PassthroughDecoder {
+    fn decode(&self, _envelope: &BlockEnvelope, payload: Bytes) -> Result<Payload, DecodeError> {
+        Ok(Payload::Raw(payload))
+    }
+}
+
+/// `ContentTypeRegistry` maps the content type found in dbin headers to the [`PayloadDecoder`] of its blocks.
+///
+/// The default registry decodes and verifies Ethereum flat files and rejects any other content type.
+/// Decoders for other chains can be plugged in with [`ContentTypeRegistry::register`], and
+/// [`ContentTypeRegistry::with_fallback`] sets the decoder of content types with no registered decoder,
+/// e.g. a [`PassthroughDecoder`] to iterate over the raw blocks of any flat file.
+pub struct ContentTypeRegistry {
+    decoders: HashMap<String, Box<dyn PayloadDecoder>>,
+    fallback: Option<Box<dyn PayloadDecoder>>,
+}
+
+impl ContentTypeRegistry {
+    /// A registry without any decoder
+    pub fn empty() -> Self {
+        Self {
+            decoders: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// A registry leaving the payloads of every content type undecoded
+    pub fn passthrough() -> Self {
+        Self::empty().with_fallback(PassthroughDecoder)
+    }
+
+    /// Registers the decoder of a content type, replacing any previously registered one.
+    pub fn register<D: PayloadDecoder + 'static>(
+        &mut self,
+        content_type: impl Into<String>,
+        decoder: D,
+    ) -> &mut Self {
+        self.decoders.insert(content_type.into(), Box::new(decoder));
+        self
+    }
+
+    /// Sets the decoder used for content types without a registered decoder.
+    pub fn with_fallback<D: PayloadDecoder + 'static>(mut self, decoder: D) -> Self {
+        self.fallback = Some(Box::new(decoder));
+        self
+    }
+
+    /// Returns the decoder of a content type.
+    pub fn decoder(&self, content_type: &str) -> Result<&dyn PayloadDecoder, DecodeError> {
+        self.decoders
+            .get(content_type)
+            .or(self.fallback.as_ref())
+            .map(|decoder| decoder.as_ref())
+            .ok_or_else(|| DecodeError::InvalidContentType(content_type.to_string()))
+    }
+}
+
+impl Default for ContentTypeRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register(ETH_CONTENT_TYPE, EthereumDecoder)
+            .register(ETH_TYPE_URL, EthereumDecoder);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentTypeRegistry, EthereumLikeDecoder, Payload, ETH_CONTENT_TYPE};
+    use crate::bstream::read_raw_blocks;
+    use crate::error::DecodeError;
+    use crate::{handle_bytes_with_registry, read_file_bytes};
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_unknown_content_type() {
+        let registry = ContentTypeRegistry::default();
+        assert!(registry.decoder(ETH_CONTENT_TYPE).is_ok());
+        assert!(matches!(
+            registry.decoder("EOS"),
+            Err(DecodeError::InvalidContentType(content_type)) if content_type == "EOS"
+        ));
+        assert!(ContentTypeRegistry::passthrough().decoder("EOS").is_ok());
+    }
+
+    #[test]
+    fn test_handle_bytes_with_registry() {
+        let bytes = read_file_bytes(&PathBuf::from("example0017686312.dbin")).unwrap();
+
+        let blocks =
+            handle_bytes_with_registry(bytes.clone(), None, &ContentTypeRegistry::default())
+                .unwrap();
+        assert!(matches!(&blocks[0].payload, Payload::Ethereum(block) if block.number == 17686312));
+
+        let mut registry = ContentTypeRegistry::empty();
+        registry.register(ETH_CONTENT_TYPE, EthereumLikeDecoder);
+        let blocks = handle_bytes_with_registry(bytes.clone(), None, &registry).unwrap();
+        assert!(matches!(&blocks[0].payload, Payload::Ethereum(_)));
+
+        let raw =
+            handle_bytes_with_registry(bytes, None, &ContentTypeRegistry::passthrough()).unwrap();
+        assert!(matches!(&raw[0].payload, Payload::Raw(payload) if !payload.is_empty()));
+        assert_eq!(raw[0].envelope.number, 17686312);
+    }
+
+    #[test]
+    fn test_read_raw_blocks() {
+        let reader = BufReader::new(File::open("example-create-17686085.dbin").unwrap());
+
+        let envelopes = read_raw_blocks(reader)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(envelopes[0].number, 17686085);
+        assert!(!envelopes[0].payload_buffer.is_empty());
+    }
+}
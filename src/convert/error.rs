@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConvertError {
+    #[error("Invalid {field}: {value}")]
+    InvalidField { field: &'static str, value: String },
+    #[error("Missing {0}")]
+    MissingField(&'static str),
+}
@@ -0,0 +1,107 @@
+pub mod error;
+
+use crate::convert::error::ConvertError;
+use alloy_primitives::{hex, Address, Bloom, B256, U256};
+use sf_protos::ethereum::r#type::v2::{BigInt, BlockHeader};
+
+/// Converts a 32 bytes field of a Firehose block, like a hash or a root
+pub fn to_b256(field: &'static str, bytes: &[u8]) -> Result<B256, ConvertError> {
+    let slice: [u8; 32] = bytes.try_into().map_err(|_| invalid_field(field, bytes))?;
+    Ok(B256::from(slice))
+}
+
+/// Same as [`to_b256`] for post-fork header fields, which are empty in blocks before the fork
+pub fn optional_b256(field: &'static str, bytes: &[u8]) -> Result<Option<B256>, ConvertError> {
+    if bytes.is_empty() {
+        Ok(None)
+    } else {
+        to_b256(field, bytes).map(Some)
+    }
+}
+
+pub fn to_address(field: &'static str, bytes: &[u8]) -> Result<Address, ConvertError> {
+    let slice: [u8; 20] = bytes.try_into().map_err(|_| invalid_field(field, bytes))?;
+    Ok(Address::from(slice))
+}
+
+pub fn to_bloom(field: &'static str, bytes: &[u8]) -> Result<Bloom, ConvertError> {
+    let slice: [u8; 256] = bytes.try_into().map_err(|_| invalid_field(field, bytes))?;
+    Ok(Bloom::from(slice))
+}
+
+/// Converts a Firehose big-endian integer, which is `0` when missing
+pub fn bigint_to_u256(field: &'static str, value: Option<&BigInt>) -> Result<U256, ConvertError> {
+    match value {
+        Some(value) => {
+            U256::try_from_be_slice(&value.bytes).ok_or_else(|| invalid_field(field, &value.bytes))
+        }
+        None => Ok(U256::ZERO),
+    }
+}
+
+/// The timestamp of a Firehose block header in seconds, which every header has
+pub fn timestamp(header: &BlockHeader) -> Result<u64, ConvertError> {
+    header
+        .timestamp
+        .as_ref()
+        .map(|timestamp| timestamp.seconds as u64)
+        .ok_or(ConvertError::MissingField("timestamp"))
+}
+
+fn invalid_field(field: &'static str, bytes: &[u8]) -> ConvertError {
+    ConvertError::InvalidField {
+        field,
+        value: hex::encode(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bigint_to_u256, optional_b256, timestamp, to_address, to_b256, to_bloom};
+    use crate::convert::error::ConvertError;
+    use alloy_primitives::U256;
+    use sf_protos::ethereum::r#type::v2::{BigInt, BlockHeader};
+
+    #[test]
+    fn test_fixed_size_fields() {
+        assert!(to_b256("hash", &[1; 32]).is_ok());
+        assert_eq!(optional_b256("withdrawals_root", &[]).unwrap(), None);
+        assert!(to_address("coinbase", &[1; 20]).is_ok());
+        assert!(to_bloom("logs_bloom", &[0; 256]).is_ok());
+
+        assert!(matches!(
+            to_bloom("logs_bloom", &[0; 32]),
+            Err(ConvertError::InvalidField {
+                field: "logs_bloom",
+                ..
+            })
+        ));
+        assert!(matches!(
+            to_address("coinbase", &[1; 32]),
+            Err(ConvertError::InvalidField {
+                field: "coinbase",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_bigint_to_u256() {
+        let value = BigInt { bytes: vec![1, 0] };
+        assert_eq!(
+            bigint_to_u256("difficulty", Some(&value)).unwrap(),
+            U256::from(256)
+        );
+        assert_eq!(bigint_to_u256("difficulty", None).unwrap(), U256::ZERO);
+        let value = BigInt { bytes: vec![1; 33] };
+        assert!(bigint_to_u256("difficulty", Some(&value)).is_err());
+    }
+
+    #[test]
+    fn test_missing_timestamp() {
+        assert!(matches!(
+            timestamp(&BlockHeader::default()),
+            Err(ConvertError::MissingField("timestamp"))
+        ));
+    }
+}
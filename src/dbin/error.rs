@@ -2,29 +2,20 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum DbinFileError {
-    #[error("Incorrect dbin bytes")]
-    InvalidDBINBytes,
-    #[error("Read error")]
+    #[error("Incorrect dbin bytes, expected the `dbin` magic bytes at offset {0}")]
+    InvalidDBINBytes(u64),
+    #[error("Read error: {0}")]
     ReadError(#[from] std::io::Error),
     #[error("Invalid UTF8")]
     InvalidUTF8(#[from] std::string::FromUtf8Error),
-    #[error("Unsupported version")]
-    UnsupportedDBINVersion,
-    #[error("Start of new DBIN file")]
-    StartOfNewDBINFile,
+    #[error("Unsupported version: {0}")]
+    UnsupportedDBINVersion(u8),
     #[error("DBIN files with different versions")]
     DifferingDBINVersions,
-}
-
-impl DbinFileError {
-    pub fn kind(&self) -> std::io::ErrorKind {
-        match self {
-            DbinFileError::StartOfNewDBINFile => std::io::ErrorKind::Other,
-            DbinFileError::InvalidDBINBytes => todo!(),
-            DbinFileError::ReadError(_) => std::io::ErrorKind::UnexpectedEof,
-            DbinFileError::InvalidUTF8(_) => todo!(),
-            DbinFileError::UnsupportedDBINVersion => todo!(),
-            DbinFileError::DifferingDBINVersions => todo!(),
-        }
-    }
+    #[error("Truncated header at offset {0}")]
+    TruncatedHeader(u64),
+    #[error("Truncated message at offset {0}")]
+    TruncatedMessage(u64),
+    #[error("Message of {size} bytes at offset {offset} exceeds the maximum message size")]
+    MessageTooLarge { offset: u64, size: u64 },
 }
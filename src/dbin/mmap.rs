@@ -0,0 +1,74 @@
+use crate::dbin::error::DbinFileError;
+use crate::dbin::{DbinFile, DbinHeader, DbinReaderOptions};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+/// `DbinMmap` is a memory-mapped `.dbin` file whose messages are borrowed straight from the mapping.
+///
+/// The header and message boundaries are validated when the file is opened, afterwards
+/// messages are handed out as `&[u8]` slices without any read syscall or copy.
+pub struct DbinMmap {
+    pub header: DbinHeader,
+    mmap: Mmap,
+    /// Position of each message in `mmap`, excluding its 4 bytes length prefix
+    ranges: Vec<Range<usize>>,
+}
+
+impl DbinFile {
+    /// Memory-maps the `.dbin` file at `path`, see [`DbinMmap`].
+    ///
+    /// The file must not be modified or truncated while it is mapped.
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<DbinMmap, DbinFileError> {
+        Self::open_mmap_with_options(path, DbinReaderOptions::default())
+    }
+
+    /// Same as [`DbinFile::open_mmap`], with custom [`DbinReaderOptions`]
+    pub fn open_mmap_with_options<P: AsRef<Path>>(
+        path: P,
+        options: DbinReaderOptions,
+    ) -> Result<DbinMmap, DbinFileError> {
+        let file = File::open(path)?;
+        // SAFETY: flat files are immutable once written, callers must not modify them while mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (header, ranges) = Self::message_ranges(&mmap, options)?;
+
+        Ok(DbinMmap {
+            header,
+            mmap,
+            ranges,
+        })
+    }
+}
+
+impl DbinMmap {
+    /// Number of messages in the file
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the message at `index`, if any
+    pub fn message(&self, index: usize) -> Option<&[u8]> {
+        self.ranges
+            .get(index)
+            .map(|range| &self.mmap[range.clone()])
+    }
+
+    /// Iterates over the messages in file order
+    pub fn messages(&self) -> impl ExactSizeIterator<Item = &[u8]> + '_ {
+        self.ranges.iter().map(|range| &self.mmap[range.clone()])
+    }
+
+    /// Iterates over the messages in parallel with rayon
+    pub fn par_messages(&self) -> impl IndexedParallelIterator<Item = &[u8]> + '_ {
+        self.ranges
+            .par_iter()
+            .map(|range| &self.mmap[range.clone()])
+    }
+}
@@ -1,7 +1,16 @@
 pub mod error;
+pub mod mmap;
 
 use crate::dbin::error::DbinFileError;
-use std::io::Read;
+use bytes::Bytes;
+use std::io::{ErrorKind, Read};
+use std::ops::Range;
+
+const MAGIC_BYTES: &[u8; 4] = b"dbin";
+/// Largest buffer allocated upfront for a message, whatever its length prefix says
+const MAX_PREALLOCATION: usize = 16 << 20;
+/// Default for [`DbinReaderOptions::max_message_size`], far above the size of any Ethereum block
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 256 << 20;
 
 /// `DbinFile` is a struct that represents a simple file storage format to pack a stream of protobuf messages. It is defined by StreamingFast.
 ///
@@ -12,62 +21,232 @@ pub struct DbinFile {
     pub messages: Vec<Vec<u8>>,
 }
 
+/// `DbinItem` is the outcome of reading the next item of a stream of `.dbin` files with a [`DbinReader`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum DbinItem {
+    /// A message, without its length prefix
+    Message(Vec<u8>),
+    /// The header of the next `.dbin` file in the stream, including the first one
+    Header(DbinHeader),
+    /// The stream ended right after a complete message or header
+    Eof,
+}
+
+/// `DbinReader` reads a stream of one or more concatenated `.dbin` files item by item.
+///
+/// The stream must start with a header. Every read returns either a [`DbinItem`] or an error,
+/// no input makes it panic. A stream ending between items is [`DbinItem::Eof`], while a stream
+/// ending in the middle of a message or header is reported as [`DbinFileError::TruncatedMessage`]
+/// or [`DbinFileError::TruncatedHeader`] along with the offset where that item starts.
+pub struct DbinReader<R> {
+    read: R,
+    /// Number of bytes consumed from `read`
+    offset: u64,
+    header: Option<DbinHeader>,
+    options: DbinReaderOptions,
+}
+
+/// `DbinReaderOptions` configures how `.dbin` files are read, by the file, buffer and stream readers alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbinReaderOptions {
+    /// Messages whose length prefix is above this size are rejected with
+    /// [`DbinFileError::MessageTooLarge`] before anything is allocated for them
+    pub max_message_size: u32,
+}
+
+impl Default for DbinReaderOptions {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+/// `DbinBytes` is a [`DbinFile`] whose messages are views into a single shared [`Bytes`] buffer
+/// rather than individually allocated vectors.
+pub struct DbinBytes {
+    pub header: DbinHeader,
+    /// Each message without its 4 bytes length prefix, sliced from the buffer the file was read into
+    pub messages: Vec<Bytes>,
+}
+
 /// `DbinHeader` contains the fields that compose the header of the .dbin file.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DbinHeader {
     /// Next single byte after the 4 magic bytes, file format version
     pub version: u8,
-    /// Next 3 bytes, content type like 'ETH', 'EOS', or something else
+    /// Version 0: next 3 bytes, content type like 'ETH', 'EOS', or something else.
+    /// Version 1: 2 bytes big-endian length followed by the content type,
+    /// like 'type.googleapis.com/sf.ethereum.type.v2.Block'
     pub content_type: String,
-    /// Next 2 bytes, 10-based string representation of content version, ranges in '00'-'99'
+    /// Version 0: next 2 bytes, 10-based string representation of content version, ranges in '00'-'99'.
+    /// Empty for version 1
     pub content_version: String,
 }
 
-impl DbinFile {
-    /// reads a DbinHeader
-    ///
-    /// It nests `read_partial_header` to read header. By itself, it reads the 4 magic bytes
-    fn read_header<R: Read>(read: &mut R) -> Result<DbinHeader, DbinFileError> {
-        let mut buf: [u8; 4] = [0; 4];
+impl<R: Read> DbinReader<R> {
+    pub fn new(read: R) -> Self {
+        Self::with_options(read, DbinReaderOptions::default())
+    }
+
+    pub fn with_options(read: R, options: DbinReaderOptions) -> Self {
+        Self {
+            read,
+            offset: 0,
+            header: None,
+            options,
+        }
+    }
+
+    /// Number of bytes read so far, i.e. the offset of the next item
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Header of the `.dbin` file currently being read
+    pub fn header(&self) -> Option<&DbinHeader> {
+        self.header.as_ref()
+    }
+
+    /// Reads the next item of the stream.
+    pub fn next_item(&mut self) -> Result<DbinItem, DbinFileError> {
+        let item_offset = self.offset;
+        let mut prefix: [u8; 4] = [0; 4];
+        let read = self.read_fully(&mut prefix)?;
+
+        if read == 0 {
+            return Ok(DbinItem::Eof);
+        }
+        if read < 4 {
+            return Err(match self.header {
+                Some(_) => DbinFileError::TruncatedMessage(item_offset),
+                None if MAGIC_BYTES.starts_with(&prefix[..read]) => {
+                    DbinFileError::TruncatedHeader(item_offset)
+                }
+                None => DbinFileError::InvalidDBINBytes(item_offset),
+            });
+        }
+
+        if &prefix == MAGIC_BYTES {
+            let header = DbinFile::read_partial_header(&mut CountingRead(self), item_offset)?;
+            self.header = Some(header.clone());
+            return Ok(DbinItem::Header(header));
+        }
+        if self.header.is_none() {
+            return Err(DbinFileError::InvalidDBINBytes(item_offset));
+        }
+
+        let size = u32::from_be_bytes(prefix);
+        if size > self.options.max_message_size {
+            return Err(DbinFileError::MessageTooLarge {
+                offset: item_offset,
+                size: size as u64,
+            });
+        }
+        let size = size as usize;
+        // The length prefix is untrusted, so the buffer only grows past `MAX_PREALLOCATION`
+        // as bytes are actually read.
+        let mut content: Vec<u8> = Vec::with_capacity(size.min(MAX_PREALLOCATION));
+        let read = CountingRead(self)
+            .take(size as u64)
+            .read_to_end(&mut content)?;
+        if read < size {
+            return Err(DbinFileError::TruncatedMessage(item_offset));
+        }
+
+        Ok(DbinItem::Message(content))
+    }
 
-        read.read_exact(&mut buf)
-            .map_err(DbinFileError::ReadError)?;
+    /// Reads the next message of the stream, skipping over headers.
+    ///
+    /// Returns `None` once the stream ended cleanly.
+    pub fn next_message(&mut self) -> Result<Option<Vec<u8>>, DbinFileError> {
+        loop {
+            match self.next_item()? {
+                DbinItem::Message(message) => return Ok(Some(message)),
+                DbinItem::Header(_) => continue,
+                DbinItem::Eof => return Ok(None),
+            }
+        }
+    }
 
-        if &buf != b"dbin" {
-            return Err(DbinFileError::StartOfNewDBINFile);
+    /// Fills `buf` until it is full or the stream ends, returning the number of bytes read.
+    fn read_fully(&mut self, buf: &mut [u8]) -> Result<usize, DbinFileError> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.read.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(DbinFileError::ReadError(err)),
+            }
         }
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
 
-        let dbin_header = Self::read_partial_header(read)?;
+/// Reads through a [`DbinReader`], keeping its offset up to date.
+struct CountingRead<'a, R>(&'a mut DbinReader<R>);
 
-        Ok(dbin_header)
+impl<R: Read> Read for CountingRead<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.0.read.read(buf)?;
+        self.0.offset += read as u64;
+        Ok(read)
     }
+}
 
-    /// Reads all the fields that make a DbinHeader
-    fn read_partial_header<R: Read>(read: &mut R) -> Result<DbinHeader, DbinFileError> {
+impl DbinFile {
+    /// Reads all the fields that make a DbinHeader, following the 4 magic bytes found at `offset`
+    fn read_partial_header<R: Read>(
+        read: &mut R,
+        offset: u64,
+    ) -> Result<DbinHeader, DbinFileError> {
+        let truncated = |err: std::io::Error| {
+            if err.kind() == ErrorKind::UnexpectedEof {
+                DbinFileError::TruncatedHeader(offset)
+            } else {
+                DbinFileError::ReadError(err)
+            }
+        };
         let version;
         let content_type;
         let content_version;
 
         let mut buf: [u8; 1] = [0; 1];
-        read.read_exact(&mut buf)
-            .map_err(DbinFileError::ReadError)?;
+        read.read_exact(&mut buf).map_err(truncated)?;
 
         if buf[0] == 0 {
             version = 0u8;
             let mut content_type_bytes: [u8; 3] = [0; 3];
             read.read_exact(&mut content_type_bytes)
-                .map_err(DbinFileError::ReadError)?;
+                .map_err(truncated)?;
 
             content_type = String::from_utf8(Vec::from(content_type_bytes))
                 .map_err(DbinFileError::InvalidUTF8)?;
 
             let mut content_version_bytes: [u8; 2] = [0; 2];
             read.read_exact(&mut content_version_bytes)
-                .map_err(DbinFileError::ReadError)?;
+                .map_err(truncated)?;
 
             content_version = String::from_utf8(Vec::from(content_version_bytes))
                 .map_err(DbinFileError::InvalidUTF8)?;
+        } else if buf[0] == 1 {
+            version = 1u8;
+            let mut content_type_length: [u8; 2] = [0; 2];
+            read.read_exact(&mut content_type_length)
+                .map_err(truncated)?;
+
+            let mut content_type_bytes = vec![0; u16::from_be_bytes(content_type_length) as usize];
+            read.read_exact(&mut content_type_bytes)
+                .map_err(truncated)?;
+
+            content_type =
+                String::from_utf8(content_type_bytes).map_err(DbinFileError::InvalidUTF8)?;
+            content_version = String::new();
         } else {
-            return Err(DbinFileError::UnsupportedDBINVersion);
+            return Err(DbinFileError::UnsupportedDBINVersion(buf[0]));
         }
 
         Ok(DbinHeader {
@@ -78,76 +257,318 @@ impl DbinFile {
     }
 
     /// Returns a `DbinFile` from a Reader
+    ///
+    /// The reader may hold several concatenated `.dbin` files as long as their headers match.
     pub fn try_from_read<R: Read>(read: &mut R) -> Result<Self, DbinFileError> {
-        let dbin_header = Self::read_header(read)?;
+        Self::try_from_read_with_options(read, DbinReaderOptions::default())
+    }
+
+    /// Same as [`DbinFile::try_from_read`], with custom [`DbinReaderOptions`]
+    pub fn try_from_read_with_options<R: Read>(
+        read: &mut R,
+        options: DbinReaderOptions,
+    ) -> Result<Self, DbinFileError> {
+        let mut reader = DbinReader::with_options(read, options);
+        let dbin_header = match reader.next_item()? {
+            DbinItem::Header(header) => header,
+            _ => return Err(DbinFileError::InvalidDBINBytes(0)),
+        };
         let mut messages: Vec<Vec<u8>> = vec![];
 
         loop {
-            match Self::read_message(read) {
-                Ok(message) => messages.push(message),
-                Err(err) => {
-                    if err.kind() == std::io::ErrorKind::UnexpectedEof {
-                        return Ok(DbinFile {
-                            header: DbinHeader {
-                                version: dbin_header.version,
-                                content_type: dbin_header.content_type,
-                                content_version: dbin_header.content_version,
-                            },
-                            messages,
-                        });
-                    } else if err.kind() == std::io::ErrorKind::Other {
-                        // Check that version, content_type, and content_version match the previous header
-                        let dbin_header_new = Self::read_partial_header(read)?;
-                        if dbin_header.version != dbin_header_new.version
-                            || dbin_header.content_type != dbin_header_new.content_type
-                            || dbin_header.content_version != dbin_header_new.content_version
-                        {
-                            return Err(DbinFileError::DifferingDBINVersions);
-                        }
-                    } else {
-                        return Err(err);
+            match reader.next_item()? {
+                DbinItem::Message(message) => messages.push(message),
+                DbinItem::Header(dbin_header_new) => {
+                    // Check that version, content_type, and content_version match the previous header
+                    if dbin_header != dbin_header_new {
+                        return Err(DbinFileError::DifferingDBINVersions);
                     }
                 }
+                DbinItem::Eof => {
+                    return Ok(DbinFile {
+                        header: dbin_header,
+                        messages,
+                    })
+                }
             }
         }
     }
 }
 
 impl DbinFile {
-    /// Reads a single message
-    pub fn read_message<R: Read>(read: &mut R) -> Result<Vec<u8>, DbinFileError> {
-        let mut size: [u8; 4] = [0; 4];
-        read.read_exact(&mut size)?;
+    /// Returns a [`DbinBytes`] from a buffer holding a whole `.dbin` file, without copying its messages.
+    pub fn try_from_bytes(bytes: Bytes) -> Result<DbinBytes, DbinFileError> {
+        Self::try_from_bytes_with_options(bytes, DbinReaderOptions::default())
+    }
 
-        if &size == b"dbin" {
-            return Err(DbinFileError::StartOfNewDBINFile);
-        }
+    /// Same as [`DbinFile::try_from_bytes`], with custom [`DbinReaderOptions`]
+    pub fn try_from_bytes_with_options(
+        bytes: Bytes,
+        options: DbinReaderOptions,
+    ) -> Result<DbinBytes, DbinFileError> {
+        let (header, ranges) = Self::message_ranges(&bytes, options)?;
+        let messages = ranges.into_iter().map(|range| bytes.slice(range)).collect();
 
-        Ok(Self::read_content(size, read)?)
+        Ok(DbinBytes { header, messages })
     }
 
-    /// Reads a stream of messages.
+    /// Locates the messages of a `.dbin` file held in memory.
     ///
-    /// Messages are separated by "dbin" (magical 4 bytes) so each
-    /// new occurrence of it marks the start of a new .dbin file
-    pub fn read_message_stream<R: Read>(read: &mut R) -> Result<Vec<u8>, DbinFileError> {
-        let mut size: [u8; 4] = [0; 4];
-        read.read_exact(&mut size)?;
+    /// Returns the header along with the range of each message in `buf`, excluding length prefixes.
+    fn message_ranges(
+        buf: &[u8],
+        options: DbinReaderOptions,
+    ) -> Result<(DbinHeader, Vec<Range<usize>>), DbinFileError> {
+        if buf.len() < 4 {
+            return Err(if MAGIC_BYTES.starts_with(buf) {
+                DbinFileError::TruncatedHeader(0)
+            } else {
+                DbinFileError::InvalidDBINBytes(0)
+            });
+        }
+        if &buf[..4] != MAGIC_BYTES {
+            return Err(DbinFileError::InvalidDBINBytes(0));
+        }
+        let mut read = &buf[4..];
+        let dbin_header = Self::read_partial_header(&mut read, 0)?;
+        let mut ranges: Vec<Range<usize>> = vec![];
+
+        while !read.is_empty() {
+            let offset = buf.len() - read.len();
+            if read.len() < 4 {
+                return Err(DbinFileError::TruncatedMessage(offset as u64));
+            }
+
+            if &read[..4] == MAGIC_BYTES {
+                read = &read[4..];
+                let dbin_header_new = Self::read_partial_header(&mut read, offset as u64)?;
+                if dbin_header != dbin_header_new {
+                    return Err(DbinFileError::DifferingDBINVersions);
+                }
+                continue;
+            }
+
+            let size = u32::from_be_bytes([read[0], read[1], read[2], read[3]]);
+            if size > options.max_message_size {
+                return Err(DbinFileError::MessageTooLarge {
+                    offset: offset as u64,
+                    size: size as u64,
+                });
+            }
+            let size = size as usize;
+            read = &read[4..];
+            if read.len() < size {
+                return Err(DbinFileError::TruncatedMessage(offset as u64));
+            }
+            ranges.push(offset + 4..offset + 4 + size);
+            read = &read[size..];
+        }
+
+        Ok((dbin_header, ranges))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error::DbinFileError;
+    use super::{DbinFile, DbinItem, DbinReader, DbinReaderOptions};
+    use bytes::Bytes;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const HEADER: &[u8] = b"dbin\x00ETH01";
 
-        if &size == b"dbin" {
-            _ = Self::read_partial_header(read)?;
-            size = [0; 4];
-            read.read_exact(&mut size)?;
+    /// Packs messages into a `.dbin` file
+    fn encode_dbin(messages: &[Vec<u8>]) -> Vec<u8> {
+        let mut file = HEADER.to_vec();
+        for message in messages {
+            file.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            file.extend_from_slice(message);
         }
+        file
+    }
+
+    fn example_file() -> Vec<u8> {
+        std::fs::read("example0017686312.dbin").expect("Failed to read file")
+    }
 
-        Ok(Self::read_content(size, read)?)
+    #[test]
+    fn test_clean_eof() {
+        let file = example_file();
+        let mut reader = DbinReader::new(file.as_slice());
+
+        assert!(matches!(reader.next_item(), Ok(DbinItem::Header(_))));
+        assert!(matches!(reader.next_item(), Ok(DbinItem::Message(_))));
+        assert!(matches!(reader.next_item(), Ok(DbinItem::Eof)));
+        assert_eq!(reader.offset(), file.len() as u64);
     }
 
-    /// reads message bytes
-    fn read_content<R: Read>(size: [u8; 4], read: &mut R) -> Result<Vec<u8>, std::io::Error> {
-        let size = u32::from_be_bytes(size);
-        let mut content: Vec<u8> = vec![0; size as usize];
-        read.read_exact(&mut content)?;
-        Ok(content)
+    #[test]
+    fn test_truncated_message() {
+        let file = example_file();
+        // the header of a version 0 file is 10 bytes long, the message starts right after
+        let truncated = &file[..file.len() - 1];
+
+        let mut reader = DbinReader::new(truncated);
+        assert!(matches!(reader.next_item(), Ok(DbinItem::Header(_))));
+        assert!(matches!(
+            reader.next_item(),
+            Err(DbinFileError::TruncatedMessage(10))
+        ));
+
+        assert!(matches!(
+            DbinFile::try_from_read(&mut &file[..12]),
+            Err(DbinFileError::TruncatedMessage(10))
+        ));
+        assert!(matches!(
+            DbinFile::try_from_bytes(Bytes::copy_from_slice(truncated)),
+            Err(DbinFileError::TruncatedMessage(10))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let file = example_file();
+
+        for len in 1..10 {
+            assert!(matches!(
+                DbinFile::try_from_read(&mut &file[..len]),
+                Err(DbinFileError::TruncatedHeader(0))
+            ));
+            assert!(matches!(
+                DbinFile::try_from_bytes(Bytes::copy_from_slice(&file[..len])),
+                Err(DbinFileError::TruncatedHeader(0))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(matches!(
+            DbinFile::try_from_read(&mut &b"not a dbin file"[..]),
+            Err(DbinFileError::InvalidDBINBytes(0))
+        ));
+        assert!(matches!(
+            DbinFile::try_from_read(&mut &b"dbin\x07ETH01"[..]),
+            Err(DbinFileError::UnsupportedDBINVersion(7))
+        ));
+        assert!(matches!(
+            DbinFile::try_from_read(&mut &b"dbin\x00\xff\xfe\xfd01"[..]),
+            Err(DbinFileError::InvalidUTF8(_))
+        ));
+    }
+
+    #[test]
+    fn test_differing_versions() {
+        let mut file = example_file();
+        file.extend_from_slice(b"dbin\x00ETH02");
+
+        assert!(matches!(
+            DbinFile::try_from_read(&mut file.as_slice()),
+            Err(DbinFileError::DifferingDBINVersions)
+        ));
+        assert!(matches!(
+            DbinFile::try_from_bytes(Bytes::from(file)),
+            Err(DbinFileError::DifferingDBINVersions)
+        ));
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let file = encode_dbin(&[vec![0; 16], vec![0; 64]]);
+        let options = DbinReaderOptions {
+            max_message_size: 32,
+        };
+
+        assert!(matches!(
+            DbinFile::try_from_read_with_options(&mut file.as_slice(), options),
+            Err(DbinFileError::MessageTooLarge {
+                offset: 30,
+                size: 64
+            })
+        ));
+        assert!(matches!(
+            DbinFile::try_from_bytes_with_options(Bytes::from(file.clone()), options),
+            Err(DbinFileError::MessageTooLarge {
+                offset: 30,
+                size: 64
+            })
+        ));
+
+        let mut reader = DbinReader::with_options(file.as_slice(), options);
+        assert!(matches!(reader.next_message(), Ok(Some(_))));
+        assert!(matches!(
+            reader.next_message(),
+            Err(DbinFileError::MessageTooLarge {
+                offset: 30,
+                size: 64
+            })
+        ));
+    }
+
+    #[test]
+    fn test_huge_length_prefix() {
+        let mut file = HEADER.to_vec();
+        file.extend_from_slice(&u32::MAX.to_be_bytes());
+        file.extend_from_slice(&[0; 16]);
+
+        assert!(matches!(
+            DbinFile::try_from_read(&mut file.as_slice()),
+            Err(DbinFileError::MessageTooLarge { offset: 10, .. })
+        ));
+
+        // without a size limit, the truncated message is detected without allocating 4 GiB
+        let options = DbinReaderOptions {
+            max_message_size: u32::MAX,
+        };
+        assert!(matches!(
+            DbinFile::try_from_read_with_options(&mut file.as_slice(), options),
+            Err(DbinFileError::TruncatedMessage(10))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_arbitrary_bytes_never_panic(bytes in vec(any::<u8>(), 0..2048)) {
+            let _ = DbinFile::try_from_read(&mut bytes.as_slice());
+            let _ = DbinFile::try_from_bytes(Bytes::from(bytes.clone()));
+
+            let mut reader = DbinReader::new(bytes.as_slice());
+            while let Ok(Some(_)) = reader.next_message() {}
+        }
+
+        #[test]
+        fn prop_arbitrary_messages_never_panic(tail in vec(any::<u8>(), 0..2048)) {
+            let mut bytes = HEADER.to_vec();
+            bytes.extend_from_slice(&tail);
+
+            let _ = DbinFile::try_from_read(&mut bytes.as_slice());
+            let _ = DbinFile::try_from_bytes(Bytes::from(bytes));
+        }
+
+        #[test]
+        fn prop_round_trip(messages in vec(vec(any::<u8>(), 0..512), 0..16)) {
+            let file = encode_dbin(&messages);
+
+            let dbin_file = DbinFile::try_from_read(&mut file.as_slice()).unwrap();
+            prop_assert_eq!(&dbin_file.messages, &messages);
+
+            let dbin_bytes = DbinFile::try_from_bytes(Bytes::from(file)).unwrap();
+            prop_assert_eq!(dbin_bytes.header, dbin_file.header);
+            prop_assert!(dbin_bytes.messages.iter().eq(messages.iter()));
+        }
+
+        #[test]
+        fn prop_truncated_round_trip(messages in vec(vec(any::<u8>(), 1..512), 1..16), cut in 1usize..512) {
+            let file = encode_dbin(&messages);
+            let cut = cut.min(messages.last().unwrap().len() + 3);
+            let truncated = &file[..file.len() - cut];
+
+            let last_offset = (file.len() - messages.last().unwrap().len() - 4) as u64;
+            prop_assert!(matches!(
+                DbinFile::try_from_read(&mut &truncated[..]),
+                Err(DbinFileError::TruncatedMessage(offset)) if offset == last_offset
+            ));
+        }
     }
 }
@@ -0,0 +1,76 @@
+use crate::era1::error::Era1Error;
+use crate::headers::error::BlockHeaderError;
+use crate::headers::HeaderRecordWithNumber;
+use crate::rlp::RlpBlock;
+use alloy_rlp::Decodable;
+use reth_primitives::{Header, TransactionSigned, B256, U256};
+use sf_protos::ethereum::r#type::v2::Block;
+
+/// `Era1Block` is a block as stored in an era1 file: its header, body and receipts, encoded
+/// as with [`RlpBlock`], and its total difficulty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Era1Block {
+    pub block: RlpBlock,
+    pub total_difficulty: U256,
+}
+
+impl Era1Block {
+    pub fn number(&self) -> u64 {
+        self.block.header.number
+    }
+
+    /// The block hash, computed from the header
+    pub fn hash(&self) -> B256 {
+        self.block.header.hash_slow()
+    }
+
+    /// Decodes a block from the decompressed entries of an era1 file
+    pub fn decode(
+        header_rlp: &[u8],
+        body_rlp: &[u8],
+        receipts: Vec<u8>,
+        total_difficulty: &[u8],
+    ) -> Result<Self, Era1Error> {
+        let header = Header::decode(&mut &header_rlp[..])?;
+
+        let mut body = body_rlp;
+        let body_header = alloy_rlp::Header::decode(&mut body)?;
+        if !body_header.list {
+            return Err(Era1Error::InvalidRlp(alloy_rlp::Error::UnexpectedString));
+        }
+        let transactions = Vec::<TransactionSigned>::decode(&mut body)?;
+        let uncles = Vec::<Header>::decode(&mut body)?;
+
+        let total_difficulty: [u8; 32] = total_difficulty
+            .try_into()
+            .map_err(|_| BlockHeaderError::InvalidTotalDifficulty)?;
+
+        Ok(Self {
+            block: RlpBlock {
+                header,
+                transactions,
+                uncles,
+                receipts,
+            },
+            total_difficulty: U256::from_le_bytes(total_difficulty),
+        })
+    }
+}
+
+impl TryFrom<&Block> for Era1Block {
+    type Error = Era1Error;
+
+    /// Converts a verified Firehose block, checking that its header hashes to the block hash
+    fn try_from(block: &Block) -> Result<Self, Self::Error> {
+        let rlp_block = RlpBlock::try_from(block)?;
+
+        let header_record = HeaderRecordWithNumber::try_from(block)?;
+        let total_difficulty = U256::try_from_be_slice(&header_record.total_difficulty)
+            .ok_or(BlockHeaderError::InvalidTotalDifficulty)?;
+
+        Ok(Self {
+            block: rlp_block,
+            total_difficulty,
+        })
+    }
+}
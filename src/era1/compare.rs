@@ -0,0 +1,266 @@
+use crate::era1::block::Era1Block;
+use crate::era1::reader::Era1File;
+use crate::error::DecodeError;
+use crate::{handle_file, is_flat_file};
+use reth_primitives::{B256, U256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `Mismatch` is a difference between a block of a flat file and the same block in an era1 archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The block isn't in the era1 archive
+    MissingBlock,
+    HeaderHash {
+        era1: B256,
+        flat: B256,
+    },
+    TransactionCount {
+        era1: usize,
+        flat: usize,
+    },
+    /// The transaction at `index` differs
+    Transaction {
+        index: usize,
+    },
+    Uncles,
+    Receipts,
+    TotalDifficulty {
+        era1: U256,
+        flat: U256,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::MissingBlock => write!(f, "missing from era1 archive"),
+            Mismatch::HeaderHash { era1, flat } => {
+                write!(f, "header hash {} != {} (era1 != flat)", era1, flat)
+            }
+            Mismatch::TransactionCount { era1, flat } => {
+                write!(f, "transaction count {} != {} (era1 != flat)", era1, flat)
+            }
+            Mismatch::Transaction { index } => write!(f, "transaction {} differs", index),
+            Mismatch::Uncles => write!(f, "uncles differ"),
+            Mismatch::Receipts => write!(f, "receipts differ"),
+            Mismatch::TotalDifficulty { era1, flat } => {
+                write!(f, "total difficulty {} != {} (era1 != flat)", era1, flat)
+            }
+        }
+    }
+}
+
+/// `CompareReport` lists the mismatches found comparing flat files to era1 archives.
+#[derive(Debug, Default)]
+pub struct CompareReport {
+    /// Number of blocks compared
+    pub blocks: usize,
+    /// Block numbers and their mismatches
+    pub mismatches: Vec<(u64, Mismatch)>,
+    /// Flat files failing to decode, whose blocks aren't compared
+    pub failed_files: Vec<(PathBuf, DecodeError)>,
+}
+
+impl CompareReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.failed_files.is_empty()
+    }
+}
+
+/// Compares a block from a trusted era1 archive with the same block from a flat file.
+pub fn compare_block(era1: &Era1Block, flat: &Era1Block) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+
+    let (era1_hash, flat_hash) = (era1.hash(), flat.hash());
+    if era1_hash != flat_hash {
+        mismatches.push(Mismatch::HeaderHash {
+            era1: era1_hash,
+            flat: flat_hash,
+        });
+    }
+
+    if era1.block.transactions.len() != flat.block.transactions.len() {
+        mismatches.push(Mismatch::TransactionCount {
+            era1: era1.block.transactions.len(),
+            flat: flat.block.transactions.len(),
+        });
+    }
+    if let Some(index) = era1
+        .block
+        .transactions
+        .iter()
+        .zip(&flat.block.transactions)
+        .position(|(era1, flat)| era1 != flat)
+    {
+        mismatches.push(Mismatch::Transaction { index });
+    }
+
+    if era1.block.uncles != flat.block.uncles {
+        mismatches.push(Mismatch::Uncles);
+    }
+    if era1.block.receipts != flat.block.receipts {
+        mismatches.push(Mismatch::Receipts);
+    }
+    if era1.total_difficulty != flat.total_difficulty {
+        mismatches.push(Mismatch::TotalDifficulty {
+            era1: era1.total_difficulty,
+            flat: flat.total_difficulty,
+        });
+    }
+
+    mismatches
+}
+
+/// Compares, block by block, the flat files of `flat_dir` with the era1 archives of `era1_dir`.
+///
+/// Flat files are decoded and verified as with [`handle_file`], then each block is compared
+/// with [`compare_block`] to the block of the same number in the era1 archives. Era1 files
+/// must be named `<network>-<epoch>-<short root>.era1`, they are read one at a time.
+/// Flat files failing to decode are listed in the report, and the others still compared.
+///
+/// # Arguments
+///
+/// * `era1_dir`: the directory of the trusted era1 archives.
+/// * `flat_dir`: the directory of the flat files to check.
+///
+pub fn compare_dirs(era1_dir: &Path, flat_dir: &Path) -> Result<CompareReport, DecodeError> {
+    let mut era1_paths = BTreeMap::new();
+    for entry in fs::read_dir(era1_dir)? {
+        let path = entry?.path();
+        if path.extension() != Some("era1".as_ref()) {
+            continue;
+        }
+        if let Some(epoch) = era1_epoch(&path) {
+            era1_paths.insert(epoch, path);
+        }
+    }
+
+    let mut flat_paths = fs::read_dir(flat_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    flat_paths.retain(|path| is_flat_file(path));
+    flat_paths.sort();
+
+    let mut report = CompareReport::default();
+    let mut era1_file: Option<(u64, Option<Era1File>)> = None;
+    for path in flat_paths {
+        let blocks = match handle_file(&path, None, None, None) {
+            Ok(blocks) => blocks,
+            Err(err) => {
+                report.failed_files.push((path, err));
+                continue;
+            }
+        };
+        for block in blocks {
+            let epoch = block.number / crate::accumulator::EPOCH_SIZE as u64;
+            if era1_file.as_ref().map(|(loaded, _)| *loaded) != Some(epoch) {
+                let file = match era1_paths.get(&epoch) {
+                    Some(era1_path) => Some(Era1File::open(era1_path)?),
+                    None => None,
+                };
+                era1_file = Some((epoch, file));
+            }
+
+            report.blocks += 1;
+            let era1_block = era1_file
+                .as_ref()
+                .and_then(|(_, file)| file.as_ref())
+                .and_then(|file| {
+                    let index = block.number.checked_sub(file.start_block)?;
+                    file.blocks.get(index as usize)
+                });
+            let era1_block = match era1_block {
+                Some(era1_block) => era1_block,
+                None => {
+                    report
+                        .mismatches
+                        .push((block.number, Mismatch::MissingBlock));
+                    continue;
+                }
+            };
+
+            let flat_block = Era1Block::try_from(&block)?;
+            for mismatch in compare_block(era1_block, &flat_block) {
+                report.mismatches.push((block.number, mismatch));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// The epoch of an era1 file named `<network>-<epoch>-<short root>.era1`
+fn era1_epoch(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.split('-').nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_block, compare_dirs, Mismatch};
+    use crate::era1::{Era1Block, Era1Sink, DEFAULT_NETWORK};
+    use crate::handle_file;
+    use crate::output::BlockSink;
+    use reth_primitives::U256;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_compare_block() {
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let era1 = Era1Block::try_from(&blocks[1]).unwrap();
+        assert!(compare_block(&era1, &era1).is_empty());
+
+        let mut flat = era1.clone();
+        flat.total_difficulty += U256::from(1);
+        flat.block.header.gas_used += 1;
+        let mismatches = compare_block(&era1, &flat);
+        assert_eq!(mismatches.len(), 2);
+        assert!(matches!(mismatches[0], Mismatch::HeaderHash { .. }));
+        assert!(matches!(mismatches[1], Mismatch::TotalDifficulty { .. }));
+    }
+
+    #[test]
+    fn test_compare_dirs_corrupt_file() {
+        let dir = std::env::temp_dir().join("decoder-test-compare-corrupt");
+        let _ = fs::remove_dir_all(&dir);
+        let (era1_dir, flat_dir) = (dir.join("era1"), dir.join("flat"));
+        fs::create_dir_all(&flat_dir).unwrap();
+        fs::copy(
+            "tests/0000000000.dbin.zst",
+            flat_dir.join("0000000000.dbin.zst"),
+        )
+        .unwrap();
+        fs::write(flat_dir.join("0000000100.dbin"), b"dbin corrupt").unwrap();
+
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let mut sink = Era1Sink::with_last_block(&era1_dir, DEFAULT_NETWORK, 99).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let report = compare_dirs(&era1_dir, &flat_dir).unwrap();
+        assert_eq!(report.blocks, blocks.len());
+        assert!(report.mismatches.is_empty());
+        assert_eq!(report.failed_files.len(), 1);
+        assert!(!report.is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
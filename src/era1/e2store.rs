@@ -0,0 +1,61 @@
+use crate::era1::error::Era1Error;
+use std::io::{self, Read, Write};
+
+/// Size of the header of an e2store entry: type (2 bytes), length (4 bytes), reserved (2 bytes)
+pub const HEADER_SIZE: u64 = 8;
+
+pub const VERSION: u16 = 0x3265;
+pub const COMPRESSED_HEADER: u16 = 0x03;
+pub const COMPRESSED_BODY: u16 = 0x04;
+pub const COMPRESSED_RECEIPTS: u16 = 0x05;
+pub const TOTAL_DIFFICULTY: u16 = 0x06;
+pub const ACCUMULATOR: u16 = 0x07;
+pub const BLOCK_INDEX: u16 = 0x3266;
+
+/// `Entry` is a single [e2store](https://github.com/status-im/nimbus-eth2/blob/stable/docs/e2store.md) record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub entry_type: u16,
+    pub data: Vec<u8>,
+}
+
+/// Writes an entry, returning the number of bytes written including its header.
+pub fn write_entry<W: Write>(writer: &mut W, entry_type: u16, data: &[u8]) -> io::Result<u64> {
+    let length = u32::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "e2store entry too large"))?;
+    writer.write_all(&entry_type.to_le_bytes())?;
+    writer.write_all(&length.to_le_bytes())?;
+    writer.write_all(&[0, 0])?;
+    writer.write_all(data)?;
+    Ok(HEADER_SIZE + data.len() as u64)
+}
+
+/// Reads the next entry, `offset` being the position of the reader for error reporting.
+///
+/// Returns `Ok(None)` when the reader is exhausted on an entry boundary.
+pub fn read_entry<R: Read>(reader: &mut R, offset: u64) -> Result<Option<Entry>, Era1Error> {
+    let mut header = [0u8; HEADER_SIZE as usize];
+    let mut read = 0;
+    while read < header.len() {
+        match reader.read(&mut header[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(Era1Error::InvalidEntry(offset)),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let entry_type = u16::from_le_bytes([header[0], header[1]]);
+    let length = u32::from_le_bytes([header[2], header[3], header[4], header[5]]);
+    if header[6..] != [0, 0] {
+        return Err(Era1Error::InvalidEntry(offset));
+    }
+
+    let mut data = Vec::new();
+    reader.take(length as u64).read_to_end(&mut data)?;
+    if data.len() != length as usize {
+        return Err(Era1Error::InvalidEntry(offset));
+    }
+    Ok(Some(Entry { entry_type, data }))
+}
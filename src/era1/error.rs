@@ -0,0 +1,27 @@
+use crate::headers::error::BlockHeaderError;
+use crate::rlp::error::RlpError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Era1Error {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid header: {0}")]
+    BlockHeaderError(#[from] BlockHeaderError),
+    #[error("Invalid block: {0}")]
+    RlpError(#[from] RlpError),
+    #[error("Block {0} is after the merge, era1 only holds pre-merge blocks")]
+    PostMerge(u64),
+    #[error("Expected block {expected}, found block {found}")]
+    NonContiguous { expected: u64, found: u64 },
+    #[error(
+        "Epoch {epoch} ends after {blocks} blocks, only the last pre-merge epoch can be partial"
+    )]
+    PartialEpoch { epoch: u64, blocks: usize },
+    #[error("Invalid e2store entry at offset {0}")]
+    InvalidEntry(u64),
+    #[error("Invalid block index")]
+    InvalidBlockIndex,
+    #[error("RLP error: {0}")]
+    InvalidRlp(#[from] alloy_rlp::Error),
+}
@@ -0,0 +1,391 @@
+mod block;
+pub mod compare;
+pub mod e2store;
+pub mod error;
+mod reader;
+
+use crate::accumulator::{epoch_accumulator_root, HeaderRecord, EPOCH_SIZE};
+use crate::era1::e2store::write_entry;
+use crate::era1::error::Era1Error;
+use crate::output::error::OutputError;
+use crate::output::BlockSink;
+use crate::MERGE_BLOCK;
+use reth_primitives::hex;
+use sf_protos::ethereum::r#type::v2::Block;
+use simple_log::log;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+pub use block::Era1Block;
+pub use reader::Era1File;
+
+/// Network name era1 files are prefixed with by default.
+pub const DEFAULT_NETWORK: &str = "mainnet";
+
+/// Writes pre-merge blocks to [era1](https://github.com/eth-clients/e2store-format-specs/blob/main/formats/era1.md) archives.
+///
+/// Each epoch of [`EPOCH_SIZE`] blocks is written to `<network>-<epoch>-<short root>.era1`,
+/// `<short root>` being the first 4 bytes of the epoch accumulator root. Blocks of an epoch
+/// must be written in order starting from its first block. Only the last pre-merge epoch, ending
+/// at [`MERGE_BLOCK`], can be partial: an epoch closed before its last block is an error, so that
+/// no era1 file is written with the accumulator root of a partial epoch.
+/// Headers are checked against the block hash before being written.
+///
+/// Epochs are written to `<epoch>.era1.tmp` until they are finished. An epoch failing to be
+/// written, left partial, or left unfinished when the sink is dropped, is discarded along with
+/// its temporary file.
+pub struct Era1Sink {
+    dir: PathBuf,
+    network: String,
+    last_block: u64,
+    epoch: Option<EpochWriter>,
+}
+
+impl Era1Sink {
+    /// Creates the sink for mainnet, and `dir` if it doesn't exist yet
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, Era1Error> {
+        Self::with_network(dir, DEFAULT_NETWORK)
+    }
+
+    /// Creates the sink, naming the era1 files after `network`
+    pub fn with_network(dir: impl Into<PathBuf>, network: &str) -> Result<Self, Era1Error> {
+        Self::with_last_block(dir, network, MERGE_BLOCK as u64)
+    }
+
+    /// Creates the sink for a network whose last pre-merge block is `last_block`
+    pub fn with_last_block(
+        dir: impl Into<PathBuf>,
+        network: &str,
+        last_block: u64,
+    ) -> Result<Self, Era1Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            network: network.to_string(),
+            last_block,
+            epoch: None,
+        })
+    }
+
+    fn write(&mut self, block: &Block) -> Result<(), Era1Error> {
+        if block.number > self.last_block {
+            return Err(Era1Error::PostMerge(block.number));
+        }
+
+        let epoch = block.number / EPOCH_SIZE as u64;
+        if self.epoch.as_ref().map(|writer| writer.epoch) != Some(epoch) {
+            self.close()?;
+        }
+
+        let writer = match self.epoch.as_mut() {
+            Some(writer) => writer,
+            None => self.epoch.insert(EpochWriter::create(&self.dir, epoch)?),
+        };
+        let result = writer.write_block(block);
+        if result.is_err() {
+            self.epoch = None;
+        }
+        result
+    }
+
+    fn close(&mut self) -> Result<(), Era1Error> {
+        if let Some(writer) = self.epoch.take() {
+            if writer.offsets.len() < EPOCH_SIZE && writer.next_block() <= self.last_block {
+                return Err(Era1Error::PartialEpoch {
+                    epoch: writer.epoch,
+                    blocks: writer.offsets.len(),
+                });
+            }
+            let path = writer.finish(&self.dir, &self.network)?;
+            log::info!("Wrote {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+impl BlockSink for Era1Sink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        Ok(self.write(block)?)
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        Ok(self.close()?)
+    }
+}
+
+/// The era1 file of an epoch being written
+struct EpochWriter {
+    epoch: u64,
+    path: PathBuf,
+    writer: BufWriter<File>,
+    written: u64,
+    offsets: Vec<u64>,
+    header_records: Vec<HeaderRecord>,
+    finished: bool,
+}
+
+impl EpochWriter {
+    /// Starts the epoch in a temporary file, it is renamed once the accumulator root is known
+    fn create(dir: &std::path::Path, epoch: u64) -> Result<Self, Era1Error> {
+        let path = dir.join(format!("{:05}.era1.tmp", epoch));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let written = write_entry(&mut writer, e2store::VERSION, &[])?;
+        Ok(Self {
+            epoch,
+            path,
+            writer,
+            written,
+            offsets: Vec::with_capacity(EPOCH_SIZE),
+            header_records: Vec::with_capacity(EPOCH_SIZE),
+            finished: false,
+        })
+    }
+
+    fn next_block(&self) -> u64 {
+        self.epoch * EPOCH_SIZE as u64 + self.offsets.len() as u64
+    }
+
+    fn write_block(&mut self, block: &Block) -> Result<(), Era1Error> {
+        if block.number != self.next_block() {
+            return Err(Era1Error::NonContiguous {
+                expected: self.next_block(),
+                found: block.number,
+            });
+        }
+
+        let era1_block = Era1Block::try_from(block)?;
+
+        self.offsets.push(self.written);
+        self.write_entry(
+            e2store::COMPRESSED_HEADER,
+            &snappy(&era1_block.block.header_rlp())?,
+        )?;
+        self.write_entry(
+            e2store::COMPRESSED_BODY,
+            &snappy(&era1_block.block.body_rlp())?,
+        )?;
+        self.write_entry(
+            e2store::COMPRESSED_RECEIPTS,
+            &snappy(era1_block.block.receipts_rlp())?,
+        )?;
+        self.write_entry(
+            e2store::TOTAL_DIFFICULTY,
+            &era1_block.total_difficulty.to_le_bytes::<32>(),
+        )?;
+
+        self.header_records.push(HeaderRecord {
+            block_hash: era1_block.hash(),
+            total_difficulty: era1_block.total_difficulty,
+        });
+        Ok(())
+    }
+
+    fn write_entry(&mut self, entry_type: u16, data: &[u8]) -> Result<(), Era1Error> {
+        self.written += write_entry(&mut self.writer, entry_type, data)?;
+        Ok(())
+    }
+
+    /// Writes the accumulator and block index, and moves the file to its final name
+    fn finish(mut self, dir: &std::path::Path, network: &str) -> Result<PathBuf, Era1Error> {
+        let root = epoch_accumulator_root(&self.header_records);
+        self.write_entry(e2store::ACCUMULATOR, root.as_slice())?;
+
+        // offsets are relative to the start of the block index entry
+        let base = self.written;
+        let start_block = self.epoch * EPOCH_SIZE as u64;
+        let mut index = Vec::with_capacity(16 + 8 * self.offsets.len());
+        index.extend_from_slice(&start_block.to_le_bytes());
+        for offset in &self.offsets {
+            let relative = *offset as i64 - base as i64;
+            index.extend_from_slice(&relative.to_le_bytes());
+        }
+        index.extend_from_slice(&(self.offsets.len() as u64).to_le_bytes());
+        self.write_entry(e2store::BLOCK_INDEX, &index)?;
+        self.writer.flush()?;
+
+        let path = dir.join(format!(
+            "{}-{:05}-{}.era1",
+            network,
+            self.epoch,
+            hex::encode(&root[..4])
+        ));
+        fs::rename(&self.path, &path)?;
+        self.finished = true;
+        Ok(path)
+    }
+}
+
+impl Drop for EpochWriter {
+    /// Removes the temporary file of an epoch which wasn't finished
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(err) = fs::remove_file(&self.path) {
+                log::warn!("Failed to remove {}: {}", self.path.display(), err);
+            }
+        }
+    }
+}
+
+/// Compresses `data` with the snappy framing format used by era1
+fn snappy(data: &[u8]) -> Result<Vec<u8>, Era1Error> {
+    let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+    encoder.write_all(data)?;
+    encoder
+        .into_inner()
+        .map_err(|err| Era1Error::IoError(io::Error::other(err.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare::compare_dirs;
+    use super::e2store::{self, read_entry};
+    use super::{Era1Block, Era1File, Era1Sink, DEFAULT_NETWORK};
+    use crate::era1::error::Era1Error;
+    use crate::handle_file;
+    use crate::output::error::OutputError;
+    use crate::output::BlockSink;
+    use std::fs::{self, File};
+    use std::io::BufReader;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_era1_export() {
+        let dir = std::env::temp_dir().join("decoder-test-era1-export");
+        let _ = fs::remove_dir_all(&dir);
+
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut sink = Era1Sink::with_last_block(&dir, DEFAULT_NETWORK, 99).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let paths: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(paths.len(), 1);
+        let file_name = paths[0].file_name().unwrap().to_str().unwrap();
+        assert!(file_name.starts_with("mainnet-00000-"));
+        assert!(file_name.ends_with(".era1"));
+
+        let mut reader = BufReader::new(File::open(&paths[0]).unwrap());
+        let mut entry_types = Vec::new();
+        while let Some(entry) = read_entry(&mut reader, 0).unwrap() {
+            entry_types.push(entry.entry_type);
+        }
+
+        assert_eq!(entry_types.first(), Some(&e2store::VERSION));
+        assert_eq!(entry_types.len(), 1 + 4 * blocks.len() + 2);
+        assert_eq!(
+            &entry_types[entry_types.len() - 2..],
+            &[e2store::ACCUMULATOR, e2store::BLOCK_INDEX]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_era1_round_trip() {
+        let dir = std::env::temp_dir().join("decoder-test-era1-round-trip");
+        let _ = fs::remove_dir_all(&dir);
+
+        let path = PathBuf::from("tests/0000000000.dbin.zst");
+        let blocks = handle_file(&path, None, None, None).unwrap();
+
+        let mut sink = Era1Sink::with_last_block(&dir, DEFAULT_NETWORK, 99).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let era1_path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let era1_file = Era1File::open(&era1_path).unwrap();
+        assert_eq!(era1_file.start_block, 0);
+        assert_eq!(era1_file.blocks.len(), blocks.len());
+        for (era1_block, block) in era1_file.blocks.iter().zip(&blocks) {
+            assert_eq!(era1_block, &Era1Block::try_from(block).unwrap());
+        }
+
+        let report = compare_dirs(&dir, Path::new("tests")).unwrap();
+        assert_eq!(report.blocks, blocks.len());
+        assert!(report.is_ok(), "{:?}", report.mismatches);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_era1_non_contiguous() {
+        let dir = std::env::temp_dir().join("decoder-test-era1-non-contiguous");
+        let _ = fs::remove_dir_all(&dir);
+
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut sink = Era1Sink::new(&dir).unwrap();
+        assert!(sink.write_block(&blocks[1]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_era1_discard_epoch() {
+        let dir = std::env::temp_dir().join("decoder-test-era1-discard-epoch");
+        let _ = fs::remove_dir_all(&dir);
+        let tmp_path = dir.join("00000.era1.tmp");
+
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        // A failed write discards the epoch
+        let mut sink = Era1Sink::new(&dir).unwrap();
+        for block in &blocks[..5] {
+            sink.write_block(block).unwrap();
+        }
+        assert!(tmp_path.exists());
+        assert!(sink.write_block(&blocks[7]).is_err());
+        assert!(!tmp_path.exists());
+
+        // So does dropping the sink before finishing it
+        sink.write_block(&blocks[0]).unwrap();
+        assert!(tmp_path.exists());
+        drop(sink);
+        assert!(!tmp_path.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        // And finishing an epoch before its last block
+        let mut sink = Era1Sink::new(&dir).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        assert!(matches!(
+            sink.finish(),
+            Err(OutputError::Era1Error(Era1Error::PartialEpoch {
+                epoch: 0,
+                blocks: 100
+            }))
+        ));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use crate::era1::block::Era1Block;
+use crate::era1::e2store::{self, read_entry, Entry, HEADER_SIZE};
+use crate::era1::error::Era1Error;
+use reth_primitives::B256;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// `Era1File` is the content of an era1 file: the blocks of an epoch and its accumulator root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Era1File {
+    pub start_block: u64,
+    pub blocks: Vec<Era1Block>,
+    pub accumulator_root: B256,
+}
+
+impl Era1File {
+    /// Reads and decodes a whole era1 file
+    pub fn open(path: &Path) -> Result<Self, Era1Error> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads and decodes era1 entries from a reader
+    ///
+    /// Entries of unknown types are skipped, as allowed by the e2store format.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Era1Error> {
+        let mut entries = Entries {
+            reader: &mut reader,
+            offset: 0,
+        };
+
+        match entries.next_entry()? {
+            Some(entry) if entry.entry_type == e2store::VERSION => {}
+            _ => return Err(Era1Error::InvalidEntry(0)),
+        }
+
+        let mut blocks = Vec::new();
+        let mut accumulator_root = None;
+        let mut block_index = None;
+        while let Some(entry) = entries.next_entry()? {
+            match entry.entry_type {
+                e2store::COMPRESSED_HEADER => {
+                    let header = decompress(&entry.data)?;
+                    let body = decompress(&entries.expect(e2store::COMPRESSED_BODY)?)?;
+                    let receipts = decompress(&entries.expect(e2store::COMPRESSED_RECEIPTS)?)?;
+                    let total_difficulty = entries.expect(e2store::TOTAL_DIFFICULTY)?;
+                    blocks.push(Era1Block::decode(
+                        &header,
+                        &body,
+                        receipts,
+                        &total_difficulty,
+                    )?);
+                }
+                e2store::ACCUMULATOR => {
+                    let root: [u8; 32] = entry
+                        .data
+                        .as_slice()
+                        .try_into()
+                        .map_err(|_| Era1Error::InvalidEntry(entries.offset))?;
+                    accumulator_root = Some(B256::from(root));
+                }
+                e2store::BLOCK_INDEX => {
+                    block_index = Some(entry.data);
+                }
+                _ => {}
+            }
+        }
+
+        let accumulator_root = accumulator_root.ok_or(Era1Error::InvalidEntry(entries.offset))?;
+        let block_index = block_index.ok_or(Era1Error::InvalidEntry(entries.offset))?;
+        let start_block = read_u64(&block_index, 0).ok_or(Era1Error::InvalidBlockIndex)?;
+        let count = read_u64(&block_index, block_index.len().saturating_sub(8))
+            .ok_or(Era1Error::InvalidBlockIndex)?;
+        if count != blocks.len() as u64 || block_index.len() as u64 != 16 + 8 * count {
+            return Err(Era1Error::InvalidBlockIndex);
+        }
+
+        for (index, block) in blocks.iter().enumerate() {
+            if block.number() != start_block + index as u64 {
+                return Err(Era1Error::NonContiguous {
+                    expected: start_block + index as u64,
+                    found: block.number(),
+                });
+            }
+        }
+
+        Ok(Self {
+            start_block,
+            blocks,
+            accumulator_root,
+        })
+    }
+}
+
+/// Reads entries, keeping track of the offset for error reporting
+struct Entries<'a, R: Read> {
+    reader: &'a mut R,
+    offset: u64,
+}
+
+impl<R: Read> Entries<'_, R> {
+    fn next_entry(&mut self) -> Result<Option<Entry>, Era1Error> {
+        let entry = read_entry(self.reader, self.offset)?;
+        if let Some(entry) = &entry {
+            self.offset += HEADER_SIZE + entry.data.len() as u64;
+        }
+        Ok(entry)
+    }
+
+    /// Reads the next entry, which must be of type `entry_type`
+    fn expect(&mut self, entry_type: u16) -> Result<Vec<u8>, Era1Error> {
+        let offset = self.offset;
+        match self.next_entry()? {
+            Some(entry) if entry.entry_type == entry_type => Ok(entry.data),
+            _ => Err(Era1Error::InvalidEntry(offset)),
+        }
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes: [u8; 8] = data.get(offset..offset + 8)?.try_into().ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>, Era1Error> {
+    let mut decompressed = Vec::new();
+    snap::read::FrameDecoder::new(data).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
@@ -1,6 +1,13 @@
+use crate::accumulator::error::AccumulatorError;
+use crate::beacon::error::BeaconError;
+use crate::bstream::error::EnvelopeError;
+use crate::compression::error::CompressionError;
 use crate::dbin::error::DbinFileError;
+use crate::era1::error::Era1Error;
 use crate::headers::error::BlockHeaderError;
+use crate::output::error::OutputError;
 use crate::receipts::error::ReceiptError;
+use crate::store::error::StoreError;
 use crate::transactions::error::TransactionError;
 use thiserror::Error;
 use tokio::task::JoinError;
@@ -9,18 +16,32 @@ use tokio::task::JoinError;
 pub enum DecodeError {
     #[error("Invalid input")]
     InvalidInput,
-    #[error("error decompressing")]
-    DecompressError,
+    #[error("Compression Error: {0}")]
+    CompressionError(#[from] CompressionError),
     #[error("Dbin File Error: {0}")]
     DbinFileError(#[from] DbinFileError),
+    #[error("Invalid Block Envelope: {0}")]
+    EnvelopeError(#[from] EnvelopeError),
     #[error("Invalid Block Header: {0}")]
     BlockHeaderError(#[from] BlockHeaderError),
     #[error("Invalid Transaction Root: {0}")]
     TransactionRoot(#[from] TransactionError),
     #[error("Invalid Receipt Root: {0}")]
     ReceiptRoot(#[from] ReceiptError),
+    #[error("Era1 Error: {0}")]
+    Era1Error(#[from] Era1Error),
+    #[error("Accumulator Error: {0}")]
+    AccumulatorError(#[from] AccumulatorError),
+    #[error("Beacon Error: {0}")]
+    BeaconError(#[from] BeaconError),
+    #[error("Output Error: {0}")]
+    OutputError(#[from] OutputError),
+    #[error("Store Error: {0}")]
+    StoreError(#[from] StoreError),
     #[error("IO Error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
     #[error("Invalid content type: {0}")]
     InvalidContentType(String),
     #[error("Invalid protobuf: {0}")]
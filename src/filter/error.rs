@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Empty filter expression")]
+    Empty,
+    #[error("Invalid filter condition, expected key=value: {0}")]
+    InvalidCondition(String),
+    #[error("Unknown filter key: {0}")]
+    UnknownKey(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid topic: {0}")]
+    InvalidTopic(String),
+    #[error("Invalid call type: {0}")]
+    InvalidCallType(String),
+}
@@ -0,0 +1,321 @@
+pub mod error;
+
+use crate::filter::error::FilterError;
+use crate::output::error::OutputError;
+use crate::output::BlockSink;
+use alloy_primitives::{Address, Bloom, BloomInput, B256};
+use sf_protos::ethereum::r#type::v2::{Block, CallType, Log, TransactionTrace};
+use std::str::FromStr;
+
+/// `BlockFilter` selects the transactions of a block touching a handful of contracts.
+///
+/// It is parsed from an expression of comma separated `key=value` conditions:
+///
+/// * `from=<address>` and `to=<address>`: the sender and the recipient of the transaction
+/// * `log=<address>`: the address of a log of the transaction
+/// * `topic0=<hash>`: the first topic, i.e. the event signature, of a log of the transaction. Along with `log`, both must match the same log
+/// * `call=<type>`: the type of the root call of the transaction: `call`, `callcode`, `delegate`, `static` or `create`
+///
+/// Conditions with the same key match if any of them does, and a transaction matches if the
+/// conditions of every key do. For instance `to=0xa,to=0xb,topic0=0xc` matches the transactions
+/// sent to `0xa` or `0xb` emitting a `0xc` event.
+///
+/// Log conditions are first checked against the logs bloom of the block header, then of each
+/// receipt, so that most blocks and transactions are rejected without walking their logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFilter {
+    from: Vec<Address>,
+    to: Vec<Address>,
+    log_addresses: Vec<Address>,
+    topics: Vec<B256>,
+    call_types: Vec<CallType>,
+}
+
+impl BlockFilter {
+    /// Whether the logs bloom of `block` may hold a log matching the filter.
+    ///
+    /// `false` is definite, while `true` still needs the transactions of the block to be checked.
+    pub fn may_match(&self, block: &Block) -> bool {
+        match &block.header {
+            Some(header) => self.bloom_may_match(&header.logs_bloom),
+            None => true,
+        }
+    }
+
+    /// Whether `block` holds a transaction matching the filter
+    pub fn matches_block(&self, block: &Block) -> bool {
+        self.may_match(block)
+            && block
+                .transaction_traces
+                .iter()
+                .any(|trace| self.matches_transaction(trace))
+    }
+
+    pub fn matches_transaction(&self, trace: &TransactionTrace) -> bool {
+        matches_address(&self.from, &trace.from)
+            && matches_address(&self.to, &trace.to)
+            && (self.call_types.is_empty()
+                || trace
+                    .calls
+                    .first()
+                    .is_some_and(|call| self.call_types.contains(&call.call_type())))
+            && self.matches_logs(trace)
+    }
+
+    /// Limits `block` to its transactions matching the filter, along with their receipts.
+    ///
+    /// Returns `None` if none of them does.
+    pub fn filter_block(&self, block: &Block) -> Option<Block> {
+        if !self.matches_block(block) {
+            return None;
+        }
+        let mut filtered = block.clone();
+        filtered
+            .transaction_traces
+            .retain(|trace| self.matches_transaction(trace));
+        Some(filtered)
+    }
+
+    fn has_log_conditions(&self) -> bool {
+        !self.log_addresses.is_empty() || !self.topics.is_empty()
+    }
+
+    /// Whether `bloom` may hold a matching log, `true` if it isn't a valid 256 bytes bloom
+    fn bloom_may_match(&self, bloom: &[u8]) -> bool {
+        if !self.has_log_conditions() {
+            return true;
+        }
+        let bloom: [u8; 256] = match bloom.try_into() {
+            Ok(bloom) => bloom,
+            Err(_) => return true,
+        };
+        let bloom = Bloom::from(bloom);
+        let contains = |input: &[u8]| bloom.contains_input(BloomInput::Raw(input));
+
+        (self.log_addresses.is_empty()
+            || self
+                .log_addresses
+                .iter()
+                .any(|address| contains(address.as_slice())))
+            && (self.topics.is_empty()
+                || self.topics.iter().any(|topic| contains(topic.as_slice())))
+    }
+
+    fn matches_logs(&self, trace: &TransactionTrace) -> bool {
+        if !self.has_log_conditions() {
+            return true;
+        }
+        match &trace.receipt {
+            Some(receipt) => {
+                self.bloom_may_match(&receipt.logs_bloom)
+                    && receipt.logs.iter().any(|log| self.matches_log(log))
+            }
+            None => false,
+        }
+    }
+
+    fn matches_log(&self, log: &Log) -> bool {
+        matches_address(&self.log_addresses, &log.address)
+            && (self.topics.is_empty()
+                || log.topics.first().is_some_and(|topic0| {
+                    self.topics
+                        .iter()
+                        .any(|topic| topic.as_slice() == topic0.as_slice())
+                }))
+    }
+}
+
+/// Whether `bytes` is one of `addresses`, or any address if there are none
+fn matches_address(addresses: &[Address], bytes: &[u8]) -> bool {
+    addresses.is_empty() || addresses.iter().any(|address| address.as_slice() == bytes)
+}
+
+impl FromStr for BlockFilter {
+    type Err = FilterError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let mut filter = BlockFilter {
+            from: vec![],
+            to: vec![],
+            log_addresses: vec![],
+            topics: vec![],
+            call_types: vec![],
+        };
+
+        let conditions = expression
+            .split(',')
+            .map(str::trim)
+            .filter(|condition| !condition.is_empty());
+        for condition in conditions {
+            let (key, value) = condition
+                .split_once('=')
+                .ok_or_else(|| FilterError::InvalidCondition(condition.to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+            let address =
+                || Address::from_str(value).map_err(|_| FilterError::InvalidAddress(value.into()));
+            match key {
+                "from" => filter.from.push(address()?),
+                "to" => filter.to.push(address()?),
+                "log" => filter.log_addresses.push(address()?),
+                "topic0" => filter.topics.push(
+                    B256::from_str(value).map_err(|_| FilterError::InvalidTopic(value.into()))?,
+                ),
+                "call" => filter.call_types.push(
+                    CallType::from_str_name(&value.to_uppercase())
+                        .filter(|call_type| *call_type != CallType::Unspecified)
+                        .ok_or_else(|| FilterError::InvalidCallType(value.into()))?,
+                ),
+                _ => return Err(FilterError::UnknownKey(key.to_string())),
+            }
+        }
+
+        if filter.from.is_empty()
+            && filter.to.is_empty()
+            && filter.call_types.is_empty()
+            && !filter.has_log_conditions()
+        {
+            return Err(FilterError::Empty);
+        }
+        Ok(filter)
+    }
+}
+
+/// `FilterSink` writes the blocks holding transactions matching a [`BlockFilter`] to another
+/// [`BlockSink`], limited to those transactions and their receipts. Other blocks are skipped.
+///
+/// Blocks are verified in full, i.e. their transaction and receipt roots and, if any, their
+/// headers, before being passed to `FilterSink`, so a filtered decode costs as much as an
+/// unfiltered one and still fails on blocks that don't match their header.
+///
+/// Filtered blocks no longer match the transaction and receipt roots of their header, so they
+/// shouldn't be written in the era1 or RLP formats, which `decode --filter` rejects.
+pub struct FilterSink<S> {
+    sink: S,
+    filter: BlockFilter,
+}
+
+impl<S: BlockSink> FilterSink<S> {
+    pub fn new(sink: S, filter: BlockFilter) -> Self {
+        Self { sink, filter }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: BlockSink> BlockSink for FilterSink<S> {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        match self.filter.filter_block(block) {
+            Some(block) => self.sink.write_block(&block),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockFilter, FilterSink};
+    use crate::handle_file;
+    use crate::output::error::OutputError;
+    use crate::output::BlockSink;
+    use alloy_primitives::hex;
+    use sf_protos::ethereum::r#type::v2::Block;
+    use std::path::PathBuf;
+
+    fn block() -> Block {
+        let mut blocks = handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None)
+            .expect("Failed to decode example block");
+        blocks.remove(0)
+    }
+
+    #[derive(Default)]
+    struct BlocksSink(Vec<Block>);
+
+    impl BlockSink for BlocksSink {
+        fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+            self.0.push(block.clone());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<(), OutputError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter: BlockFilter =
+            "to=0xdAC17F958D2ee523a2206206994597C13D831ec7, call=create,call=STATIC"
+                .parse()
+                .unwrap();
+        assert_eq!(filter.to.len(), 1);
+        assert_eq!(filter.call_types.len(), 2);
+
+        assert!("".parse::<BlockFilter>().is_err());
+        assert!("to".parse::<BlockFilter>().is_err());
+        assert!("value=1".parse::<BlockFilter>().is_err());
+        assert!("log=0x1234".parse::<BlockFilter>().is_err());
+        assert!("call=unspecified".parse::<BlockFilter>().is_err());
+    }
+
+    #[test]
+    fn test_filter_logs() {
+        let block = block();
+        let (index, log) = block
+            .transaction_traces
+            .iter()
+            .enumerate()
+            .find_map(|(index, trace)| {
+                let receipt = trace.receipt.as_ref()?;
+                let log = receipt.logs.iter().find(|log| !log.topics.is_empty())?;
+                Some((index, log.clone()))
+            })
+            .expect("No log in the example block");
+
+        let expression = format!(
+            "log=0x{},topic0=0x{}",
+            hex::encode(&log.address),
+            hex::encode(&log.topics[0])
+        );
+        let filter: BlockFilter = expression.parse().unwrap();
+        let filtered = filter.filter_block(&block).unwrap();
+        assert!(!filtered.transaction_traces.is_empty());
+        assert!(filtered.transaction_traces.len() <= block.transaction_traces.len());
+        assert!(filtered
+            .transaction_traces
+            .contains(&block.transaction_traces[index]));
+        assert_eq!(filtered.header, block.header);
+
+        // An address missing from the logs bloom rejects the block without walking its transactions
+        let filter: BlockFilter = "log=0x00000000000000000000000000000000000000ff"
+            .parse()
+            .unwrap();
+        assert!(!filter.may_match(&block));
+        assert_eq!(filter.filter_block(&block), None);
+    }
+
+    #[test]
+    fn test_filter_sink() {
+        let block = block();
+        let trace = &block.transaction_traces[0];
+        let expression = format!("from=0x{}", hex::encode(&trace.from));
+        let filter: BlockFilter = expression.parse().unwrap();
+
+        let mut sink = FilterSink::new(BlocksSink::default(), filter);
+        sink.write_block(&block).unwrap();
+        sink.write_block(&Block::default()).unwrap();
+        sink.finish().unwrap();
+
+        let blocks = sink.into_inner().0;
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0]
+            .transaction_traces
+            .iter()
+            .all(|filtered| filtered.from == trace.from));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::error::DecodeError;
+use crate::filter::BlockFilter;
+use crate::firehose::error::FirehoseError;
+use crate::firehose::{
+    ForkStep, Request, Response, SingleBlockRequest, SingleBlockResponse, ETHEREUM_BLOCK_TYPE_URL,
+};
+use crate::output::BlockSink;
+use crate::{verify_block, write_header_record};
+use prost::Message;
+use prost_types::Any;
+use sf_protos::ethereum::r#type::v2::Block;
+use std::io::Write;
+use tonic::client::Grpc;
+use tonic::codec::{ProstCodec, Streaming};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
+
+/// `FirehoseClient` reads blocks from a Firehose endpoint, with the `sf.firehose.v2.Stream`
+/// and `sf.firehose.v2.Fetch` services.
+#[derive(Clone)]
+pub struct FirehoseClient {
+    grpc: Grpc<Channel>,
+}
+
+impl FirehoseClient {
+    /// Connects to `endpoint`, like `http://localhost:10015`
+    pub async fn connect(endpoint: String) -> Result<Self, FirehoseError> {
+        let channel = Channel::from_shared(endpoint)
+            .map_err(|err| FirehoseError::InvalidRequest(err.to_string()))?
+            .connect()
+            .await?;
+        Ok(Self {
+            grpc: Grpc::new(channel),
+        })
+    }
+
+    /// Calls `Stream/Blocks`
+    pub async fn blocks(&mut self, request: Request) -> Result<Streaming<Response>, FirehoseError> {
+        self.grpc.ready().await?;
+        let response = self
+            .grpc
+            .server_streaming(
+                tonic::Request::new(request),
+                PathAndQuery::from_static("/sf.firehose.v2.Stream/Blocks"),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Calls `Fetch/Block`
+    pub async fn block(
+        &mut self,
+        request: SingleBlockRequest,
+    ) -> Result<SingleBlockResponse, FirehoseError> {
+        self.grpc.ready().await?;
+        let response = self
+            .grpc
+            .unary(
+                tonic::Request::new(request),
+                PathAndQuery::from_static("/sf.firehose.v2.Fetch/Block"),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Streams the final blocks from `start_block` to `stop_block` included, see [`Request`]
+    async fn final_blocks(
+        &mut self,
+        start_block: i64,
+        stop_block: u64,
+    ) -> Result<Streaming<Response>, FirehoseError> {
+        self.blocks(Request {
+            start_block_num: start_block,
+            stop_block_num: stop_block,
+            final_blocks_only: true,
+            ..Default::default()
+        })
+        .await
+    }
+}
+
+/// Decodes an Ethereum block sent by Firehose and verifies it, see [`crate::decode_flat_files`].
+///
+/// # Arguments
+///
+/// * `block`: The block of a [`Response`] or [`SingleBlockResponse`].
+/// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
+pub fn decode_block(
+    block: Option<&Any>,
+    headers_dir: Option<&str>,
+) -> Result<Block, FirehoseError> {
+    let block = block.ok_or(FirehoseError::MissingBlock)?;
+    if block.type_url != ETHEREUM_BLOCK_TYPE_URL {
+        return Err(FirehoseError::InvalidBlockType(block.type_url.clone()));
+    }
+    let block = Block::decode(block.value.as_slice())
+        .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+    Ok(verify_block(block, headers_dir)?)
+}
+
+/// Decodes the block of each response of `stream` as it arrives, off the async runtime
+async fn next_block(
+    stream: &mut Streaming<Response>,
+    headers_dir: Option<&str>,
+) -> Result<Option<(Block, String)>, FirehoseError> {
+    let response = match stream.message().await? {
+        Some(response) => response,
+        None => return Ok(None),
+    };
+    if response.step != ForkStep::StepFinal as i32 {
+        return Err(FirehoseError::InvalidRequest(format!(
+            "expected final blocks only, got step {}",
+            response.step
+        )));
+    }
+
+    let headers_dir = headers_dir.map(str::to_string);
+    let block = tokio::task::spawn_blocking(move || {
+        decode_block(response.block.as_ref(), headers_dir.as_deref())
+    })
+    .await
+    .map_err(DecodeError::JoinError)??;
+    Ok(Some((block, response.cursor)))
+}
+
+/// Streams blocks from Firehose and writes their header records to `writer`, the same way
+/// [`crate::stream_blocks`] does for flat files.
+///
+/// The receipt and transaction roots of each block are verified as it arrives, and streaming
+/// stops at the first invalid block.
+///
+/// # Arguments
+///
+/// * `client`: The [`FirehoseClient`] to stream blocks from.
+/// * `start_block`: The first block, negative values being relative to the head block.
+/// * `stop_block`: The last block, included.
+/// * `writer`: where header records are written to
+/// * `filter`: An optional [`BlockFilter`], only the blocks holding matching transactions are written.
+///
+/// Returns the cursor of the last block streamed, to resume streaming from.
+pub async fn stream_blocks<W: Write>(
+    client: &mut FirehoseClient,
+    start_block: i64,
+    stop_block: u64,
+    mut writer: W,
+    filter: Option<&BlockFilter>,
+) -> Result<String, FirehoseError> {
+    let mut stream = client.final_blocks(start_block, stop_block).await?;
+    let mut cursor = String::new();
+    while let Some((block, block_cursor)) = next_block(&mut stream, None).await? {
+        let filtered_out = filter.is_some_and(|filter| !filter.matches_block(&block));
+        if !filtered_out {
+            write_header_record(block, &mut writer)?;
+        }
+        cursor = block_cursor;
+    }
+    Ok(cursor)
+}
+
+/// Decodes and verifies blocks streamed from Firehose, the same way
+/// [`crate::decode_flat_files_with_sink`] does for flat files.
+///
+/// # Arguments
+///
+/// * `client`: The [`FirehoseClient`] to stream blocks from.
+/// * `start_block`: The first block, negative values being relative to the head block.
+/// * `stop_block`: The last block, included.
+/// * `sink`: An optional [`BlockSink`] the decoded blocks are written to, see [`crate::output::open_sink`].
+/// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
+pub async fn decode_blocks(
+    client: &mut FirehoseClient,
+    start_block: i64,
+    stop_block: u64,
+    mut sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+) -> Result<Vec<Block>, FirehoseError> {
+    let mut stream = client.final_blocks(start_block, stop_block).await?;
+    let mut blocks = Vec::new();
+    while let Some((block, _)) = next_block(&mut stream, headers_dir).await? {
+        if let Some(sink) = sink.as_deref_mut() {
+            sink.write_block(&block).map_err(DecodeError::OutputError)?;
+        }
+        blocks.push(block);
+    }
+
+    if let Some(sink) = sink {
+        sink.finish().map_err(DecodeError::OutputError)?;
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_blocks, stream_blocks, FirehoseClient};
+    use crate::firehose::server::{serve_with_listener, FirehoseServer};
+    use crate::firehose::BlockCursor;
+    use crate::handle_file;
+    use crate::index::BlockIndex;
+    use std::path::{Path, PathBuf};
+    use tokio::net::TcpListener;
+
+    async fn client() -> FirehoseClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = FirehoseServer::new(BlockIndex::build(Path::new("tests")).unwrap());
+        tokio::spawn(serve_with_listener(server, listener));
+        FirehoseClient::connect(endpoint).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_decode_blocks() {
+        let mut client = client().await;
+        let expected = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let blocks = decode_blocks(&mut client, 0, 9, None, None).await.unwrap();
+        assert_eq!(blocks.as_slice(), &expected[..10]);
+
+        assert!(decode_blocks(&mut client, 90, 100, None, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_blocks() {
+        let mut client = client().await;
+
+        let mut records = Vec::new();
+        let cursor = stream_blocks(&mut client, -5, 0, &mut records, None)
+            .await
+            .unwrap();
+        assert_eq!(cursor.parse::<BlockCursor>().unwrap().number, 99);
+
+        let mut frames = 0;
+        let mut remaining = records.as_slice();
+        while !remaining.is_empty() {
+            let size = u32::from_be_bytes(remaining[..4].try_into().unwrap()) as usize;
+            remaining = &remaining[4 + size..];
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::error::DecodeError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FirehoseError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Decode error: {0}")]
+    DecodeError(#[from] DecodeError),
+    #[error("Transport error: {0}")]
+    TransportError(#[from] tonic::transport::Error),
+    #[error("gRPC error: {0}")]
+    StatusError(Box<tonic::Status>),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Block not found: {0}")]
+    BlockNotFound(String),
+    #[error("Missing block in response")]
+    MissingBlock,
+    #[error("Invalid block type: {0}")]
+    InvalidBlockType(String),
+}
+
+impl From<FirehoseError> for tonic::Status {
+    fn from(err: FirehoseError) -> Self {
+        match err {
+            FirehoseError::InvalidCursor(_) | FirehoseError::InvalidRequest(_) => {
+                tonic::Status::invalid_argument(err.to_string())
+            }
+            FirehoseError::BlockNotFound(_) => tonic::Status::not_found(err.to_string()),
+            FirehoseError::StatusError(status) => *status,
+            err => tonic::Status::internal(err.to_string()),
+        }
+    }
+}
+
+impl From<tonic::Status> for FirehoseError {
+    fn from(status: tonic::Status) -> Self {
+        FirehoseError::StatusError(Box::new(status))
+    }
+}
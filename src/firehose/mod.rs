@@ -0,0 +1,140 @@
+//! Firehose `sf.firehose.v2` messages and services.
+//!
+//! The messages mirror `sf/firehose/v2/firehose.proto`, as `sf-protos` doesn't provide them
+//! nor their gRPC services.
+
+pub mod client;
+pub mod error;
+pub mod server;
+
+use crate::firehose::error::FirehoseError;
+use prost_types::Any;
+use reth_primitives::hex;
+use std::fmt;
+use std::str::FromStr;
+
+/// Type URL of the Ethereum blocks sent in [`Response::block`]
+pub const ETHEREUM_BLOCK_TYPE_URL: &str = "type.googleapis.com/sf.ethereum.type.v2.Block";
+
+/// `Request` is the `sf.firehose.v2.Request` message of `Stream/Blocks`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Request {
+    /// First block to stream, negative values being relative to the head block: `-1` is the head block
+    #[prost(int64, tag = "1")]
+    pub start_block_num: i64,
+    /// Cursor of the last block received, streaming resumes after it. Supersedes `start_block_num`
+    #[prost(string, tag = "2")]
+    pub cursor: String,
+    /// Last block to stream, inclusive. Streams until the head block if `0`
+    #[prost(uint64, tag = "3")]
+    pub stop_block_num: u64,
+    #[prost(bool, tag = "4")]
+    pub final_blocks_only: bool,
+    #[prost(message, repeated, tag = "10")]
+    pub transforms: Vec<Any>,
+}
+
+/// `Response` is the `sf.firehose.v2.Response` message streamed by `Stream/Blocks`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Response {
+    #[prost(message, optional, tag = "1")]
+    pub block: Option<Any>,
+    #[prost(enumeration = "ForkStep", tag = "6")]
+    pub step: i32,
+    #[prost(string, tag = "10")]
+    pub cursor: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ForkStep {
+    StepUnset = 0,
+    /// Block is new and can still be reverted
+    StepNew = 1,
+    /// Block was reverted
+    StepUndo = 2,
+    /// Block is irreversible
+    StepFinal = 3,
+}
+
+/// `SingleBlockRequest` is the `sf.firehose.v2.SingleBlockRequest` message of `Fetch/Block`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SingleBlockRequest {
+    #[prost(message, repeated, tag = "6")]
+    pub transforms: Vec<Any>,
+    #[prost(oneof = "single_block_request::Reference", tags = "3, 4, 5")]
+    pub reference: Option<single_block_request::Reference>,
+}
+
+pub mod single_block_request {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BlockNumber {
+        #[prost(uint64, tag = "1")]
+        pub num: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BlockHashAndNumber {
+        #[prost(uint64, tag = "1")]
+        pub num: u64,
+        #[prost(string, tag = "2")]
+        pub hash: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Cursor {
+        #[prost(string, tag = "1")]
+        pub cursor: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Reference {
+        #[prost(message, tag = "3")]
+        BlockNumber(BlockNumber),
+        #[prost(message, tag = "4")]
+        BlockHashAndNumber(BlockHashAndNumber),
+        #[prost(message, tag = "5")]
+        Cursor(Cursor),
+    }
+}
+
+/// `SingleBlockResponse` is the `sf.firehose.v2.SingleBlockResponse` message of `Fetch/Block`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SingleBlockResponse {
+    #[prost(message, optional, tag = "1")]
+    pub block: Option<Any>,
+}
+
+/// `BlockCursor` locates a block streamed from flat files, as `<number>:<hash>` with the hash
+/// hex encoded without `0x` prefix.
+///
+/// Flat files only hold irreversible blocks, so unlike the opaque cursors of a Firehose node
+/// it doesn't need to track forks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockCursor {
+    pub number: u64,
+    pub hash: Vec<u8>,
+}
+
+impl fmt::Display for BlockCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.number, hex::encode(&self.hash))
+    }
+}
+
+impl FromStr for BlockCursor {
+    type Err = FirehoseError;
+
+    fn from_str(cursor: &str) -> Result<Self, Self::Err> {
+        let (number, hash) = cursor
+            .split_once(':')
+            .ok_or_else(|| FirehoseError::InvalidCursor(cursor.to_string()))?;
+        Ok(Self {
+            number: number
+                .parse()
+                .map_err(|_| FirehoseError::InvalidCursor(cursor.to_string()))?,
+            hash: hex::decode(hash)
+                .map_err(|_| FirehoseError::InvalidCursor(cursor.to_string()))?,
+        })
+    }
+}
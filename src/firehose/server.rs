@@ -0,0 +1,494 @@
+use crate::firehose::error::FirehoseError;
+use crate::firehose::single_block_request::Reference;
+use crate::firehose::{
+    BlockCursor, ForkStep, Request, Response, SingleBlockRequest, SingleBlockResponse,
+    ETHEREUM_BLOCK_TYPE_URL,
+};
+use crate::index::{BlockIndex, BlockLookup, BlockReader};
+use prost::Message;
+use prost_types::Any;
+use reth_primitives::hex;
+use sf_protos::ethereum::r#type::v2::Block;
+use simple_log::log;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::codegen::{http, Body, BoxFuture, Context, Poll, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::Status;
+
+/// Number of blocks read ahead of a `Stream/Blocks` client
+const STREAM_BUFFER: usize = 16;
+
+/// `FirehoseServer` serves the blocks of a folder of flat files as a Firehose node would, with
+/// the `sf.firehose.v2.Stream/Blocks` and `sf.firehose.v2.Fetch/Block` services.
+///
+/// Streams end at the last block of the folder, or earlier with a stop block. Flat files only
+/// hold irreversible blocks: they are sent as [`ForkStep::StepFinal`] when final blocks only
+/// are requested, [`ForkStep::StepNew`] otherwise, and never undone.
+///
+/// Blocks are read with a [`BlockReader`], or any other [`BlockLookup`] like a
+/// [`crate::index::remote::RemoteBlockStore`].
+pub struct FirehoseServer {
+    reader: Box<dyn BlockLookup>,
+}
+
+impl FirehoseServer {
+    pub fn new(index: BlockIndex) -> Self {
+        Self::with_lookup(Box::new(BlockReader::new(index)))
+    }
+
+    /// Serves the blocks read by `reader`
+    pub fn with_lookup(reader: Box<dyn BlockLookup>) -> Self {
+        Self { reader }
+    }
+
+    /// Streams the blocks of `request`, reading them ahead of the client
+    pub fn blocks(
+        self: Arc<Self>,
+        request: &Request,
+    ) -> Result<ReceiverStream<Result<Response, Status>>, FirehoseError> {
+        let (start, stop) = self.range(request)?;
+        let step = if request.final_blocks_only {
+            ForkStep::StepFinal
+        } else {
+            ForkStep::StepNew
+        };
+
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            for number in start..=stop {
+                let response: Result<Response, Status> = match self.reader.block(number) {
+                    Ok(Some(block)) => Ok(response(&block, step)),
+                    Ok(None) => Err(FirehoseError::BlockNotFound(number.to_string()).into()),
+                    Err(err) => Err(FirehoseError::from(err).into()),
+                };
+                let failed = response.is_err();
+                // the client is gone if the stream is closed
+                if sender.blocking_send(response).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(receiver))
+    }
+
+    /// Reads the block of `request`
+    pub fn fetch(
+        &self,
+        request: &SingleBlockRequest,
+    ) -> Result<SingleBlockResponse, FirehoseError> {
+        let block = match &request.reference {
+            Some(Reference::BlockNumber(reference)) => self.block(reference.num)?,
+            Some(Reference::BlockHashAndNumber(reference)) => {
+                let hash = hex::decode(&reference.hash)
+                    .map_err(|_| FirehoseError::InvalidRequest(reference.hash.clone()))?;
+                self.block_at_cursor(&BlockCursor {
+                    number: reference.num,
+                    hash,
+                })?
+            }
+            Some(Reference::Cursor(reference)) => {
+                self.block_at_cursor(&reference.cursor.parse()?)?
+            }
+            None => {
+                return Err(FirehoseError::InvalidRequest(
+                    "missing block reference".to_string(),
+                ))
+            }
+        };
+        Ok(SingleBlockResponse {
+            block: Some(any_block(&block)),
+        })
+    }
+
+    /// First and last blocks of a `Stream/Blocks` request.
+    ///
+    /// The start is clamped to the first indexed block. The range is empty, the first block
+    /// being right after the last one, when resuming from a cursor at the stop block.
+    fn range(&self, request: &Request) -> Result<(u64, u64), FirehoseError> {
+        let index = self.reader.index();
+        let (earliest, latest) = index
+            .earliest()
+            .zip(index.latest())
+            .ok_or_else(|| FirehoseError::BlockNotFound("no blocks indexed".to_string()))?;
+
+        let start = if !request.cursor.is_empty() {
+            let cursor: BlockCursor = request.cursor.parse()?;
+            self.block_at_cursor(&cursor)?;
+            cursor.number + 1
+        } else if request.start_block_num < 0 {
+            (latest + 1).saturating_sub(request.start_block_num.unsigned_abs())
+        } else {
+            request.start_block_num as u64
+        };
+        let start = start.max(earliest);
+        let stop = match request.stop_block_num {
+            0 => latest,
+            stop => stop,
+        };
+
+        if stop > latest {
+            return Err(FirehoseError::InvalidRequest(format!(
+                "stop block {} is after the last block {}",
+                stop, latest
+            )));
+        }
+        if stop + 1 < start {
+            return Err(FirehoseError::InvalidRequest(format!(
+                "stop block {} is before the start block {}",
+                stop, start
+            )));
+        }
+        Ok((start, stop))
+    }
+
+    fn block(&self, number: u64) -> Result<Block, FirehoseError> {
+        self.reader
+            .block(number)?
+            .ok_or_else(|| FirehoseError::BlockNotFound(number.to_string()))
+    }
+
+    /// Reads the block of `cursor`, checking its hash
+    fn block_at_cursor(&self, cursor: &BlockCursor) -> Result<Block, FirehoseError> {
+        let block = self.block(cursor.number)?;
+        if block.hash != cursor.hash {
+            return Err(FirehoseError::BlockNotFound(cursor.to_string()));
+        }
+        Ok(block)
+    }
+}
+
+fn any_block(block: &Block) -> Any {
+    Any {
+        type_url: ETHEREUM_BLOCK_TYPE_URL.to_string(),
+        value: block.encode_to_vec(),
+    }
+}
+
+fn response(block: &Block, step: ForkStep) -> Response {
+    Response {
+        block: Some(any_block(block)),
+        step: step as i32,
+        cursor: BlockCursor {
+            number: block.number,
+            hash: block.hash.clone(),
+        }
+        .to_string(),
+    }
+}
+
+/// Serves the `Stream` and `Fetch` services on `addr`, until the server fails.
+pub async fn serve(server: FirehoseServer, addr: SocketAddr) -> Result<(), FirehoseError> {
+    serve_with_listener(server, TcpListener::bind(addr).await?).await
+}
+
+/// Same as [`serve`], accepting connections from `listener`
+pub async fn serve_with_listener(
+    server: FirehoseServer,
+    listener: TcpListener,
+) -> Result<(), FirehoseError> {
+    let server = Arc::new(server);
+    log::info!("Serving Firehose on {}", listener.local_addr()?);
+    tonic::transport::Server::builder()
+        .add_service(StreamService(server.clone()))
+        .add_service(FetchService(server))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await?;
+    Ok(())
+}
+
+/// `StreamService` is the gRPC service of `sf.firehose.v2.Stream`
+#[derive(Clone)]
+pub struct StreamService(pub Arc<FirehoseServer>);
+
+/// `FetchService` is the gRPC service of `sf.firehose.v2.Fetch`
+#[derive(Clone)]
+pub struct FetchService(pub Arc<FirehoseServer>);
+
+impl NamedService for StreamService {
+    const NAME: &'static str = "sf.firehose.v2.Stream";
+}
+
+impl NamedService for FetchService {
+    const NAME: &'static str = "sf.firehose.v2.Fetch";
+}
+
+impl ServerStreamingService<Request> for StreamService {
+    type Response = Response;
+    type ResponseStream = ReceiverStream<Result<Response, Status>>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Request>) -> Self::Future {
+        let server = self.0.clone();
+        Box::pin(async move {
+            // resolving the range reads the block of the cursor, which is blocking
+            let stream = tokio::task::spawn_blocking(move || server.blocks(request.get_ref()))
+                .await
+                .map_err(|err| Status::internal(err.to_string()))??;
+            Ok(tonic::Response::new(stream))
+        })
+    }
+}
+
+impl UnaryService<SingleBlockRequest> for FetchService {
+    type Response = SingleBlockResponse;
+    type Future = BoxFuture<tonic::Response<SingleBlockResponse>, Status>;
+
+    fn call(&mut self, request: tonic::Request<SingleBlockRequest>) -> Self::Future {
+        let server = self.0.clone();
+        Box::pin(async move {
+            // reading flat files is blocking
+            let response = tokio::task::spawn_blocking(move || server.fetch(request.get_ref()))
+                .await
+                .map_err(|err| Status::internal(err.to_string()))??;
+            Ok(tonic::Response::new(response))
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for StreamService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.clone();
+        match request.uri().path() {
+            "/sf.firehose.v2.Stream/Blocks" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.server_streaming(service, request).await)
+            }),
+            _ => Box::pin(async move { Ok(unimplemented()) }),
+        }
+    }
+}
+
+impl<B> Service<http::Request<B>> for FetchService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.clone();
+        match request.uri().path() {
+            "/sf.firehose.v2.Fetch/Block" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(service, request).await)
+            }),
+            _ => Box::pin(async move { Ok(unimplemented()) }),
+        }
+    }
+}
+
+/// Response to an unknown method of a service
+fn unimplemented() -> http::Response<tonic::body::BoxBody> {
+    Status::unimplemented("unknown method").into_http()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FirehoseServer, StreamService};
+    use crate::firehose::single_block_request::{BlockHashAndNumber, BlockNumber, Reference};
+    use crate::firehose::{BlockCursor, ForkStep, Request, SingleBlockRequest};
+    use crate::index::BlockIndex;
+    use prost::Message;
+    use sf_protos::ethereum::r#type::v2::Block;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio_stream::StreamExt;
+    use tonic::server::ServerStreamingService;
+
+    fn server() -> Arc<FirehoseServer> {
+        Arc::new(FirehoseServer::new(
+            BlockIndex::build(Path::new("tests")).unwrap(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_stream_blocks() {
+        let server = server();
+        let request = Request {
+            start_block_num: 10,
+            stop_block_num: 14,
+            final_blocks_only: true,
+            ..Default::default()
+        };
+        let responses: Vec<_> = server
+            .clone()
+            .blocks(&request)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 5);
+        for (response, number) in responses.iter().zip(10..) {
+            let block = Block::decode(response.block.as_ref().unwrap().value.as_slice()).unwrap();
+            assert_eq!(block.number, number);
+            assert_eq!(response.step, ForkStep::StepFinal as i32);
+            let cursor: BlockCursor = response.cursor.parse().unwrap();
+            assert_eq!(cursor.number, number);
+            assert_eq!(cursor.hash, block.hash);
+        }
+
+        // resumes after the cursor, up to the last block
+        let request = Request {
+            cursor: responses[4].cursor.clone(),
+            ..Default::default()
+        };
+        let numbers: Vec<u64> = server
+            .clone()
+            .blocks(&request)
+            .unwrap()
+            .map(|response| {
+                let block = response.unwrap().block.unwrap();
+                Block::decode(block.value.as_slice()).unwrap().number
+            })
+            .collect()
+            .await;
+        assert_eq!(numbers, (15..100).collect::<Vec<u64>>());
+
+        let request = Request {
+            start_block_num: -2,
+            ..Default::default()
+        };
+        let responses: Vec<_> = server.clone().blocks(&request).unwrap().collect().await;
+        assert_eq!(responses.len(), 2);
+
+        // nothing is left after a cursor at the last block
+        let last = server
+            .fetch(&SingleBlockRequest {
+                reference: Some(Reference::BlockNumber(BlockNumber { num: 99 })),
+                ..Default::default()
+            })
+            .unwrap();
+        let last = Block::decode(last.block.unwrap().value.as_slice()).unwrap();
+        let request = Request {
+            cursor: BlockCursor {
+                number: 99,
+                hash: last.hash,
+            }
+            .to_string(),
+            ..Default::default()
+        };
+        let responses: Vec<_> = server.clone().blocks(&request).unwrap().collect().await;
+        assert!(responses.is_empty());
+
+        let request = Request {
+            cursor: "14:00".to_string(),
+            ..Default::default()
+        };
+        assert!(server.clone().blocks(&request).is_err());
+
+        let request = Request {
+            start_block_num: 10,
+            stop_block_num: 100,
+            ..Default::default()
+        };
+        assert!(server.blocks(&request).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_before_first_block() {
+        let dir = std::env::temp_dir().join("decoder-test-firehose-first-block");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("example0017686312.dbin", dir.join("0017686312.dbin")).unwrap();
+        let server = Arc::new(FirehoseServer::new(BlockIndex::build(&dir).unwrap()));
+
+        // the start is clamped to the first indexed block
+        let request = Request {
+            start_block_num: 0,
+            ..Default::default()
+        };
+        let numbers: Vec<u64> = server
+            .blocks(&request)
+            .unwrap()
+            .map(|response| {
+                let block = response.unwrap().block.unwrap();
+                Block::decode(block.value.as_slice()).unwrap().number
+            })
+            .collect()
+            .await;
+        assert_eq!(numbers, vec![17686312]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_service() {
+        let mut service = StreamService(server());
+        let request = Request {
+            start_block_num: 95,
+            ..Default::default()
+        };
+        let numbers: Vec<u64> = service
+            .call(tonic::Request::new(request))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|response| {
+                let block = response.unwrap().block.unwrap();
+                Block::decode(block.value.as_slice()).unwrap().number
+            })
+            .collect()
+            .await;
+        assert_eq!(numbers, (95..100).collect::<Vec<u64>>());
+
+        // the block of the cursor doesn't match
+        let request = Request {
+            cursor: "5:00".to_string(),
+            ..Default::default()
+        };
+        let status = service
+            .call(tonic::Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn test_fetch_block() {
+        let server = server();
+        let request = SingleBlockRequest {
+            reference: Some(Reference::BlockNumber(BlockNumber { num: 7 })),
+            ..Default::default()
+        };
+        let response = server.fetch(&request).unwrap();
+        let block = Block::decode(response.block.unwrap().value.as_slice()).unwrap();
+        assert_eq!(block.number, 7);
+
+        let request = SingleBlockRequest {
+            reference: Some(Reference::BlockHashAndNumber(BlockHashAndNumber {
+                num: 8,
+                hash: reth_primitives::hex::encode(&block.hash),
+            })),
+            ..Default::default()
+        };
+        assert!(server.fetch(&request).is_err());
+
+        let request = SingleBlockRequest {
+            reference: Some(Reference::BlockNumber(BlockNumber { num: 100 })),
+            ..Default::default()
+        };
+        assert!(server.fetch(&request).is_err());
+    }
+}
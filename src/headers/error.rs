@@ -1,4 +1,8 @@
+use crate::convert::error::ConvertError;
+use crate::headers::reference::HeaderDiff;
+#[allow(deprecated)]
 use crate::headers::BlockHeaderRoots;
+use reth_primitives::U256;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,10 +13,43 @@ pub enum BlockHeaderError {
     JsonError(#[from] serde_json::Error),
     #[error("Invalid input")]
     InvalidInput,
+    #[deprecated(note = "no longer returned, see `BlockHeaderError::MismatchedFields`")]
+    #[allow(deprecated)]
     #[error("Mismatched roots")]
     MismatchedRoots(Box<(BlockHeaderRoots, BlockHeaderRoots)>),
+    #[error("Header of block {0} doesn't match the reference header: {1}")]
+    MismatchedFields(u64, HeaderDiff),
+    #[error("Missing reference header of block {0}")]
+    MissingReference(u64),
+    #[error("Invalid header source: {0}")]
+    InvalidSource(String),
+    #[error(
+        "Unsupported header source {0}, expected a folder or a .jsonl, .csv, .era1 or .bin file"
+    )]
+    UnsupportedSource(String),
     #[error("Missing header")]
     MissingHeader,
     #[error("Invalid total difficulty")]
     InvalidTotalDifficulty,
+    #[error("Invalid header field {0}")]
+    InvalidField(String),
+    #[error("Total difficulty {recorded} of block {block} doesn't match the computed {expected}")]
+    MismatchedTotalDifficulty {
+        block: u64,
+        expected: U256,
+        recorded: U256,
+    },
+    #[error("Expected block {expected}, found block {found}")]
+    NonContiguous { expected: u64, found: u64 },
+}
+
+impl From<ConvertError> for BlockHeaderError {
+    fn from(err: ConvertError) -> Self {
+        match err {
+            ConvertError::InvalidField { field, value } => {
+                BlockHeaderError::InvalidField(format!("{}: {}", field, value))
+            }
+            ConvertError::MissingField(field) => BlockHeaderError::InvalidField(field.to_string()),
+        }
+    }
 }
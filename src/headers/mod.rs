@@ -1,51 +1,41 @@
 pub mod error;
+pub mod reference;
+pub mod source;
+pub mod total_difficulty;
 
+use crate::convert::{bigint_to_u256, optional_b256, timestamp, to_address, to_b256, to_bloom};
 use crate::headers::error::BlockHeaderError;
-use reth_primitives::B256;
+use crate::headers::source::{HeaderSource, JsonDirSource};
+use crate::transactions::bigint_to_u128;
+use reth_primitives::{Bytes, Header, B256};
 use serde::{Deserialize, Serialize};
 use sf_protos::ethereum::r#type::v2::{Block, BlockHeader};
-use std::fs::File;
 
+/// Receipt and transactions roots of a block header.
+#[deprecated(note = "compare headers field by field with `reference::ReferenceHeader::check`")]
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BlockHeaderRoots {
     pub receipt_root: B256,
     pub transactions_root: B256,
 }
 
+#[allow(deprecated)]
 impl TryFrom<BlockHeader> for BlockHeaderRoots {
     type Error = BlockHeaderError;
 
     fn try_from(header: BlockHeader) -> Result<Self, Self::Error> {
-        let receipt_root: [u8; 32] = header.receipt_root.as_slice().try_into().unwrap();
-        let transactions_root: [u8; 32] = header.transactions_root.as_slice().try_into().unwrap();
-
         Ok(Self {
-            receipt_root: receipt_root.into(),
-            transactions_root: transactions_root.into(),
+            receipt_root: to_b256("receipt_root", &header.receipt_root)?,
+            transactions_root: to_b256("transactions_root", &header.transactions_root)?,
         })
     }
 }
 
+/// Checks the header of `block` field by field against the trusted header of `header_dir`,
+/// stored as `block-<number>.json` or `<number>.json`. See [`reference::ReferenceHeader`] for the formats
+/// accepted, and [`source`] for other sources of headers.
 pub fn check_valid_header(block: &Block, header_dir: &str) -> Result<(), BlockHeaderError> {
-    let header_file_path = format!("{}/{}.json", header_dir, block.number);
-    let header_file = File::open(header_file_path)?;
-
-    let header_roots: BlockHeaderRoots = serde_json::from_reader(header_file)?; // TODO: Errors
-
-    let block_header = match block.header.as_ref() {
-        Some(header) => header,
-        None => return Err(BlockHeaderError::MissingHeader),
-    };
-    let block_header_roots: BlockHeaderRoots = block_header.clone().try_into()?; // TODO: Errors
-
-    if header_roots != block_header_roots {
-        return Err(BlockHeaderError::MismatchedRoots(Box::new((
-            header_roots,
-            block_header_roots,
-        ))));
-    }
-
-    Ok(())
+    JsonDirSource::new(header_dir).check(block)
 }
 
 #[derive(Serialize, Deserialize)]
@@ -58,7 +48,14 @@ pub struct HeaderRecordWithNumber {
 impl TryFrom<Block> for HeaderRecordWithNumber {
     type Error = BlockHeaderError;
     fn try_from(block: Block) -> Result<Self, Self::Error> {
-        let block_header = match block.header.clone() {
+        HeaderRecordWithNumber::try_from(&block)
+    }
+}
+
+impl TryFrom<&Block> for HeaderRecordWithNumber {
+    type Error = BlockHeaderError;
+    fn try_from(block: &Block) -> Result<Self, Self::Error> {
+        let block_header = match block.header.as_ref() {
             Some(header) => header,
             None => {
                 return Err(BlockHeaderError::MissingHeader);
@@ -77,3 +74,108 @@ impl TryFrom<Block> for HeaderRecordWithNumber {
         Ok(header_record_with_number)
     }
 }
+
+/// Converts a Firehose block header into a [`Header`], whose RLP encoding is the canonical one.
+///
+/// The keccak hash of the encoded header, [`Header::hash_slow`], is the block hash.
+/// Fields introduced by forks (base fee, withdrawals root, blob gas, parent beacon root)
+/// are `None` when they are missing from the Firehose header. The requests root of Prague
+/// isn't held by Firehose headers, so headers from Prague on don't hash to their block hash.
+pub fn block_header_to_header(header: &BlockHeader) -> Result<Header, BlockHeaderError> {
+    Ok(Header {
+        parent_hash: to_b256("parent_hash", &header.parent_hash)?,
+        ommers_hash: to_b256("uncle_hash", &header.uncle_hash)?,
+        beneficiary: to_address("coinbase", &header.coinbase)?,
+        state_root: to_b256("state_root", &header.state_root)?,
+        transactions_root: to_b256("transactions_root", &header.transactions_root)?,
+        receipts_root: to_b256("receipt_root", &header.receipt_root)?,
+        withdrawals_root: optional_b256("withdrawals_root", &header.withdrawals_root)?,
+        logs_bloom: to_bloom("logs_bloom", &header.logs_bloom)?,
+        difficulty: bigint_to_u256("difficulty", header.difficulty.as_ref())?,
+        number: header.number,
+        gas_limit: header.gas_limit,
+        gas_used: header.gas_used,
+        timestamp: timestamp(header)?,
+        mix_hash: to_b256("mix_hash", &header.mix_hash)?,
+        nonce: header.nonce,
+        base_fee_per_gas: header
+            .base_fee_per_gas
+            .clone()
+            .map(|base_fee| {
+                bigint_to_u128(base_fee)
+                    .ok()
+                    .and_then(|base_fee| u64::try_from(base_fee).ok())
+                    .ok_or(BlockHeaderError::InvalidField(
+                        "base_fee_per_gas".to_string(),
+                    ))
+            })
+            .transpose()?,
+        blob_gas_used: header.blob_gas_used,
+        excess_blob_gas: header.excess_blob_gas,
+        parent_beacon_block_root: optional_b256("parent_beacon_root", &header.parent_beacon_root)?,
+        requests_root: None,
+        extra_data: Bytes::copy_from_slice(&header.extra_data),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::block_header_to_header;
+    use crate::handle_file;
+    use crate::headers::error::BlockHeaderError;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_header_hash() {
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        for block in blocks.iter().take(10) {
+            let header = block_header_to_header(block.header.as_ref().unwrap()).unwrap();
+            assert_eq!(header.hash_slow().as_slice(), block.hash.as_slice());
+        }
+
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let block = blocks.first().unwrap();
+        let header = block_header_to_header(block.header.as_ref().unwrap()).unwrap();
+        assert_eq!(header.hash_slow().as_slice(), block.hash.as_slice());
+    }
+
+    #[test]
+    fn test_missing_timestamp() {
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let mut header = blocks[0].header.clone().unwrap();
+        header.timestamp = None;
+
+        assert!(matches!(
+            block_header_to_header(&header),
+            Err(BlockHeaderError::InvalidField(field)) if field == "timestamp"
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_block_header_roots() {
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let mut header = blocks[0].header.clone().unwrap();
+        let roots = super::BlockHeaderRoots::try_from(header.clone()).unwrap();
+        assert_eq!(
+            roots.receipt_root.as_slice(),
+            header.receipt_root.as_slice()
+        );
+
+        header.receipt_root.truncate(4);
+        assert!(matches!(
+            super::BlockHeaderRoots::try_from(header),
+            Err(BlockHeaderError::InvalidField(field)) if field.starts_with("receipt_root")
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+use crate::headers::block_header_to_header;
+use crate::headers::error::BlockHeaderError;
+use alloy_primitives::{Address, Bloom, Bytes, B256, B64, U256, U64};
+use reth_primitives::{hex, Header};
+use serde::Deserialize;
+use sf_protos::ethereum::r#type::v2::Block;
+use std::fmt;
+
+/// `ReferenceHeader` is a trusted header flat file headers are checked against, as returned by
+/// `eth_getBlockByNumber`.
+///
+/// Only the fields present are compared, so partial headers can be used, including files
+/// holding only the `receipt_root` and `transactions_root` of a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceHeader {
+    pub hash: Option<B256>,
+    pub parent_hash: Option<B256>,
+    #[serde(rename = "sha3Uncles")]
+    pub uncles_hash: Option<B256>,
+    pub miner: Option<Address>,
+    pub state_root: Option<B256>,
+    #[serde(alias = "transactions_root")]
+    pub transactions_root: Option<B256>,
+    #[serde(alias = "receipt_root")]
+    pub receipts_root: Option<B256>,
+    pub logs_bloom: Option<Bloom>,
+    pub difficulty: Option<U256>,
+    pub number: Option<U64>,
+    pub gas_limit: Option<U64>,
+    pub gas_used: Option<U64>,
+    pub timestamp: Option<U64>,
+    pub extra_data: Option<Bytes>,
+    pub mix_hash: Option<B256>,
+    pub nonce: Option<B64>,
+    pub base_fee_per_gas: Option<U256>,
+    pub withdrawals_root: Option<B256>,
+    pub blob_gas_used: Option<U64>,
+    pub excess_blob_gas: Option<U64>,
+    pub parent_beacon_block_root: Option<B256>,
+    /// Total difficulty of the block, returned by `eth_getBlockByNumber` before the merge.
+    /// It isn't part of the header hash, so it is compared with the Firehose header's.
+    pub total_difficulty: Option<U256>,
+}
+
+impl From<&Header> for ReferenceHeader {
+    /// Takes all the fields of a trusted header, which has no total difficulty
+    fn from(header: &Header) -> Self {
+        Self {
+            hash: Some(header.hash_slow()),
+            parent_hash: Some(header.parent_hash),
+            uncles_hash: Some(header.ommers_hash),
+            miner: Some(header.beneficiary),
+            state_root: Some(header.state_root),
+            transactions_root: Some(header.transactions_root),
+            receipts_root: Some(header.receipts_root),
+            logs_bloom: Some(header.logs_bloom),
+            difficulty: Some(header.difficulty),
+            number: Some(U64::from(header.number)),
+            gas_limit: Some(U64::from(header.gas_limit)),
+            gas_used: Some(U64::from(header.gas_used)),
+            timestamp: Some(U64::from(header.timestamp)),
+            extra_data: Some(header.extra_data.clone()),
+            mix_hash: Some(header.mix_hash),
+            nonce: Some(B64::from(header.nonce.to_be_bytes())),
+            base_fee_per_gas: header.base_fee_per_gas.map(U256::from),
+            withdrawals_root: header.withdrawals_root,
+            blob_gas_used: header.blob_gas_used.map(U64::from),
+            excess_blob_gas: header.excess_blob_gas.map(U64::from),
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            total_difficulty: None,
+        }
+    }
+}
+
+/// A reference header file, either a whole JSON-RPC response or its result
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReferenceFile {
+    Response { result: ReferenceHeader },
+    Header(ReferenceHeader),
+}
+
+impl ReferenceHeader {
+    /// Parses a JSON reference header, either a header or a whole JSON-RPC response
+    pub fn from_json(json: &[u8]) -> Result<Self, BlockHeaderError> {
+        match serde_json::from_slice(json)? {
+            ReferenceFile::Response { result } => Ok(result),
+            ReferenceFile::Header(header) => Ok(header),
+        }
+    }
+
+    /// Checks the header of `block` against the reference, see [`ReferenceHeader::diff`]
+    pub fn check(&self, block: &Block) -> Result<(), BlockHeaderError> {
+        if *self == ReferenceHeader::default() {
+            return Err(BlockHeaderError::InvalidInput);
+        }
+        let block_header = block
+            .header
+            .as_ref()
+            .ok_or(BlockHeaderError::MissingHeader)?;
+        let header = block_header_to_header(block_header)?;
+
+        let mut diff = self.diff(&header);
+        if let Some(total_difficulty) = self.total_difficulty {
+            let flat = block_header
+                .total_difficulty
+                .as_ref()
+                .and_then(|total_difficulty| U256::try_from_be_slice(&total_difficulty.bytes));
+            if flat != Some(total_difficulty) {
+                diff.push(HeaderFieldDiff {
+                    field: "totalDifficulty",
+                    reference: total_difficulty.to_string(),
+                    flat: flat.map_or_else(|| "none".to_string(), |flat| flat.to_string()),
+                });
+            }
+        }
+        if !diff.is_empty() {
+            return Err(BlockHeaderError::MismatchedFields(
+                block.number,
+                HeaderDiff(diff),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Compares the fields present in the reference with `header`.
+    ///
+    /// The reference hash is compared with the hash of `header`, so a flat file header
+    /// must match the reference as a whole even when only its hash is known.
+    pub fn diff(&self, header: &Header) -> Vec<HeaderFieldDiff> {
+        let mut diff = Vec::new();
+        let mut compare = |field: &'static str, reference: Option<String>, flat: Option<String>| {
+            if reference.is_some() && reference != flat {
+                diff.push(HeaderFieldDiff {
+                    field,
+                    reference: reference.unwrap_or_default(),
+                    flat: flat.unwrap_or_else(|| "none".to_string()),
+                });
+            }
+        };
+        let some = |value: &dyn fmt::Display| Some(value.to_string());
+        let string = |value: &Option<B256>| value.as_ref().map(B256::to_string);
+
+        compare("hash", string(&self.hash), some(&header.hash_slow()));
+        compare(
+            "parentHash",
+            string(&self.parent_hash),
+            some(&header.parent_hash),
+        );
+        compare(
+            "sha3Uncles",
+            string(&self.uncles_hash),
+            some(&header.ommers_hash),
+        );
+        compare(
+            "miner",
+            self.miner.as_ref().map(Address::to_string),
+            some(&header.beneficiary),
+        );
+        compare(
+            "stateRoot",
+            string(&self.state_root),
+            some(&header.state_root),
+        );
+        compare(
+            "transactionsRoot",
+            string(&self.transactions_root),
+            some(&header.transactions_root),
+        );
+        compare(
+            "receiptsRoot",
+            string(&self.receipts_root),
+            some(&header.receipts_root),
+        );
+        compare(
+            "logsBloom",
+            self.logs_bloom.as_ref().map(Bloom::to_string),
+            some(&header.logs_bloom),
+        );
+        compare(
+            "difficulty",
+            self.difficulty.as_ref().map(U256::to_string),
+            some(&header.difficulty),
+        );
+        compare(
+            "number",
+            self.number.as_ref().map(U64::to_string),
+            some(&header.number),
+        );
+        compare(
+            "gasLimit",
+            self.gas_limit.as_ref().map(U64::to_string),
+            some(&header.gas_limit),
+        );
+        compare(
+            "gasUsed",
+            self.gas_used.as_ref().map(U64::to_string),
+            some(&header.gas_used),
+        );
+        compare(
+            "timestamp",
+            self.timestamp.as_ref().map(U64::to_string),
+            some(&header.timestamp),
+        );
+        compare(
+            "extraData",
+            self.extra_data.as_ref().map(hex::encode_prefixed),
+            Some(hex::encode_prefixed(&header.extra_data)),
+        );
+        compare("mixHash", string(&self.mix_hash), some(&header.mix_hash));
+        compare(
+            "nonce",
+            self.nonce.as_ref().map(B64::to_string),
+            some(&B64::from(header.nonce.to_be_bytes())),
+        );
+        compare(
+            "baseFeePerGas",
+            self.base_fee_per_gas.as_ref().map(U256::to_string),
+            header.base_fee_per_gas.map(|value| value.to_string()),
+        );
+        compare(
+            "withdrawalsRoot",
+            string(&self.withdrawals_root),
+            string(&header.withdrawals_root),
+        );
+        compare(
+            "blobGasUsed",
+            self.blob_gas_used.as_ref().map(U64::to_string),
+            header.blob_gas_used.map(|value| value.to_string()),
+        );
+        compare(
+            "excessBlobGas",
+            self.excess_blob_gas.as_ref().map(U64::to_string),
+            header.excess_blob_gas.map(|value| value.to_string()),
+        );
+        compare(
+            "parentBeaconBlockRoot",
+            string(&self.parent_beacon_block_root),
+            string(&header.parent_beacon_block_root),
+        );
+        diff
+    }
+}
+
+/// `HeaderFieldDiff` is a field of a flat file header which differs from the reference header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderFieldDiff {
+    /// The JSON-RPC name of the field
+    pub field: &'static str,
+    pub reference: String,
+    pub flat: String,
+}
+
+impl fmt::Display for HeaderFieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} != {} (reference != flat)",
+            self.field, self.reference, self.flat
+        )
+    }
+}
+
+/// `HeaderDiff` lists the fields of a flat file header which differ from the reference header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderDiff(pub Vec<HeaderFieldDiff>);
+
+impl fmt::Display for HeaderDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, field) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", field)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handle_file;
+    use crate::headers::error::BlockHeaderError;
+    use crate::headers::source::{HeaderSource, JsonDirSource};
+    use crate::rpc::block_to_rpc;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_reference_header() {
+        let dir = std::env::temp_dir().join("decoder-test-reference-header");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let block = &blocks[0];
+
+        let mut rpc_block = serde_json::to_value(block_to_rpc(block, false).unwrap()).unwrap();
+        let response = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": rpc_block.clone()});
+        fs::write(
+            dir.join("block-17686312.json"),
+            serde_json::to_vec(&response).unwrap(),
+        )
+        .unwrap();
+        let reference = JsonDirSource::new(&dir).header(17686312).unwrap().unwrap();
+        assert!(reference.hash.is_some() && reference.withdrawals_root.is_some());
+        reference.check(block).unwrap();
+
+        rpc_block["gasUsed"] = serde_json::json!("0x1");
+        fs::write(
+            dir.join("block-17686312.json"),
+            serde_json::to_vec(&rpc_block).unwrap(),
+        )
+        .unwrap();
+        let reference = JsonDirSource::new(&dir).header(17686312).unwrap().unwrap();
+        match reference.check(block) {
+            Err(BlockHeaderError::MismatchedFields(17686312, diff)) => {
+                assert_eq!(diff.0.len(), 1);
+                assert_eq!(diff.0[0].field, "gasUsed");
+                assert_eq!(diff.0[0].reference, "1");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        fs::remove_file(dir.join("block-17686312.json")).unwrap();
+        let header = block.header.as_ref().unwrap();
+        let roots = serde_json::json!({
+            "receipt_root": format!("0x{}", reth_primitives::hex::encode(&header.receipt_root)),
+            "transactions_root": format!("0x{}", reth_primitives::hex::encode(&header.transactions_root)),
+        });
+        fs::write(
+            dir.join("17686312.json"),
+            serde_json::to_vec(&roots).unwrap(),
+        )
+        .unwrap();
+        JsonDirSource::new(&dir).check(block).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,470 @@
+use crate::era1::error::Era1Error;
+use crate::era1::Era1File;
+use crate::error::DecodeError;
+use crate::headers::error::BlockHeaderError;
+use crate::headers::reference::ReferenceHeader;
+use crate::headers::HeaderRecordWithNumber;
+use crate::{handle_file, is_flat_file};
+use alloy_primitives::{B256, U256, U64};
+use sf_protos::ethereum::r#type::v2::Block;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// `HeaderSource` is a trusted source of headers, flat file headers are checked against.
+pub trait HeaderSource {
+    /// The reference header of block `number`, `None` if the source doesn't hold it
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError>;
+
+    /// Checks the header of `block` against its reference header, see [`ReferenceHeader::check`]
+    fn check(&mut self, block: &Block) -> Result<(), BlockHeaderError> {
+        self.header(block.number)?
+            .ok_or(BlockHeaderError::MissingReference(block.number))?
+            .check(block)
+    }
+}
+
+/// Opens the header source stored at `path`, picking the source from the path:
+///
+/// * a directory: [`JsonDirSource`]
+/// * a `.jsonl` file: [`JsonLinesSource`]
+/// * a `.csv` file: [`CsvSource`]
+/// * an `.era1` file: [`Era1Source`]
+/// * a `.bin` file: [`HeaderRecordSource`], as written by the `stream` command
+///
+/// Any other file is rejected as an unsupported source.
+pub fn open_header_source(path: &Path) -> Result<Box<dyn HeaderSource>, DecodeError> {
+    if path.is_dir() {
+        return Ok(Box::new(JsonDirSource::new(path)));
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jsonl") => Ok(Box::new(JsonLinesSource::open(path)?)),
+        Some("csv") => Ok(Box::new(CsvSource::open(path)?)),
+        Some("era1") => Ok(Box::new(Era1Source::open(path)?)),
+        Some("bin") => Ok(Box::new(HeaderRecordSource::read(BufReader::new(
+            File::open(path)?,
+        ))?)),
+        _ => Err(BlockHeaderError::UnsupportedSource(path.display().to_string()).into()),
+    }
+}
+
+/// A directory of JSON headers, one per block, named `block-<number>.json` or `<number>.json`.
+pub struct JsonDirSource {
+    dir: PathBuf,
+}
+
+impl JsonDirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl HeaderSource for JsonDirSource {
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError> {
+        for file_name in [format!("block-{}.json", number), format!("{}.json", number)] {
+            match fs::read(self.dir.join(file_name)) {
+                Ok(json) => return ReferenceHeader::from_json(&json).map(Some),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A single JSON lines file holding a JSON header per line, which must have its `number`.
+pub struct JsonLinesSource {
+    headers: HashMap<u64, ReferenceHeader>,
+}
+
+impl JsonLinesSource {
+    pub fn open(path: &Path) -> Result<Self, BlockHeaderError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, BlockHeaderError> {
+        let mut headers = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let header = ReferenceHeader::from_json(line.as_bytes())?;
+            let number = header.number.ok_or_else(|| {
+                BlockHeaderError::InvalidSource("header without number".to_string())
+            })?;
+            headers.insert(number.to::<u64>(), header);
+        }
+        Ok(Self { headers })
+    }
+}
+
+impl HeaderSource for JsonLinesSource {
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError> {
+        Ok(self.headers.get(&number).cloned())
+    }
+}
+
+/// A CSV file of `number,hash` lines, with an optional header line.
+///
+/// Only block hashes are compared, which is enough as the hash commits to the whole header.
+pub struct CsvSource {
+    hashes: HashMap<u64, B256>,
+}
+
+impl CsvSource {
+    pub fn open(path: &Path) -> Result<Self, BlockHeaderError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, BlockHeaderError> {
+        let mut hashes = HashMap::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut fields = line.split(',').map(str::trim);
+            let (number, hash) = match (fields.next(), fields.next()) {
+                (Some(number), Some(hash)) => (number, hash),
+                _ if line.trim().is_empty() => continue,
+                _ => {
+                    return Err(BlockHeaderError::InvalidSource(format!(
+                        "line {}: {}",
+                        index + 1,
+                        line
+                    )))
+                }
+            };
+            let number = match number.parse::<u64>() {
+                Ok(number) => number,
+                // header line
+                Err(_) if index == 0 => continue,
+                Err(_) => {
+                    return Err(BlockHeaderError::InvalidSource(format!(
+                        "line {}: invalid number {}",
+                        index + 1,
+                        number
+                    )))
+                }
+            };
+            let hash = B256::from_str(hash).map_err(|_| {
+                BlockHeaderError::InvalidSource(format!(
+                    "line {}: invalid hash {}",
+                    index + 1,
+                    hash
+                ))
+            })?;
+            hashes.insert(number, hash);
+        }
+        Ok(Self { hashes })
+    }
+}
+
+impl HeaderSource for CsvSource {
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError> {
+        Ok(self.hashes.get(&number).map(|hash| ReferenceHeader {
+            hash: Some(*hash),
+            number: Some(U64::from(number)),
+            ..Default::default()
+        }))
+    }
+}
+
+/// A stream of [`HeaderRecordWithNumber`] frames, as written by [`crate::stream_blocks`]:
+/// each frame is its bincode encoding prefixed with its size as a big endian `u32`.
+///
+/// Block hashes and total difficulties are compared.
+pub struct HeaderRecordSource {
+    records: HashMap<u64, HeaderRecordWithNumber>,
+}
+
+impl HeaderRecordSource {
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, BlockHeaderError> {
+        let mut records = HashMap::new();
+        loop {
+            let mut size = [0u8; 4];
+            match reader.read_exact(&mut size) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err.into()),
+            }
+            // the size is read from the source, so the frame is only allocated as it is read
+            let size = u32::from_be_bytes(size) as u64;
+            let mut frame = Vec::new();
+            (&mut reader).take(size).read_to_end(&mut frame)?;
+            if frame.len() as u64 != size {
+                return Err(BlockHeaderError::InvalidSource(format!(
+                    "truncated header record of {} bytes, {} read",
+                    size,
+                    frame.len()
+                )));
+            }
+            let record: HeaderRecordWithNumber = bincode::deserialize(&frame)
+                .map_err(|err| BlockHeaderError::InvalidSource(err.to_string()))?;
+            records.insert(record.block_number, record);
+        }
+        Ok(Self { records })
+    }
+}
+
+impl HeaderSource for HeaderRecordSource {
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError> {
+        self.records
+            .get(&number)
+            .map(|record| {
+                let hash = B256::try_from(record.block_hash.as_slice()).map_err(|_| {
+                    BlockHeaderError::InvalidSource(format!("invalid hash of block {}", number))
+                })?;
+                let total_difficulty = U256::try_from_be_slice(&record.total_difficulty)
+                    .ok_or(BlockHeaderError::InvalidTotalDifficulty)?;
+                Ok(ReferenceHeader {
+                    hash: Some(hash),
+                    number: Some(U64::from(number)),
+                    total_difficulty: Some(total_difficulty),
+                    ..Default::default()
+                })
+            })
+            .transpose()
+    }
+}
+
+/// The blocks of an era1 file, whose headers and total difficulties are compared.
+pub struct Era1Source {
+    headers: HashMap<u64, ReferenceHeader>,
+}
+
+impl Era1Source {
+    pub fn open(path: &Path) -> Result<Self, Era1Error> {
+        let era1_file = Era1File::open(path)?;
+        let headers = era1_file
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut header = ReferenceHeader::from(&block.block.header);
+                header.total_difficulty = Some(block.total_difficulty);
+                (block.number(), header)
+            })
+            .collect();
+        Ok(Self { headers })
+    }
+}
+
+impl HeaderSource for Era1Source {
+    fn header(&mut self, number: u64) -> Result<Option<ReferenceHeader>, BlockHeaderError> {
+        Ok(self.headers.get(&number).cloned())
+    }
+}
+
+/// `HeaderReport` lists the blocks whose header doesn't match a [`HeaderSource`].
+#[derive(Debug, Default)]
+pub struct HeaderReport {
+    /// Number of blocks checked
+    pub blocks: usize,
+    /// Blocks missing from the source or differing from it
+    pub failures: Vec<(u64, BlockHeaderError)>,
+    /// Flat files failing to decode, whose blocks aren't checked
+    pub failed_files: Vec<(PathBuf, DecodeError)>,
+}
+
+impl HeaderReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && self.failed_files.is_empty()
+    }
+}
+
+/// Checks the headers of the blocks of the flat files of `dir` against `source`.
+///
+/// Flat files failing to decode are listed in the report, and the others still checked.
+///
+/// # Arguments
+///
+/// * `dir`: folder of the flat files to check
+/// * `source`: the trusted headers, see [`open_header_source`]
+pub fn check_headers_dir(
+    dir: &Path,
+    source: &mut dyn HeaderSource,
+) -> Result<HeaderReport, DecodeError> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|path| is_flat_file(path));
+    paths.sort();
+
+    let mut report = HeaderReport::default();
+    for path in paths {
+        let blocks = match handle_file(&path, None, None, None) {
+            Ok(blocks) => blocks,
+            Err(err) => {
+                report.failed_files.push((path, err));
+                continue;
+            }
+        };
+        for block in blocks {
+            report.blocks += 1;
+            if let Err(err) = source.check(&block) {
+                report.failures.push((block.number, err));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_headers_dir, open_header_source, CsvSource, Era1Source, HeaderRecordSource,
+        HeaderSource, JsonLinesSource,
+    };
+    use crate::era1::{Era1Sink, DEFAULT_NETWORK};
+    use crate::error::DecodeError;
+    use crate::handle_file;
+    use crate::headers::error::BlockHeaderError;
+    use crate::headers::HeaderRecordWithNumber;
+    use crate::output::BlockSink;
+    use crate::rpc::block_to_rpc;
+    use reth_primitives::hex;
+    use sf_protos::ethereum::r#type::v2::Block;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+
+    fn blocks() -> Vec<Block> {
+        handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn check_all(source: &mut dyn HeaderSource, blocks: &[Block]) {
+        for block in blocks {
+            source.check(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_jsonl_source() {
+        let blocks = blocks();
+        let mut jsonl = String::new();
+        for block in &blocks {
+            jsonl.push_str(&serde_json::to_string(&block_to_rpc(block, false).unwrap()).unwrap());
+            jsonl.push('\n');
+        }
+
+        let mut source = JsonLinesSource::read(Cursor::new(jsonl)).unwrap();
+        check_all(&mut source, &blocks);
+        assert!(matches!(source.header(100), Ok(None)));
+    }
+
+    #[test]
+    fn test_csv_source() {
+        let blocks = blocks();
+        let mut csv = "number,hash\n".to_string();
+        for block in &blocks {
+            csv.push_str(&format!(
+                "{},0x{}\n",
+                block.number,
+                hex::encode(&block.hash)
+            ));
+        }
+
+        let mut source = CsvSource::read(Cursor::new(csv)).unwrap();
+        check_all(&mut source, &blocks);
+
+        let mut source = CsvSource::read(Cursor::new(format!(
+            "1,0x{}\n",
+            hex::encode(&blocks[2].hash)
+        )))
+        .unwrap();
+        assert!(matches!(
+            source.check(&blocks[1]),
+            Err(BlockHeaderError::MismatchedFields(1, _))
+        ));
+        assert!(matches!(
+            source.check(&blocks[2]),
+            Err(BlockHeaderError::MissingReference(2))
+        ));
+    }
+
+    #[test]
+    fn test_check_headers_dir_corrupt_file() {
+        let dir = std::env::temp_dir().join("decoder-test-check-headers-corrupt");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy("tests/0000000000.dbin.zst", dir.join("0000000000.dbin.zst")).unwrap();
+        fs::write(dir.join("0000000100.dbin"), b"dbin corrupt").unwrap();
+
+        let mut csv = "number,hash\n".to_string();
+        for block in &blocks() {
+            csv.push_str(&format!(
+                "{},0x{}\n",
+                block.number,
+                hex::encode(&block.hash)
+            ));
+        }
+        let mut source = CsvSource::read(Cursor::new(csv)).unwrap();
+
+        let report = check_headers_dir(&dir, &mut source).unwrap();
+        assert_eq!(report.blocks, 100);
+        assert!(report.failures.is_empty());
+        assert_eq!(report.failed_files.len(), 1);
+        assert_eq!(report.failed_files[0].0, dir.join("0000000100.dbin"));
+        assert!(!report.is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_header_record_source() {
+        let blocks = blocks();
+        let mut frames = Vec::new();
+        for block in &blocks {
+            let record =
+                bincode::serialize(&HeaderRecordWithNumber::try_from(block).unwrap()).unwrap();
+            frames.extend_from_slice(&(record.len() as u32).to_be_bytes());
+            frames.extend_from_slice(&record);
+        }
+
+        let mut source = HeaderRecordSource::read(Cursor::new(frames)).unwrap();
+        check_all(&mut source, &blocks);
+
+        let mut truncated = u32::MAX.to_be_bytes().to_vec();
+        truncated.extend_from_slice(&[0; 16]);
+        assert!(matches!(
+            HeaderRecordSource::read(Cursor::new(truncated)),
+            Err(BlockHeaderError::InvalidSource(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_source() {
+        assert!(matches!(
+            open_header_source(Path::new("tests/headers.json")),
+            Err(DecodeError::BlockHeaderError(
+                BlockHeaderError::UnsupportedSource(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_era1_source() {
+        let dir = std::env::temp_dir().join("decoder-test-era1-source");
+        let _ = fs::remove_dir_all(&dir);
+
+        let blocks = blocks();
+        let mut sink = Era1Sink::with_last_block(&dir, DEFAULT_NETWORK, 99).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let era1_path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let mut source = Era1Source::open(&era1_path).unwrap();
+        check_all(&mut source, &blocks);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
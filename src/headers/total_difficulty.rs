@@ -0,0 +1,190 @@
+use crate::convert::bigint_to_u256;
+use crate::error::DecodeError;
+use crate::headers::error::BlockHeaderError;
+use crate::{handle_file, is_flat_file};
+use reth_primitives::U256;
+use sf_protos::ethereum::r#type::v2::Block;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `TotalDifficultyChecker` checks the total difficulty recorded in the headers of consecutive
+/// blocks, `td[n] = td[n - 1] + difficulty[n]`, starting from a trusted checkpoint.
+///
+/// The total difficulty isn't part of the block hash, so it can't be verified from a header alone,
+/// yet it is part of the epoch accumulator records, see [`crate::accumulator::HeaderRecord`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TotalDifficultyChecker {
+    next_block: u64,
+    total_difficulty: U256,
+}
+
+impl TotalDifficultyChecker {
+    /// Starts checking from the genesis block, whose total difficulty is its difficulty
+    pub fn genesis() -> Self {
+        Self {
+            next_block: 0,
+            total_difficulty: U256::ZERO,
+        }
+    }
+
+    /// Starts checking from the block after `block_number`, `total_difficulty` being the trusted
+    /// total difficulty of `block_number`
+    pub fn from_checkpoint(block_number: u64, total_difficulty: U256) -> Self {
+        Self {
+            next_block: block_number + 1,
+            total_difficulty,
+        }
+    }
+
+    /// The next block expected, and the total difficulty of the block before it
+    pub fn checkpoint(&self) -> (u64, U256) {
+        (self.next_block, self.total_difficulty)
+    }
+
+    /// Checks the total difficulty of `block`, which must follow the last block checked.
+    ///
+    /// On a mismatch, the computed total difficulty is kept, so following blocks are still
+    /// checked against the correct value. Returns the total difficulty of `block`.
+    pub fn check(&mut self, block: &Block) -> Result<U256, BlockHeaderError> {
+        if block.number != self.next_block {
+            return Err(BlockHeaderError::NonContiguous {
+                expected: self.next_block,
+                found: block.number,
+            });
+        }
+        let header = block
+            .header
+            .as_ref()
+            .ok_or(BlockHeaderError::MissingHeader)?;
+
+        let difficulty = bigint_to_u256("difficulty", header.difficulty.as_ref())?;
+        let expected = self
+            .total_difficulty
+            .checked_add(difficulty)
+            .ok_or(BlockHeaderError::InvalidTotalDifficulty)?;
+        let recorded = header
+            .total_difficulty
+            .as_ref()
+            .and_then(|total_difficulty| U256::try_from_be_slice(&total_difficulty.bytes))
+            .ok_or(BlockHeaderError::InvalidTotalDifficulty)?;
+
+        self.next_block += 1;
+        self.total_difficulty = expected;
+
+        if recorded != expected {
+            return Err(BlockHeaderError::MismatchedTotalDifficulty {
+                block: block.number,
+                expected,
+                recorded,
+            });
+        }
+        Ok(expected)
+    }
+}
+
+/// `TotalDifficultyReport` lists the blocks whose recorded total difficulty is inconsistent.
+#[derive(Debug, Default)]
+pub struct TotalDifficultyReport {
+    /// Number of blocks checked
+    pub blocks: usize,
+    pub mismatches: Vec<(u64, BlockHeaderError)>,
+}
+
+impl TotalDifficultyReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Checks the total difficulty of the blocks of the flat files of `dir`, in order.
+///
+/// # Arguments
+///
+/// * `dir`: folder of the flat files, following each other from the checkpoint on
+/// * `checker`: the checker seeded with a trusted checkpoint, see [`TotalDifficultyChecker::from_checkpoint`]
+pub fn check_total_difficulty_dir(
+    dir: &Path,
+    mut checker: TotalDifficultyChecker,
+) -> Result<TotalDifficultyReport, DecodeError> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|path| is_flat_file(path));
+    paths.sort();
+
+    let mut report = TotalDifficultyReport::default();
+    for path in paths {
+        for block in handle_file(&path, None, None, None)? {
+            report.blocks += 1;
+            match checker.check(&block) {
+                Ok(_) => {}
+                Err(err @ BlockHeaderError::MismatchedTotalDifficulty { .. }) => {
+                    report.mismatches.push((block.number, err));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TotalDifficultyChecker;
+    use crate::handle_file;
+    use crate::headers::error::BlockHeaderError;
+    use reth_primitives::U256;
+    use sf_protos::ethereum::r#type::v2::BigInt;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_total_difficulty() {
+        let mut blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut checker = TotalDifficultyChecker::genesis();
+        let total_difficulties: Vec<U256> = blocks
+            .iter()
+            .map(|block| checker.check(block).unwrap())
+            .collect();
+        assert_eq!(total_difficulties[0], U256::from(17179869184u64));
+        assert_eq!(checker.checkpoint(), (100, total_difficulties[99]));
+
+        // resuming from a checkpoint
+        let mut checker = TotalDifficultyChecker::from_checkpoint(49, total_difficulties[49]);
+        for block in &blocks[50..] {
+            checker.check(block).unwrap();
+        }
+        assert_eq!(checker.checkpoint(), (100, total_difficulties[99]));
+
+        // a wrong total difficulty is flagged, following blocks still check
+        blocks[10].header.as_mut().unwrap().total_difficulty = Some(BigInt {
+            bytes: U256::from(1).to_be_bytes_vec(),
+        });
+        let mut checker = TotalDifficultyChecker::genesis();
+        for block in &blocks {
+            match checker.check(block) {
+                Ok(_) => assert_ne!(block.number, 10),
+                Err(BlockHeaderError::MismatchedTotalDifficulty { block, .. }) => {
+                    assert_eq!(block, 10)
+                }
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        let mut checker = TotalDifficultyChecker::genesis();
+        assert!(matches!(
+            checker.check(&blocks[1]),
+            Err(BlockHeaderError::NonContiguous {
+                expected: 0,
+                found: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+pub mod remote;
+
+use crate::compression::Compression;
+use crate::dbin::{DbinItem, DbinReader};
+use crate::error::DecodeError;
+use crate::{handle_file, is_flat_file};
+use reth_primitives::B256;
+use serde::{Deserialize, Serialize};
+use sf_protos::ethereum::r#type::v2::Block;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// `BlockLookup` reads indexed blocks by number, whether from local flat files with a [`BlockReader`]
+/// or from remote ones with a [`remote::RemoteBlockStore`].
+pub trait BlockLookup: Send + Sync {
+    /// The index of the blocks
+    fn index(&self) -> &BlockIndex;
+
+    /// Reads block `number`, `None` if the block isn't indexed
+    fn block(&self, number: u64) -> Result<Option<Block>, DecodeError>;
+}
+
+/// `BlockIndex` locates the blocks and transactions of a folder of flat files.
+///
+/// It is built by decoding, and so verifying, every flat file once. Blocks are then read
+/// back from the file holding them. For uncompressed flat files, the byte range of each block
+/// in its file is indexed as well, so that it can be read on its own, see [`BlockIndex::range`].
+///
+/// An index can be saved as JSON with [`BlockIndex::save`] and loaded back elsewhere, for
+/// instance to read blocks from a copy of the flat files served over HTTP.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BlockIndex {
+    paths: Vec<PathBuf>,
+    /// Block number to the index of its file in `paths` and its position in the file
+    blocks: BTreeMap<u64, (usize, usize)>,
+    /// Block number to the byte range of its message in an uncompressed file, length prefix excluded
+    ranges: BTreeMap<u64, Range<u64>>,
+    hashes: HashMap<B256, u64>,
+    /// Transaction hash to the number of its block and its index in the block
+    transactions: HashMap<B256, (u64, usize)>,
+}
+
+impl BlockIndex {
+    /// Indexes the flat files of `dir`
+    pub fn build(dir: &Path) -> Result<Self, DecodeError> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        paths.retain(|path| is_flat_file(path));
+        paths.sort();
+
+        let mut index = BlockIndex::default();
+        for path in paths {
+            let blocks = handle_file(&path, None, None, None)?;
+            let ranges = message_ranges(&path)?;
+            index.add_file(path, &blocks, ranges);
+        }
+        Ok(index)
+    }
+
+    /// Loads an index saved with [`BlockIndex::save`]
+    pub fn load(path: &Path) -> Result<Self, DecodeError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Saves the index as JSON
+    pub fn save(&self, path: &Path) -> Result<(), DecodeError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    fn add_file(&mut self, path: PathBuf, blocks: &[Block], ranges: Option<Vec<Range<u64>>>) {
+        let file = self.paths.len();
+        self.paths.push(path);
+        let ranges = ranges.filter(|ranges| ranges.len() == blocks.len());
+        for (position, block) in blocks.iter().enumerate() {
+            self.blocks.insert(block.number, (file, position));
+            if let Some(ranges) = &ranges {
+                self.ranges.insert(block.number, ranges[position].clone());
+            }
+            if let Ok(hash) = B256::try_from(block.hash.as_slice()) {
+                self.hashes.insert(hash, block.number);
+            }
+            for (index, trace) in block.transaction_traces.iter().enumerate() {
+                if let Ok(hash) = B256::try_from(trace.hash.as_slice()) {
+                    self.transactions.insert(hash, (block.number, index));
+                }
+            }
+        }
+    }
+
+    /// The file holding block `number`, and the position of the block in the file
+    pub fn location(&self, number: u64) -> Option<(&Path, usize)> {
+        self.blocks
+            .get(&number)
+            .map(|(file, position)| (self.paths[*file].as_path(), *position))
+    }
+
+    /// The file holding block `number`, and the byte range of the block in the file.
+    ///
+    /// `None` if the block isn't indexed or its file is compressed.
+    pub fn range(&self, number: u64) -> Option<(&Path, Range<u64>)> {
+        let (file, _) = self.blocks.get(&number)?;
+        let range = self.ranges.get(&number)?;
+        Some((self.paths[*file].as_path(), range.clone()))
+    }
+
+    pub fn block_number(&self, hash: &B256) -> Option<u64> {
+        self.hashes.get(hash).copied()
+    }
+
+    /// The number of the block of a transaction and its index in the block
+    pub fn transaction(&self, hash: &B256) -> Option<(u64, usize)> {
+        self.transactions.get(hash).copied()
+    }
+
+    pub fn earliest(&self) -> Option<u64> {
+        self.blocks.keys().next().copied()
+    }
+
+    pub fn latest(&self) -> Option<u64> {
+        self.blocks.keys().next_back().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+/// Byte ranges of the messages of a flat file, `None` if the file is compressed
+fn message_ranges(path: &Path) -> Result<Option<Vec<Range<u64>>>, DecodeError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if Compression::detect_from(&mut reader)? != Compression::None {
+        return Ok(None);
+    }
+
+    let mut reader = DbinReader::new(reader);
+    let mut ranges = vec![];
+    loop {
+        // Skips the 4 bytes length prefix of the message
+        let start = reader.offset() + 4;
+        match reader.next_item()? {
+            DbinItem::Message(_) => ranges.push(start..reader.offset()),
+            DbinItem::Header(_) => continue,
+            DbinItem::Eof => return Ok(Some(ranges)),
+        }
+    }
+}
+
+/// `BlockReader` reads indexed blocks back from their flat files.
+///
+/// The blocks of the last file read are kept in memory, as consecutive reads usually
+/// target nearby blocks.
+#[derive(Debug)]
+pub struct BlockReader {
+    index: BlockIndex,
+    cache: Mutex<Option<(PathBuf, Arc<Vec<Block>>)>>,
+}
+
+impl BlockReader {
+    pub fn new(index: BlockIndex) -> Self {
+        Self {
+            index,
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn index(&self) -> &BlockIndex {
+        &self.index
+    }
+
+    /// Reads block `number` from its flat file, `None` if the block isn't indexed
+    pub fn block(&self, number: u64) -> Result<Option<Block>, DecodeError> {
+        let (path, position) = match self.index.location(number) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        let blocks = match cache.as_ref() {
+            Some((cached, blocks)) if cached == path => blocks.clone(),
+            _ => {
+                let blocks = Arc::new(handle_file(&path.to_path_buf(), None, None, None)?);
+                *cache = Some((path.to_path_buf(), blocks.clone()));
+                blocks
+            }
+        };
+        Ok(blocks.get(position).cloned())
+    }
+}
+
+impl BlockLookup for BlockReader {
+    fn index(&self) -> &BlockIndex {
+        &self.index
+    }
+
+    fn block(&self, number: u64) -> Result<Option<Block>, DecodeError> {
+        BlockReader::block(self, number)
+    }
+}
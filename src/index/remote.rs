@@ -0,0 +1,214 @@
+use crate::error::DecodeError;
+use crate::index::{BlockIndex, BlockLookup};
+use crate::store::ObjectStore;
+use crate::{decode_block_with_envelope, handle_bytes, verify_block};
+use sf_protos::ethereum::r#type::v2::Block;
+use std::path::Path;
+
+/// `RemoteBlockStore` reads indexed blocks from copies of their flat files held in an [`ObjectStore`],
+/// like flat files served over HTTP with [`crate::store::remote::RemoteStore::http`].
+///
+/// A block of an uncompressed flat file is fetched on its own, with a range read of the byte range
+/// recorded by the [`BlockIndex`], rather than by downloading its whole file. Compressed flat files
+/// can't be read by range, so blocks of those are read from their whole file.
+pub struct RemoteBlockStore {
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+    index: BlockIndex,
+}
+
+impl RemoteBlockStore {
+    /// Creates a `RemoteBlockStore`
+    ///
+    /// # Arguments
+    ///
+    /// * `store`: The [`ObjectStore`] holding the flat files.
+    /// * `prefix`: The prefix of the keys of the flat files, each file being keyed by `<prefix>/<file name>`.
+    /// * `index`: The [`BlockIndex`] of the flat files, built from local copies and loaded with [`BlockIndex::load`].
+    pub fn new(store: Box<dyn ObjectStore>, prefix: impl Into<String>, index: BlockIndex) -> Self {
+        Self {
+            store,
+            prefix: prefix.into(),
+            index,
+        }
+    }
+
+    pub fn index(&self) -> &BlockIndex {
+        &self.index
+    }
+
+    pub fn store(&self) -> &dyn ObjectStore {
+        self.store.as_ref()
+    }
+
+    /// Key of the flat file indexed as `path`
+    fn key(&self, path: &Path) -> Result<String, DecodeError> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(DecodeError::InvalidInput)?;
+        let prefix = self.prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            Ok(name.to_string())
+        } else {
+            Ok(format!("{}/{}", prefix, name))
+        }
+    }
+
+    /// Reads block `number` from the store, `None` if the block isn't indexed
+    pub fn block(&self, number: u64) -> Result<Option<Block>, DecodeError> {
+        if let Some((path, range)) = self.index.range(number) {
+            let bytes = self.store.get_range(&self.key(path)?, range)?;
+            let block = decode_block_with_envelope(bytes)?.block;
+            return Ok(Some(verify_block(block, None)?));
+        }
+
+        let (path, position) = match self.index.location(number) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let bytes = self.store.get(&self.key(path)?)?;
+        Ok(handle_bytes(bytes, None)?.into_iter().nth(position))
+    }
+}
+
+impl BlockLookup for RemoteBlockStore {
+    fn index(&self) -> &BlockIndex {
+        &self.index
+    }
+
+    fn block(&self, number: u64) -> Result<Option<Block>, DecodeError> {
+        RemoteBlockStore::block(self, number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoteBlockStore;
+    use crate::index::{BlockIndex, BlockLookup, BlockReader};
+    use crate::store::{LocalStore, MemoryStore, ObjectStore};
+    use bytes::Bytes;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+
+    /// Folder holding an uncompressed copy of the first 100 mainnet blocks, as `0000000000.dbin`
+    fn flat_files_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = zstd::decode_all(File::open("tests/0000000000.dbin.zst").unwrap()).unwrap();
+        fs::write(dir.join("0000000000.dbin"), file).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_remote_block_store() {
+        let dir = flat_files_dir("decoder-test-remote-block-store");
+        let index = BlockIndex::build(&dir).unwrap();
+        let (_, range) = index.range(5).unwrap();
+        assert!(range.start > 0 && range.end > range.start);
+
+        let path = dir.join("index.json");
+        index.save(&path).unwrap();
+        let loaded = BlockIndex::load(&path).unwrap();
+        assert_eq!(loaded.len(), 100);
+        assert_eq!(loaded.range(5).map(|(_, range)| range), Some(range));
+
+        let store = MemoryStore::new();
+        let file = fs::read(dir.join("0000000000.dbin")).unwrap();
+        store
+            .put("flat/0000000000.dbin", Bytes::from(file))
+            .unwrap();
+        let remote = RemoteBlockStore::new(Box::new(store), "flat", loaded);
+        let reader = BlockReader::new(index);
+        for number in [0, 5, 99] {
+            let block = BlockLookup::block(&remote, number).unwrap().unwrap();
+            assert_eq!(Some(block), reader.block(number).unwrap());
+        }
+        assert_eq!(remote.block(100).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_remote_block_store() {
+        let index = BlockIndex::build(&PathBuf::from("tests")).unwrap();
+        assert!(index.range(5).is_none());
+
+        let remote = RemoteBlockStore::new(Box::new(LocalStore::new("tests")), "", index);
+        let block = remote.block(5).unwrap().unwrap();
+        assert_eq!(block.number, 5);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_block_store() {
+        use crate::store::remote::RemoteStore;
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let dir = flat_files_dir("decoder-test-http-block-store");
+        let index = BlockIndex::build(&dir).unwrap();
+        let (_, range) = index.range(42).unwrap();
+
+        // Static file server answering range requests, recording the `Range` header of each request
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let served = dir.clone();
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let mut bytes_range = None;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("range") {
+                            bytes_range = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                recorded.lock().unwrap().push(bytes_range.clone());
+
+                let file = fs::read(served.join(path.trim_start_matches("/files/"))).unwrap();
+                let (start, end) = bytes_range
+                    .as_deref()
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.split_once('-'))
+                    .map(|(start, end)| (start.parse().unwrap(), end.parse::<usize>().unwrap()))
+                    .unwrap_or((0, file.len() - 1));
+                let head = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                    end + 1 - start,
+                    start,
+                    end,
+                    file.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&file[start..=end]).unwrap();
+            }
+        });
+
+        let store = RemoteStore::http(&format!("http://{}/files", addr)).unwrap();
+        let remote = RemoteBlockStore::new(Box::new(store), "", index);
+        let block = remote.block(42).unwrap().unwrap();
+        assert_eq!(block.number, 42);
+        assert_eq!(
+            requests.lock().unwrap().as_slice(),
+            [Some(format!("bytes={}-{}", range.start, range.end - 1))]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
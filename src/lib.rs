@@ -4,16 +4,39 @@
 //! provided in the block header. Optionally, the verifier can also check the block headers
 //! against a directory of block headers in json format.
 
+pub mod accumulator;
+pub mod beacon;
+pub mod bstream;
+pub mod compression;
+pub mod content_type;
+pub mod convert;
 pub mod dbin;
+pub mod era1;
 pub mod error;
+pub mod filter;
+#[cfg(feature = "firehose")]
+pub mod firehose;
 pub mod headers;
+pub mod index;
+pub mod output;
 pub mod receipts;
+pub mod rlp;
+pub mod rpc;
+#[cfg(feature = "serve")]
+pub mod server;
+pub mod store;
 pub mod transactions;
 
+use crate::bstream::{BlockEnvelope, DecodedBlock};
+use crate::compression::Compression;
+use crate::content_type::{is_ethereum, ContentTypeRegistry, DecodedPayload};
 use crate::error::DecodeError;
+use crate::filter::BlockFilter;
 use crate::headers::check_valid_header;
+use crate::output::{BlockSink, JsonFileSink};
 use crate::transactions::check_transaction_root;
-use dbin::DbinFile;
+use bytes::{Buf, Bytes};
+use dbin::{DbinFile, DbinReader, DbinReaderOptions};
 use error::CheckError;
 use headers::HeaderRecordWithNumber;
 use prost::Message;
@@ -23,12 +46,12 @@ use sf_protos::ethereum::r#type::v2::Block;
 use simple_log::log;
 use std::fs;
 use std::fs::File;
-use std::io::{BufReader, Cursor, Read, Write};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
 use tokio::join;
-use zstd::stream::decode_all;
 
-const MERGE_BLOCK: usize = 15537393;
+/// Number of the last pre-merge block
+pub const MERGE_BLOCK: usize = 15537393;
 
 pub enum DecodeInput {
     Path(String),
@@ -39,8 +62,9 @@ pub enum DecodeInput {
 ///
 /// This function processes input which can be a file or a directory containing multiple `.dbin` files.
 /// If `headers_dir` is provided, it verifies the block headers against the files found in this directory.
-/// These header files must be in JSON format and named after the block number they represent (e.g., `block-<block number>.json`).
-/// it can also handle `zstd` compressed flat files.
+/// These header files must be JSON-RPC headers named after the block number they represent (`block-<block number>.json`
+/// or `<block number>.json`), see [`headers::reference::ReferenceHeader`].
+/// it can also handle compressed flat files, see [`Compression`].
 ///
 /// # Arguments
 ///
@@ -49,55 +73,112 @@ pub enum DecodeInput {
 ///             If `None`, decoded blocks are not written to disk.
 /// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
 ///                  Must be a directory if provided.
-/// * `decompress`: An [`Option<bool>`] specifying if it is necessary to decompress from zstd.
+/// * `compression`: An [`Option<Compression>`] specifying how the flat files are compressed.
+///                  If `None`, the compression of each file is detected from its magic bytes.
 pub fn decode_flat_files(
     input: String,
     output: Option<&str>,
     headers_dir: Option<&str>,
-    decompress: Option<bool>,
+    compression: Option<Compression>,
 ) -> Result<Vec<Block>, DecodeError> {
-    let metadata = fs::metadata(&input).map_err(DecodeError::IoError)?;
+    let mut sink = output.map(JsonFileSink::new).transpose()?;
+    decode_flat_files_with_sink(
+        input,
+        sink.as_mut().map(|sink| sink as &mut dyn BlockSink),
+        headers_dir,
+        compression,
+        DbinReaderOptions::default(),
+    )
+}
 
-    if let Some(output) = output {
-        fs::create_dir_all(output).map_err(DecodeError::IoError)?;
-    }
+/// Same as [`decode_flat_files`], writing the decoded blocks to a [`BlockSink`].
+///
+/// Files of a directory are decoded in the order of their names, so that blocks reach the sink
+/// in ascending order. [`BlockSink::finish`] is called once all files are decoded.
+/// Each block is verified before being written, so a [`filter::FilterSink`] only sees valid blocks.
+///
+/// Files of a directory failing to decode are logged and skipped, unless blocks are written to a
+/// sink: the error is then returned, so that the output doesn't silently miss the blocks of a file.
+///
+/// # Arguments
+///
+/// * `input`: A [`String`] specifying the path to the input directory or file.
+/// * `sink`: An optional [`BlockSink`] the decoded blocks are written to, see [`output::open_sink`].
+/// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
+/// * `compression`: An [`Option<Compression>`] specifying how the flat files are compressed.
+/// * `options`: The [`DbinReaderOptions`] the flat files are read with.
+pub fn decode_flat_files_with_sink(
+    input: String,
+    mut sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let metadata = fs::metadata(&input).map_err(DecodeError::IoError)?;
 
-    if metadata.is_dir() {
-        decode_flat_files_dir(&input, output, headers_dir, decompress)
+    let blocks = if metadata.is_dir() {
+        decode_flat_files_dir(
+            &input,
+            sink.as_deref_mut(),
+            headers_dir,
+            compression,
+            options,
+        )
     } else if metadata.is_file() {
-        handle_file(&PathBuf::from(input), output, headers_dir, decompress)
+        handle_file_with_sink(
+            &PathBuf::from(input),
+            sink.as_deref_mut(),
+            headers_dir,
+            compression,
+            options,
+        )
     } else {
         Err(DecodeError::InvalidInput)
+    }?;
+
+    if let Some(sink) = sink {
+        sink.finish()?;
     }
+    Ok(blocks)
 }
 
 fn decode_flat_files_dir(
     input: &str,
-    output: Option<&str>,
+    mut sink: Option<&mut (dyn BlockSink + '_)>,
     headers_dir: Option<&str>,
-    decompress: Option<bool>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
 ) -> Result<Vec<Block>, DecodeError> {
-    let paths = fs::read_dir(input).map_err(DecodeError::IoError)?;
+    let mut paths = fs::read_dir(input)
+        .map_err(DecodeError::IoError)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(DecodeError::IoError)?;
+    paths.sort();
 
     let mut blocks: Vec<Block> = vec![];
     for path in paths {
-        let path = path.map_err(DecodeError::IoError)?;
-        match path.path().extension() {
-            Some(ext) => {
-                if ext != "dbin" {
-                    continue;
-                }
-            }
-            None => continue,
-        };
+        if !is_flat_file(&path) {
+            continue;
+        }
 
-        println!("Processing file: {}", path.path().display());
-        match handle_file(&path.path(), output, headers_dir, decompress) {
+        println!("Processing file: {}", path.display());
+        match handle_file_with_sink(
+            &path,
+            sink.as_deref_mut(),
+            headers_dir,
+            compression,
+            options,
+        ) {
             Ok(file_blocks) => {
                 blocks.extend(file_blocks);
             }
+            Err(err) if sink.is_some() => {
+                log::error!("Failed to process file {}: {}", path.display(), err);
+                return Err(err);
+            }
             Err(err) => {
-                println!("Failed to process file: {}", err);
+                log::error!("Failed to process file {}: {}", path.display(), err);
             }
         }
     }
@@ -105,13 +186,28 @@ fn decode_flat_files_dir(
     Ok(blocks)
 }
 
+/// Whether a path looks like a flat file, either plain (`.dbin`) or compressed (e.g. `.dbin.zst`).
+fn is_flat_file(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("dbin") => true,
+        Some(ext) => {
+            ext.parse::<Compression>().is_ok()
+                && path
+                    .file_stem()
+                    .map(|stem| Path::new(stem).extension() == Some("dbin".as_ref()))
+                    .unwrap_or(false)
+        }
+        None => false,
+    }
+}
+
 /// Decodes and optionally verifies block flat files from a single file.
 ///
 /// This function decodes flat files and, if an `output` directory is provided, writes the decoded blocks to this directory.
 /// If no `output` is specified, the decoded blocks are not written to disk. The function can also verify block headers
-/// against header files found in an optional `headers_dir`. These header files must be in JSON format and named after
-/// the block number they represent (e.g., `block-<block number>.json`). Additionally, the function supports handling `zstd` compressed
-/// flat files if decompression is required.
+/// against header files found in an optional `headers_dir`. These header files must be JSON-RPC headers named after
+/// the block number they represent (`block-<block number>.json` or `<block number>.json`), see
+/// [`headers::reference::ReferenceHeader`]. Additionally, the function supports handling compressed flat files, see [`Compression`].
 ///
 /// # Arguments
 ///
@@ -120,26 +216,73 @@ fn decode_flat_files_dir(
 ///             If `None`, decoded blocks are not written to disk.
 /// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
 ///                  Must be a directory if provided.
-/// * `decompress`: An [`Option<bool>`] indicating whether decompression from `zstd` format is necessary.
+/// * `compression`: An [`Option<Compression>`] indicating how the file is compressed.
+///                  If `None`, it is detected from the file's magic bytes.
 ///
 pub fn handle_file(
     path: &PathBuf,
     output: Option<&str>,
     headers_dir: Option<&str>,
-    decompress: Option<bool>,
+    compression: Option<Compression>,
+) -> Result<Vec<Block>, DecodeError> {
+    handle_file_with_options(
+        path,
+        output,
+        headers_dir,
+        compression,
+        DbinReaderOptions::default(),
+    )
+}
+
+/// Same as [`handle_file`], reading the flat file with custom [`DbinReaderOptions`]
+pub fn handle_file_with_options(
+    path: &PathBuf,
+    output: Option<&str>,
+    headers_dir: Option<&str>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let mut sink = output.map(JsonFileSink::new).transpose()?;
+    let blocks = handle_file_with_sink(
+        path,
+        sink.as_mut().map(|sink| sink as &mut dyn BlockSink),
+        headers_dir,
+        compression,
+        options,
+    )?;
+    if let Some(mut sink) = sink {
+        sink.finish()?;
+    }
+    Ok(blocks)
+}
+
+fn handle_file_with_sink(
+    path: &PathBuf,
+    sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
 ) -> Result<Vec<Block>, DecodeError> {
     let input_file = BufReader::new(File::open(path).map_err(DecodeError::IoError)?);
-    // Check if decompression is required and read the file accordingly.
-    let mut file_contents: Box<dyn Read> = if decompress == Some(true) {
-        let decompressed_data = decode_all(input_file)
-            .map_err(|e| DecodeError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
-        Box::new(Cursor::new(decompressed_data))
-    } else {
-        Box::new(input_file)
+    handle_reader_with_sink(input_file, sink, headers_dir, compression, options)
+}
+
+/// Same as [`handle_file_with_sink`], reading the flat file from `reader`
+fn handle_reader_with_sink<R: BufRead>(
+    mut reader: R,
+    mut sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let compression = match compression {
+        Some(compression) => compression,
+        None => Compression::detect_from(&mut reader)?,
     };
+    let mut file_contents = compression.decoder(reader)?;
 
-    let dbin_file = DbinFile::try_from_read(&mut file_contents)?;
-    if dbin_file.header.content_type != "ETH" {
+    let dbin_file = DbinFile::try_from_read_with_options(&mut file_contents, options)?;
+    if !is_ethereum(&dbin_file.header.content_type) {
         return Err(DecodeError::InvalidContentType(
             dbin_file.header.content_type,
         ));
@@ -148,7 +291,11 @@ pub fn handle_file(
     let mut blocks: Vec<Block> = vec![];
 
     for message in dbin_file.messages {
-        blocks.push(handle_block(&message, output, headers_dir)?);
+        let block = handle_block(&message, headers_dir)?;
+        if let Some(sink) = sink.as_deref_mut() {
+            sink.write_block(&block)?;
+        }
+        blocks.push(block);
     }
 
     Ok(blocks)
@@ -156,40 +303,182 @@ pub fn handle_file(
 
 /// Decodes a flat file from a buffer containing its contents and optionally decompresses it.
 ///
-/// Decodes flat files that are already loaded into memory, without direct file system ac   cess.
-/// It can handle both compressed (if `zstd` decompression is specified) and uncompressed data. Upon successful
+/// Decodes flat files that are already loaded into memory, without direct file system access.
+/// It can handle both compressed (see [`Compression`]) and uncompressed data. Upon successful
 /// decoding, it returns a vector of all the blocks contained within the flat file. The actual number of blocks
 /// returned depends on the format and content of the flat file—ranging from a single block to multiple blocks.
 ///
 /// # Arguments
 ///
 /// * `buf`: A byte slice referencing the in-memory content of the flat file to be decoded.
-/// * `decompress`: A boolean indicating whether the input buffer should be decompressed.
+/// * `compression`: An [`Option<Compression>`] indicating how the buffer is compressed.
+///                  If `None`, it is detected from the buffer's magic bytes.
 ///
-pub fn handle_buf(buf: &[u8], decompress: Option<bool>) -> Result<Vec<Block>, DecodeError> {
-    let buf = if decompress.unwrap_or(false) {
-        zstd::decode_all(buf).map_err(|_| DecodeError::DecompressError)?
-    } else {
-        buf.to_vec()
-    };
+pub fn handle_buf(buf: &[u8], compression: Option<Compression>) -> Result<Vec<Block>, DecodeError> {
+    handle_buf_with_options(buf, compression, DbinReaderOptions::default())
+}
 
-    let dbin_file = DbinFile::try_from_read(&mut Cursor::new(buf))?;
+/// Same as [`handle_buf`], reading the flat file with custom [`DbinReaderOptions`]
+pub fn handle_buf_with_options(
+    buf: &[u8],
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let compression = compression.unwrap_or_else(|| Compression::detect(buf));
+    let mut reader = compression.decoder(buf)?;
+
+    let dbin_file = DbinFile::try_from_read_with_options(&mut reader, options)?;
+    if !is_ethereum(&dbin_file.header.content_type) {
+        return Err(DecodeError::InvalidContentType(
+            dbin_file.header.content_type,
+        ));
+    }
 
     let mut blocks: Vec<Block> = vec![];
 
     for message in dbin_file.messages {
-        blocks.push(handle_block(&message, None, None)?);
+        blocks.push(handle_block(&message, None)?);
     }
     Ok(blocks)
 }
 
-fn handle_block(
-    message: &Vec<u8>,
-    output: Option<&str>,
-    headers_dir: Option<&str>,
-) -> Result<Block, DecodeError> {
+/// Decodes a flat file held in a [`Bytes`] buffer, e.g. read with [`read_file_bytes`].
+///
+/// Unlike [`handle_buf`], messages and their block payloads are not copied out of `bytes`:
+/// both the dbin messages and the `sf.bstream.v1.Block` payloads are views into the same buffer.
+/// Compressed input is decompressed into a new buffer first.
+///
+/// # Arguments
+///
+/// * `bytes`: A [`Bytes`] buffer holding the content of the flat file.
+/// * `compression`: An [`Option<Compression>`] indicating how the buffer is compressed.
+///                  If `None`, it is detected from the buffer's magic bytes.
+///
+pub fn handle_bytes(
+    bytes: Bytes,
+    compression: Option<Compression>,
+) -> Result<Vec<Block>, DecodeError> {
+    handle_bytes_with_options(bytes, compression, DbinReaderOptions::default())
+}
+
+/// Same as [`handle_bytes`], reading the flat file with custom [`DbinReaderOptions`]
+pub fn handle_bytes_with_options(
+    bytes: Bytes,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let blocks = handle_bytes_with_envelopes_and_options(bytes, compression, options)?;
+    Ok(blocks.into_iter().map(|decoded| decoded.block).collect())
+}
+
+/// Decodes a flat file held in a [`Bytes`] buffer, keeping the bstream envelope of each block.
+///
+/// Same as [`handle_bytes`], but returns each block as a [`DecodedBlock`] so that the envelope's
+/// metadata (timestamp, LIB number, payload kind and version) is available to the caller.
+pub fn handle_bytes_with_envelopes(
+    bytes: Bytes,
+    compression: Option<Compression>,
+) -> Result<Vec<DecodedBlock>, DecodeError> {
+    handle_bytes_with_envelopes_and_options(bytes, compression, DbinReaderOptions::default())
+}
+
+/// Same as [`handle_bytes_with_envelopes`], reading the flat file with custom [`DbinReaderOptions`]
+pub fn handle_bytes_with_envelopes_and_options(
+    bytes: Bytes,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<DecodedBlock>, DecodeError> {
+    let dbin_file =
+        DbinFile::try_from_bytes_with_options(decompress_bytes(bytes, compression)?, options)?;
+    if !is_ethereum(&dbin_file.header.content_type) {
+        return Err(DecodeError::InvalidContentType(
+            dbin_file.header.content_type,
+        ));
+    }
+
+    let mut blocks: Vec<DecodedBlock> = vec![];
+
+    for message in dbin_file.messages {
+        let DecodedBlock { envelope, block } = decode_block_with_envelope(message)?;
+        let block = verify_block(block, None)?;
+        blocks.push(DecodedBlock { envelope, block });
+    }
+    Ok(blocks)
+}
+
+/// Decodes a flat file of any content type held in a [`Bytes`] buffer.
+///
+/// Payloads are decoded by the decoder registered for the content type found in the dbin header.
+/// With [`ContentTypeRegistry::passthrough`], the raw payload of every block is returned.
+///
+/// # Arguments
+///
+/// * `bytes`: A [`Bytes`] buffer holding the content of the flat file.
+/// * `compression`: An [`Option<Compression>`] indicating how the buffer is compressed.
+///                  If `None`, it is detected from the buffer's magic bytes.
+/// * `registry`: The [`ContentTypeRegistry`] to look the payload decoder up from.
+///
+pub fn handle_bytes_with_registry(
+    bytes: Bytes,
+    compression: Option<Compression>,
+    registry: &ContentTypeRegistry,
+) -> Result<Vec<DecodedPayload>, DecodeError> {
+    handle_bytes_with_registry_and_options(
+        bytes,
+        compression,
+        registry,
+        DbinReaderOptions::default(),
+    )
+}
+
+/// Same as [`handle_bytes_with_registry`], reading the flat file with custom [`DbinReaderOptions`]
+pub fn handle_bytes_with_registry_and_options(
+    bytes: Bytes,
+    compression: Option<Compression>,
+    registry: &ContentTypeRegistry,
+    options: DbinReaderOptions,
+) -> Result<Vec<DecodedPayload>, DecodeError> {
+    let dbin_file =
+        DbinFile::try_from_bytes_with_options(decompress_bytes(bytes, compression)?, options)?;
+    let decoder = registry.decoder(&dbin_file.header.content_type)?;
+
+    let mut blocks: Vec<DecodedPayload> = vec![];
+
+    for message in dbin_file.messages {
+        let mut envelope = BlockEnvelope::decode(message)
+            .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+        let payload = envelope.take_payload();
+        let payload = decoder.decode(&envelope, payload)?;
+        blocks.push(DecodedPayload { envelope, payload });
+    }
+    Ok(blocks)
+}
+
+fn decompress_bytes(bytes: Bytes, compression: Option<Compression>) -> Result<Bytes, DecodeError> {
+    let compression = compression.unwrap_or_else(|| Compression::detect(&bytes));
+    match compression {
+        Compression::None => Ok(bytes),
+        compression => {
+            let mut decompressed = Vec::new();
+            compression
+                .decoder(bytes.as_ref())?
+                .read_to_end(&mut decompressed)?;
+            Ok(Bytes::from(decompressed))
+        }
+    }
+}
+
+/// Reads a whole file into a [`Bytes`] buffer, to be decoded with [`handle_bytes`].
+pub fn read_file_bytes(path: &Path) -> Result<Bytes, DecodeError> {
+    Ok(Bytes::from(fs::read(path)?))
+}
+
+fn handle_block(message: &Vec<u8>, headers_dir: Option<&str>) -> Result<Block, DecodeError> {
     let block = decode_block_from_bytes(message)?;
+    verify_block(block, headers_dir)
+}
 
+fn verify_block(block: Block, headers_dir: Option<&str>) -> Result<Block, DecodeError> {
     if let Some(headers_dir) = headers_dir {
         check_valid_header(&block, headers_dir)?;
     }
@@ -198,38 +487,66 @@ fn handle_block(
         check_transaction_root(&block)?;
     }
 
-    if let Some(output) = output {
-        let file_name = format!("{}/block-{}.json", output, block.number);
-        let mut out_file = File::create(file_name)?;
-
-        let block_json = serde_json::to_string(&block)
-            .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
-
-        out_file
-            .write_all(block_json.as_bytes())
-            .map_err(DecodeError::IoError)?;
-    }
-
     Ok(block)
 }
 
 /// Gets a vector of blocks from a single .dbin file
-pub fn extract_blocks<R: Read>(mut reader: R) -> Result<Vec<Block>, DecodeError> {
+pub fn extract_blocks<R: Read>(reader: R) -> Result<Vec<Block>, DecodeError> {
+    extract_blocks_with_options(reader, DbinReaderOptions::default())
+}
+
+/// Same as [`extract_blocks`], reading the file with custom [`DbinReaderOptions`]
+pub fn extract_blocks_with_options<R: Read>(
+    mut reader: R,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
     log::debug!("Reading messages");
-    let dbin_file = DbinFile::try_from_read(&mut reader)?;
+    let dbin_file = DbinFile::try_from_read_with_options(&mut reader, options)?;
+    if !is_ethereum(&dbin_file.header.content_type) {
+        return Err(DecodeError::InvalidContentType(
+            dbin_file.header.content_type,
+        ));
+    }
     log::debug!("Validating blocks");
 
     // Parallel processing of block headers
     dbin_file
         .messages
         .par_iter()
-        .map(|message| handle_block(message, None, None))
+        .map(|message| handle_block(message, None))
+        .collect()
+}
+
+/// Gets a vector of blocks from a single .dbin file by memory-mapping it.
+///
+/// Messages are decoded and validated in parallel directly from the mapping,
+/// without read syscalls or per-message allocations. See [`DbinFile::open_mmap`].
+pub fn extract_blocks_mmap(path: &Path) -> Result<Vec<Block>, DecodeError> {
+    extract_blocks_mmap_with_options(path, DbinReaderOptions::default())
+}
+
+/// Same as [`extract_blocks_mmap`], reading the file with custom [`DbinReaderOptions`]
+pub fn extract_blocks_mmap_with_options(
+    path: &Path,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let dbin_file = DbinFile::open_mmap_with_options(path, options)?;
+    if !is_ethereum(&dbin_file.header.content_type) {
+        return Err(DecodeError::InvalidContentType(
+            dbin_file.header.content_type,
+        ));
+    }
+
+    dbin_file
+        .par_messages()
+        .map(|message| verify_block(decode_block_from_bytes(message)?, None))
         .collect()
 }
 
 /// Decode blocks from a reader and writes them, serialized, to a writer
 ///
 /// data can be piped into this function from stdin via `cargo run stream < ./example0017686312.dbin`.
+/// Compressed input should be wrapped with [`Compression::decoder`] before being passed in.
 /// It also has a check for end_block. By default, it stops the stream reading when MERGE_BLOCK
 /// is reached.
 ///
@@ -240,18 +557,45 @@ pub fn extract_blocks<R: Read>(mut reader: R) -> Result<Vec<Block>, DecodeError>
 /// * `reader`: where bytes are read from
 /// * `writer`: where bytes written to
 pub async fn stream_blocks<R: Read, W: Write>(
-    mut reader: R,
+    reader: R,
+    writer: W,
+    end_block: Option<usize>,
+) -> Result<(), DecodeError> {
+    stream_blocks_with_options(reader, writer, end_block, DbinReaderOptions::default()).await
+}
+
+/// Same as [`stream_blocks`], reading the stream with custom [`DbinReaderOptions`]
+pub async fn stream_blocks_with_options<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    end_block: Option<usize>,
+    options: DbinReaderOptions,
+) -> Result<(), DecodeError> {
+    stream_blocks_with_filter(reader, writer, end_block, options, None).await
+}
+
+/// Same as [`stream_blocks_with_options`], only writing the header records of the blocks
+/// holding transactions matching `filter`.
+///
+/// Every block is verified before being checked against the filter, the same way the Firehose
+/// client does. The records of a filtered stream skip the blocks without matching transactions,
+/// so they can't be used to build an accumulator.
+pub async fn stream_blocks_with_filter<R: Read, W: Write>(
+    reader: R,
     mut writer: W,
     end_block: Option<usize>,
+    options: DbinReaderOptions,
+    filter: Option<&BlockFilter>,
 ) -> Result<(), DecodeError> {
     let end_block = match end_block {
         Some(end_block) => end_block,
         None => MERGE_BLOCK,
     };
     let mut block_number = 0;
+    let mut reader = DbinReader::with_options(reader, options);
     loop {
-        match DbinFile::read_message_stream(&mut reader) {
-            Ok(message) => {
+        match reader.next_message() {
+            Ok(Some(message)) => {
                 let block = decode_block_from_bytes(&message)?;
                 block_number = block.number as usize;
 
@@ -267,16 +611,12 @@ pub async fn stream_blocks<R: Read, W: Write>(
                 joint_return.0.map_err(DecodeError::JoinError)?;
                 joint_return.1.map_err(DecodeError::JoinError)?;
 
-                let header_record_with_number = HeaderRecordWithNumber::try_from(block)?;
-                let header_record_bin = bincode::serialize(&header_record_with_number)
-                    .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
-
-                let size = header_record_bin.len() as u32;
-                writer.write_all(&size.to_be_bytes())?;
-                writer.write_all(&header_record_bin)?;
-                writer.flush().map_err(DecodeError::IoError)?;
+                if filter.is_some_and(|filter| !filter.matches_block(&block)) {
+                    continue;
+                }
+                write_header_record(block, &mut writer)?;
             }
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Ok(None) => {
                 if block_number < end_block {
                     log::info!("Reached end of file, waiting for more blocks");
                     continue; // More blocks to read
@@ -293,13 +633,41 @@ pub async fn stream_blocks<R: Read, W: Write>(
     Ok(())
 }
 
-fn decode_block_from_bytes(bytes: &Vec<u8>) -> Result<Block, DecodeError> {
-    let block_stream = sf_protos::bstream::v1::Block::decode(bytes.as_slice())
+/// Writes the bincode serialized [`HeaderRecordWithNumber`] of `block`, prefixed by its big-endian `u32` size
+pub(crate) fn write_header_record<W: Write>(
+    block: Block,
+    writer: &mut W,
+) -> Result<(), DecodeError> {
+    let header_record_with_number = HeaderRecordWithNumber::try_from(block)?;
+    let header_record_bin = bincode::serialize(&header_record_with_number)
         .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+
+    let size = header_record_bin.len() as u32;
+    writer.write_all(&size.to_be_bytes())?;
+    writer.write_all(&header_record_bin)?;
+    writer.flush().map_err(DecodeError::IoError)?;
+    Ok(())
+}
+
+fn decode_block_from_bytes(bytes: &[u8]) -> Result<Block, DecodeError> {
+    Ok(decode_block_with_envelope(bytes)?.block)
+}
+
+/// Decodes a single flat file message into its bstream envelope and Ethereum block.
+///
+/// The envelope's number, id and parent id are checked against the decoded block,
+/// see [`BlockEnvelope::validate`]. When `buf` is a [`Bytes`] buffer, the payload is decoded
+/// as a view into it, so the only copies made are the ones of the fields of the resulting [`Block`].
+pub fn decode_block_with_envelope<B: Buf>(buf: B) -> Result<DecodedBlock, DecodeError> {
+    let mut envelope =
+        BlockEnvelope::decode(buf).map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+    let payload = envelope.take_payload();
     let block =
-        sf_protos::ethereum::r#type::v2::Block::decode(block_stream.payload_buffer.as_slice())
-            .map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
-    Ok(block)
+        Block::decode(payload).map_err(|err| DecodeError::ProtobufError(err.to_string()))?;
+
+    envelope.validate(&block)?;
+
+    Ok(DecodedBlock { envelope, block })
 }
 
 // Define a generic function to spawn a blocking task for a given check.
@@ -320,9 +688,27 @@ where
 mod tests {
     use prost::Message;
 
-    use crate::dbin::DbinFile;
+    use crate::bstream::error::EnvelopeError;
+    use crate::bstream::DecodedBlock;
+    use crate::compression::Compression;
+    use crate::content_type::ContentTypeRegistry;
+    use crate::dbin::error::DbinFileError;
+    use crate::dbin::{DbinFile, DbinReaderOptions};
+    use crate::error::DecodeError;
+    use crate::filter::BlockFilter;
+    use crate::headers::HeaderRecordWithNumber;
+    use crate::output::JsonLinesSink;
     use crate::receipts::check_receipt_root;
-    use crate::{handle_buf, handle_file, receipts, stream_blocks};
+    use crate::{
+        decode_block_with_envelope, decode_flat_files_with_sink, extract_blocks_mmap,
+        extract_blocks_mmap_with_options, extract_blocks_with_options, handle_buf,
+        handle_buf_with_options, handle_bytes, handle_bytes_with_envelopes,
+        handle_bytes_with_options, handle_bytes_with_registry_and_options, handle_file,
+        handle_file_with_options, read_file_bytes, receipts, stream_blocks,
+        stream_blocks_with_filter,
+    };
+    use bytes::Bytes;
+    use reth_primitives::hex;
     use sf_protos::bstream::v1::Block as BstreamBlock;
     use sf_protos::ethereum::r#type::v2::Block;
     use std::fs::File;
@@ -343,13 +729,107 @@ mod tests {
     fn test_handle_file_zstd() {
         let path = PathBuf::from("./tests/0000000000.dbin.zst");
 
-        let result = handle_file(&path, None, None, Some(true));
+        let result = handle_file(&path, None, None, Some(Compression::Zstd));
 
         assert!(result.is_ok());
         let blocks: Vec<Block> = result.unwrap();
         assert_eq!(blocks[0].number, 0);
     }
 
+    #[test]
+    fn test_handle_file_max_message_size() {
+        let path = PathBuf::from("./tests/0000000000.dbin.zst");
+        let options = DbinReaderOptions {
+            max_message_size: 32,
+        };
+
+        let result = handle_file_with_options(&path, None, None, None, options);
+
+        assert!(matches!(
+            result,
+            Err(DecodeError::DbinFileError(
+                DbinFileError::MessageTooLarge { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_decode_dir_corrupt_file() {
+        let dir = std::env::temp_dir().join("decoder-test-corrupt-dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("tests/0000000000.dbin.zst", dir.join("0000000000.dbin.zst")).unwrap();
+        std::fs::write(dir.join("0000000100.dbin"), b"dbin corrupt").unwrap();
+        let input = dir.to_string_lossy().to_string();
+
+        let blocks = decode_flat_files_with_sink(
+            input.clone(),
+            None,
+            None,
+            None,
+            DbinReaderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 100);
+
+        let mut sink = JsonLinesSink::new(&dir.join("output"), Compression::None, None).unwrap();
+        let result = decode_flat_files_with_sink(
+            input,
+            Some(&mut sink),
+            None,
+            None,
+            DbinReaderOptions::default(),
+        );
+        assert!(matches!(result, Err(DecodeError::DbinFileError(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_message_size_readers() {
+        let path = PathBuf::from("./tests/0000000000.dbin.zst");
+        let file = std::fs::read(&path).unwrap();
+        let options = DbinReaderOptions {
+            max_message_size: 32,
+        };
+        fn too_large<T>(result: Result<T, DecodeError>) -> bool {
+            matches!(
+                result,
+                Err(DecodeError::DbinFileError(
+                    DbinFileError::MessageTooLarge { .. }
+                ))
+            )
+        }
+
+        assert!(too_large(handle_buf_with_options(&file, None, options)));
+        assert!(too_large(handle_bytes_with_options(
+            Bytes::from(file.clone()),
+            None,
+            options
+        )));
+        assert!(too_large(handle_bytes_with_registry_and_options(
+            Bytes::from(file.clone()),
+            None,
+            &ContentTypeRegistry::passthrough(),
+            options
+        )));
+        let decompressed = zstd::decode_all(file.as_slice()).unwrap();
+        assert!(too_large(extract_blocks_with_options(
+            decompressed.as_slice(),
+            options
+        )));
+
+        let dir = std::env::temp_dir().join("decoder-test-max-message-size");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dbin_path = dir.join("0000000000.dbin");
+        std::fs::write(&dbin_path, &decompressed).unwrap();
+        assert!(too_large(extract_blocks_mmap_with_options(
+            &dbin_path, options
+        )));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_check_valid_root_fail() {
         let path = PathBuf::from("example0017686312.dbin");
@@ -398,6 +878,51 @@ mod tests {
         );
     }
 
+    /// Header records written by a stream, each prefixed by its big-endian `u32` size
+    fn header_records(mut output: &[u8]) -> Vec<HeaderRecordWithNumber> {
+        let mut records = Vec::new();
+        while !output.is_empty() {
+            let (size, rest) = output.split_at(4);
+            let size = u32::from_be_bytes(size.try_into().unwrap()) as usize;
+            let (record, rest) = rest.split_at(size);
+            records.push(bincode::deserialize(record).unwrap());
+            output = rest;
+        }
+        records
+    }
+
+    #[test]
+    fn test_block_stream_filter() {
+        let mut input = zstd::decode_all(File::open("tests/0000000000.dbin.zst").unwrap()).unwrap();
+        input.extend(std::fs::read("example0017686312.dbin").unwrap());
+
+        let block = handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None)
+            .unwrap()
+            .remove(0);
+        let expression = format!("from=0x{}", hex::encode(&block.transaction_traces[0].from));
+        let filter: BlockFilter = expression.parse().unwrap();
+
+        let stream = |filter: Option<&BlockFilter>| {
+            let mut output = Vec::new();
+            tokio_test::block_on(stream_blocks_with_filter(
+                input.as_slice(),
+                &mut output,
+                None,
+                DbinReaderOptions::default(),
+                filter,
+            ))
+            .unwrap();
+            header_records(&output)
+        };
+
+        assert_eq!(stream(None).len(), 101);
+        // the first 100 blocks don't hold any transaction
+        let records = stream(Some(&filter));
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].block_number, 17686312);
+        assert_eq!(records[0].block_hash, block.hash);
+    }
+
     #[test]
     fn test_handle_buff() {
         let path = PathBuf::from("example0017686312.dbin");
@@ -410,7 +935,7 @@ mod tests {
             .read_to_end(&mut buffer)
             .expect("Failed to read file");
 
-        let result = handle_buf(&buffer, Some(false));
+        let result = handle_buf(&buffer, Some(Compression::None));
         assert!(result.is_ok(), "handle_buf should complete successfully");
     }
 
@@ -426,10 +951,71 @@ mod tests {
             .read_to_end(&mut buffer)
             .expect("Failed to read file");
 
-        let result = handle_buf(&buffer, Some(true));
+        let result = handle_buf(&buffer, Some(Compression::Zstd));
         assert!(
             result.is_ok(),
             "handle_buf should complete successfully with decompression"
         );
     }
+
+    #[test]
+    fn test_handle_bytes() {
+        let path = PathBuf::from("example0017686312.dbin");
+        let bytes = read_file_bytes(&path).expect("Failed to read file");
+
+        let blocks = handle_bytes(bytes, None).expect("handle_bytes should decode the file");
+        let expected = handle_file(&path, None, None, None).unwrap();
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn test_handle_bytes_with_envelopes() {
+        let bytes = read_file_bytes(&PathBuf::from("example0017686312.dbin")).unwrap();
+
+        let blocks = handle_bytes_with_envelopes(bytes, None).unwrap();
+        let DecodedBlock { envelope, block } = &blocks[0];
+        assert_eq!(envelope.number, 17686312);
+        assert_eq!(envelope.number, block.number);
+        assert_eq!(
+            envelope.id,
+            "030613279dd0e91f5dc80ffa2d1fd0023a9386814c705c580f5bad14978d108e"
+        );
+        assert!(envelope.payload_buffer.is_empty());
+    }
+
+    #[test]
+    fn test_mismatched_envelope() {
+        let path = PathBuf::from("example0017686312.dbin");
+        let mut file = BufReader::new(File::open(path).expect("Failed to open file"));
+        let dbin_file = DbinFile::try_from_read(&mut file).expect("Failed to parse dbin file");
+
+        let mut block_stream = BstreamBlock::decode(dbin_file.messages[0].as_slice()).unwrap();
+        block_stream.number += 1;
+        let message = block_stream.encode_to_vec();
+
+        let result = decode_block_with_envelope(message.as_slice());
+        assert!(matches!(
+            result,
+            Err(DecodeError::EnvelopeError(EnvelopeError::MismatchedNumber(
+                17686313, 17686312
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_extract_blocks_mmap() {
+        let path = PathBuf::from("example0017686312.dbin");
+
+        let blocks = extract_blocks_mmap(&path).expect("Failed to decode mapped file");
+        let expected = handle_file(&path, None, None, None).unwrap();
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn test_handle_buff_detect_compression() {
+        let buffer = std::fs::read("tests/0000000000.dbin.zst").expect("Failed to read file");
+
+        let blocks = handle_buf(&buffer, None).expect("handle_buf should detect zstd");
+        assert_eq!(blocks[0].number, 0);
+    }
 }
@@ -1,6 +1,30 @@
-use clap::{Parser, Subcommand};
-use decoder::{decode_flat_files, stream_blocks};
+use alloy_primitives::U256;
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+#[cfg(feature = "firehose")]
+use decoder::firehose::{self, client::FirehoseClient, server::FirehoseServer};
+use decoder::index::BlockIndex;
+#[cfg(any(feature = "serve", feature = "firehose"))]
+use decoder::index::{remote::RemoteBlockStore, BlockLookup, BlockReader};
+#[cfg(feature = "serve")]
+use decoder::server::{self, RpcServer};
+use decoder::{
+    accumulator::{error::AccumulatorError, verify_dir, PreMergeAccumulator},
+    beacon::{self, BeaconFork},
+    compression::Compression,
+    dbin::{DbinReaderOptions, DEFAULT_MAX_MESSAGE_SIZE},
+    decode_flat_files_with_sink,
+    era1::compare::compare_dirs,
+    filter::{BlockFilter, FilterSink},
+    headers::source::{check_headers_dir, open_header_source},
+    headers::total_difficulty::{check_total_difficulty_dir, TotalDifficultyChecker},
+    output::{open_sink, BlockSink, OutputFormat, SinkOptions, StoreSink},
+    store::{decode_store, is_store_url, open_store},
+    stream_blocks_with_filter, MERGE_BLOCK,
+};
+use sf_protos::ethereum::r#type::v2::Block;
 use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -13,28 +37,178 @@ struct Cli {
 enum Commands {
     /// Stream data continuously
     Stream {
-        /// decompress .dibn files if they are compressed with zstd
-        #[clap(short, long, default_value = "false")]
+        /// compression of the incoming .dbin stream: none, zstd, gzip, xz or lz4.
+        /// Detected from the stream's magic bytes if not set
+        #[clap(short, long)]
+        compression: Option<Compression>,
+        /// deprecated alias of --compression zstd
+        #[clap(short, long, hide = true, conflicts_with = "compression")]
         decompress: bool,
         /// the block to end streaming
         #[clap(short, long)]
         end_block: Option<usize>,
+        /// maximum size in bytes of a single message, larger ones are rejected as corrupt
+        #[clap(long, default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+        max_message_size: u32,
+        /// Firehose endpoint to stream blocks from instead of stdin, like http://localhost:10015.
+        /// Needs the firehose feature
+        #[clap(long)]
+        firehose: Option<String>,
+        /// the block to start streaming from Firehose, negative values being relative to the head block
+        #[clap(long, default_value_t = 0, allow_negative_numbers = true)]
+        start_block: i64,
+        /// only stream the blocks holding transactions matching a filter of comma separated
+        /// key=value conditions on from, to, log (log address), topic0 and call (root call type),
+        /// like to=0x...,topic0=0x...
+        #[clap(long)]
+        filter: Option<BlockFilter>,
     },
     /// Decode files from input to output
     Decode {
-        /// input folder where flat files are stored
-        #[clap(short, long)]
-        input: String,
+        /// input folder where flat files are stored, or a store URL: s3://<bucket>/<prefix>
+        /// (needs the s3 feature) or gs://<bucket>/<prefix> (needs the gcs feature)
+        #[clap(short, long, required_unless_present = "firehose")]
+        input: Option<String>,
         #[clap(long)]
         /// folder where valid headers are stored so decoded blocks can be validated against
-        /// their headers.
+        /// their headers, as JSON-RPC headers named block-<number>.json or <number>.json
         headers_dir: Option<String>,
-        /// output folder where decoded headers will be stored as .json
+        /// output folder where decoded headers will be stored as .json, or a store URL like --input
         #[clap(short, long)]
         output: Option<String>,
         #[clap(short, long)]
-        /// optionally decompress zstd compressed flat files
+        /// compression of the flat files: none, zstd, gzip, xz or lz4.
+        /// Detected from each file's magic bytes if not set
+        compression: Option<Compression>,
+        /// deprecated alias of --compression zstd (true) or --compression none (false)
+        #[clap(short, long, hide = true, conflicts_with = "compression")]
         decompress: Option<bool>,
+        /// format of the decoded blocks written to the output folder: json (one file per block),
+        /// jsonl (one block per line), rpc (JSON-RPC blocks and receipts, one file each per block)
+        /// parquet (blocks, transactions, logs and traces datasets, needs the parquet feature),
+        /// era1 (pre-merge blocks in era1 archives) or rlp (canonical RLP blocks and receipts)
+        #[clap(short, long, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+        /// number of blocks per output file, jsonl, rlp and parquet only. All blocks go to a single
+        /// jsonl or rlp file if not set, parquet datasets are partitioned every 1000 blocks
+        #[clap(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        blocks_per_file: Option<usize>,
+        /// compression of the output files, jsonl and rlp only: none, zstd, gzip, xz or lz4
+        #[clap(long, default_value_t = Compression::None)]
+        output_compression: Compression,
+        /// Firehose endpoint to decode blocks from instead of the input folder, like
+        /// http://localhost:10015. Needs the firehose feature
+        #[clap(long, conflicts_with = "input", requires = "stop_block")]
+        firehose: Option<String>,
+        /// first block to decode from Firehose, negative values being relative to the head block
+        #[clap(long, default_value_t = 0, allow_negative_numbers = true)]
+        start_block: i64,
+        /// last block to decode from Firehose, included
+        #[clap(long)]
+        stop_block: Option<u64>,
+        /// only write the transactions matching a filter of comma separated key=value conditions
+        /// on from, to, log (log address), topic0 and call (root call type), like
+        /// to=0x...,topic0=0x..., along with their receipts. Blocks without any are skipped.
+        /// Needs an --output in the json, jsonl, rpc or parquet format
+        #[clap(long, requires = "output")]
+        filter: Option<BlockFilter>,
+        /// maximum size in bytes of a single message of the flat files, larger ones are rejected as corrupt
+        #[clap(long, default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+        max_message_size: u32,
+    },
+    /// Compare flat files block by block with a trusted era1 archive
+    Compare {
+        /// folder of the era1 files to compare against
+        #[clap(long)]
+        era1: String,
+        /// folder of the flat files to check
+        #[clap(long)]
+        flat: String,
+    },
+    /// Verify pre-merge flat files against the mainnet header accumulator
+    Verify {
+        /// folder of the flat files to verify, covering whole epochs of 8192 blocks
+        #[clap(short, long)]
+        input: String,
+        /// SSZ encoded pre-merge accumulator, checked against the mainnet accumulator root.
+        /// The embedded mainnet accumulator is used if not set, needs the mainnet-accumulator feature
+        #[clap(long)]
+        accumulator: Option<String>,
+    },
+    /// Verify post-merge flat files against trusted beacon blocks
+    VerifyBeacon {
+        /// folder of the beacon blocks, as JSON or SSZ
+        #[clap(long)]
+        beacon: String,
+        /// folder of the flat files to verify
+        #[clap(long)]
+        flat: String,
+        /// fork of the SSZ encoded beacon blocks: bellatrix, capella, deneb or electra
+        #[clap(long)]
+        fork: Option<BeaconFork>,
+    },
+    /// Check the total difficulty of consecutive blocks, from genesis or a trusted checkpoint
+    CheckTd {
+        /// folder of the flat files to check
+        #[clap(short, long)]
+        input: String,
+        /// number of the checkpoint block, checking starts at the next block. Starts at genesis if not set
+        #[clap(long, requires = "checkpoint_td")]
+        checkpoint_block: Option<u64>,
+        /// total difficulty of the checkpoint block, decimal or 0x prefixed hex
+        #[clap(long, requires = "checkpoint_block")]
+        checkpoint_td: Option<String>,
+    },
+    /// Check flat file headers against trusted headers
+    CheckHeaders {
+        /// folder of the flat files to check
+        #[clap(short, long)]
+        input: String,
+        /// trusted headers: a folder of JSON headers, a .jsonl file of JSON headers, a .csv file
+        /// of number,hash lines, an .era1 file, or a .bin file of header records written by stream
+        #[clap(long)]
+        headers: String,
+    },
+    /// Index flat files into a JSON file, recording the byte range of each block of the
+    /// uncompressed ones so that they can be served from a copy with range reads
+    Index {
+        /// folder of the flat files to index
+        #[clap(short, long)]
+        dir: String,
+        /// JSON file the index is written to
+        #[clap(short, long)]
+        output: String,
+    },
+    /// Serve flat files over JSON-RPC, needs the serve feature
+    #[cfg(feature = "serve")]
+    Serve {
+        /// folder of the flat files to serve, or a store URL like http://<host>/<path> (needs
+        /// the http feature) along with the index of the flat files
+        #[clap(short, long)]
+        dir: String,
+        /// index written by the index command, the flat files are indexed on startup if not set
+        #[clap(long)]
+        index: Option<String>,
+        /// address to listen on
+        #[clap(long, default_value = "127.0.0.1:8545")]
+        addr: std::net::SocketAddr,
+        /// chain id returned by eth_chainId
+        #[clap(long, default_value_t = 1)]
+        chain_id: u64,
+    },
+    /// Serve flat files over the Firehose gRPC API, needs the firehose feature
+    #[cfg(feature = "firehose")]
+    FirehoseServe {
+        /// folder of the flat files to serve, or a store URL like http://<host>/<path> (needs
+        /// the http feature) along with the index of the flat files
+        #[clap(short, long)]
+        dir: String,
+        /// index written by the index command, the flat files are indexed on startup if not set
+        #[clap(long)]
+        index: Option<String>,
+        /// address to listen on
+        #[clap(long, default_value = "127.0.0.1:10015")]
+        addr: std::net::SocketAddr,
     },
 }
 #[tokio::main]
@@ -43,35 +217,354 @@ async fn main() {
 
     match cli.command {
         Commands::Stream {
+            end_block,
+            firehose: Some(endpoint),
+            start_block,
+            filter,
+            ..
+        } => {
+            let stop_block = end_block.unwrap_or(MERGE_BLOCK) as u64;
+            stream_firehose(endpoint, start_block, stop_block, filter).await;
+        }
+        Commands::Stream {
+            compression,
             decompress,
             end_block,
+            max_message_size,
+            firehose: None,
+            filter,
+            ..
         } => {
-            if decompress {
-                let reader =
-                    zstd::stream::Decoder::new(io::stdin()).expect("Failed to create zstd decoder");
-                let writer = BufWriter::new(io::stdout().lock());
-                stream_blocks(reader, writer, end_block)
-                    .await
-                    .expect("Failed to stream blocks");
-            } else {
-                let reader = BufReader::with_capacity((64 * 2) << 20, io::stdin().lock());
-                let writer = BufWriter::new(io::stdout().lock());
-                stream_blocks(reader, writer, end_block)
-                    .await
-                    .expect("Failed to stream blocks");
-            }
+            let mut reader = BufReader::with_capacity((64 * 2) << 20, io::stdin().lock());
+            let compression = match compression.or(decompress.then_some(Compression::Zstd)) {
+                Some(compression) => compression,
+                None => Compression::detect_from(&mut reader)
+                    .expect("Failed to detect stream compression"),
+            };
+            let reader = compression
+                .decoder(reader)
+                .expect("Failed to create decoder");
+            let writer = BufWriter::new(io::stdout().lock());
+            let options = DbinReaderOptions { max_message_size };
+            stream_blocks_with_filter(reader, writer, end_block, options, filter.as_ref())
+                .await
+                .expect("Failed to stream blocks");
         }
         Commands::Decode {
             input,
             headers_dir,
             output,
+            compression,
             decompress,
+            format,
+            blocks_per_file,
+            output_compression,
+            firehose,
+            start_block,
+            stop_block,
+            filter,
+            max_message_size,
         } => {
-            let blocks =
-                decode_flat_files(input, output.as_deref(), headers_dir.as_deref(), decompress)
-                    .expect("Failed to decode files");
+            if filter.is_some() && matches!(format, OutputFormat::Era1 | OutputFormat::Rlp) {
+                Cli::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("--filter can't be used with the {} format, filtered blocks no longer match their header roots", format),
+                    )
+                    .exit();
+            }
+            let compression = compression.or(decompress.map(|decompress| {
+                if decompress {
+                    Compression::Zstd
+                } else {
+                    Compression::None
+                }
+            }));
+            let reader_options = DbinReaderOptions { max_message_size };
+            let options = SinkOptions {
+                compression: output_compression,
+                blocks_per_file,
+            };
+            let mut sink = output
+                .map(|output| {
+                    if is_store_url(&output) {
+                        let (store, prefix) = open_store(&output).expect("Failed to open store");
+                        StoreSink::new(format, store, prefix, options)
+                            .map(|sink| Box::new(sink) as Box<dyn BlockSink>)
+                    } else {
+                        open_sink(format, output, options)
+                    }
+                })
+                .transpose()
+                .expect("Failed to create output")
+                .map(|sink| match filter {
+                    Some(filter) => Box::new(FilterSink::new(sink, filter)) as Box<dyn BlockSink>,
+                    None => sink,
+                });
+            let blocks = match firehose {
+                Some(endpoint) => {
+                    decode_firehose(
+                        endpoint,
+                        start_block,
+                        stop_block.expect("Missing stop block"),
+                        sink.as_deref_mut(),
+                        headers_dir.as_deref(),
+                    )
+                    .await
+                }
+                None => {
+                    let input = input.expect("Missing input");
+                    let blocks = if is_store_url(&input) {
+                        let (store, prefix) = open_store(&input).expect("Failed to open store");
+                        decode_store(
+                            store.as_ref(),
+                            &prefix,
+                            sink.as_deref_mut(),
+                            headers_dir.as_deref(),
+                            compression,
+                            reader_options,
+                        )
+                    } else {
+                        decode_flat_files_with_sink(
+                            input,
+                            sink.as_deref_mut(),
+                            headers_dir.as_deref(),
+                            compression,
+                            reader_options,
+                        )
+                    };
+                    blocks.expect("Failed to decode files")
+                }
+            };
 
             println!("Total blocks: {}", blocks.len());
         }
+        Commands::Compare { era1, flat } => {
+            let report =
+                compare_dirs(Path::new(&era1), Path::new(&flat)).expect("Failed to compare files");
+
+            for (path, err) in &report.failed_files {
+                println!("File {}: {}", path.display(), err);
+            }
+            for (block_number, mismatch) in &report.mismatches {
+                println!("Block {}: {}", block_number, mismatch);
+            }
+            println!(
+                "Compared blocks: {}, mismatches: {}, failed files: {}",
+                report.blocks,
+                report.mismatches.len(),
+                report.failed_files.len()
+            );
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Verify { input, accumulator } => {
+            let accumulator = match accumulator {
+                Some(accumulator) => PreMergeAccumulator::load(Path::new(&accumulator)),
+                None => mainnet_accumulator(),
+            }
+            .expect("Failed to load accumulator");
+            let epochs =
+                verify_dir(Path::new(&input), &accumulator).expect("Failed to verify files");
+
+            println!("Verified epochs: {:?}", epochs);
+        }
+        Commands::VerifyBeacon { beacon, flat, fork } => {
+            let report = beacon::verify_dir(Path::new(&beacon), Path::new(&flat), fork)
+                .expect("Failed to verify files");
+
+            for (block_number, err) in &report.failures {
+                println!("Block {}: {}", block_number, err);
+            }
+            for block_number in &report.missing {
+                println!("Block {}: missing beacon block", block_number);
+            }
+            println!(
+                "Verified blocks: {}, failures: {}, missing: {}",
+                report.blocks,
+                report.failures.len(),
+                report.missing.len()
+            );
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::CheckTd {
+            input,
+            checkpoint_block,
+            checkpoint_td,
+        } => {
+            let checker = match (checkpoint_block, checkpoint_td) {
+                (Some(block_number), Some(total_difficulty)) => {
+                    let total_difficulty = U256::from_str(&total_difficulty)
+                        .expect("Invalid checkpoint total difficulty");
+                    TotalDifficultyChecker::from_checkpoint(block_number, total_difficulty)
+                }
+                _ => TotalDifficultyChecker::genesis(),
+            };
+            let report = check_total_difficulty_dir(Path::new(&input), checker)
+                .expect("Failed to check files");
+
+            for (block_number, err) in &report.mismatches {
+                println!("Block {}: {}", block_number, err);
+            }
+            println!(
+                "Checked blocks: {}, mismatches: {}",
+                report.blocks,
+                report.mismatches.len()
+            );
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::CheckHeaders { input, headers } => {
+            let mut source =
+                open_header_source(Path::new(&headers)).expect("Failed to open headers");
+            let report = check_headers_dir(Path::new(&input), source.as_mut())
+                .expect("Failed to check files");
+
+            for (path, err) in &report.failed_files {
+                println!("File {}: {}", path.display(), err);
+            }
+            for (block_number, err) in &report.failures {
+                println!("Block {}: {}", block_number, err);
+            }
+            println!(
+                "Checked blocks: {}, failures: {}, failed files: {}",
+                report.blocks,
+                report.failures.len(),
+                report.failed_files.len()
+            );
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Index { dir, output } => {
+            let index = BlockIndex::build(Path::new(&dir)).expect("Failed to index files");
+            index
+                .save(Path::new(&output))
+                .expect("Failed to save index");
+            println!("Indexed blocks: {}", index.len());
+        }
+        #[cfg(feature = "serve")]
+        Commands::Serve {
+            dir,
+            index,
+            addr,
+            chain_id,
+        } => {
+            let reader = open_blocks(&dir, index);
+            server::serve(RpcServer::with_lookup(reader, chain_id), addr)
+                .await
+                .expect("Failed to serve");
+        }
+        #[cfg(feature = "firehose")]
+        Commands::FirehoseServe { dir, index, addr } => {
+            let reader = open_blocks(&dir, index);
+            firehose::server::serve(FirehoseServer::with_lookup(reader), addr)
+                .await
+                .expect("Failed to serve");
+        }
+    }
+}
+
+/// Streams the header records of blocks read from Firehose to stdout
+#[cfg(feature = "firehose")]
+async fn stream_firehose(
+    endpoint: String,
+    start_block: i64,
+    stop_block: u64,
+    filter: Option<BlockFilter>,
+) {
+    let mut client = FirehoseClient::connect(endpoint)
+        .await
+        .expect("Failed to connect to Firehose");
+    let writer = BufWriter::new(io::stdout().lock());
+    firehose::client::stream_blocks(
+        &mut client,
+        start_block,
+        stop_block,
+        writer,
+        filter.as_ref(),
+    )
+    .await
+    .expect("Failed to stream blocks");
+}
+
+/// Decodes blocks read from Firehose, writing them to `sink`
+#[cfg(feature = "firehose")]
+async fn decode_firehose(
+    endpoint: String,
+    start_block: i64,
+    stop_block: u64,
+    sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+) -> Vec<Block> {
+    let mut client = FirehoseClient::connect(endpoint)
+        .await
+        .expect("Failed to connect to Firehose");
+    firehose::client::decode_blocks(&mut client, start_block, stop_block, sink, headers_dir)
+        .await
+        .expect("Failed to decode blocks")
+}
+
+#[cfg(not(feature = "firehose"))]
+async fn stream_firehose(
+    _endpoint: String,
+    _start_block: i64,
+    _stop_block: u64,
+    _filter: Option<BlockFilter>,
+) {
+    firehose_unsupported()
+}
+
+#[cfg(not(feature = "firehose"))]
+async fn decode_firehose(
+    _endpoint: String,
+    _start_block: i64,
+    _stop_block: u64,
+    _sink: Option<&mut (dyn BlockSink + '_)>,
+    _headers_dir: Option<&str>,
+) -> Vec<Block> {
+    firehose_unsupported()
+}
+
+#[cfg(feature = "mainnet-accumulator")]
+fn mainnet_accumulator() -> Result<PreMergeAccumulator, AccumulatorError> {
+    PreMergeAccumulator::mainnet()
+}
+
+#[cfg(not(feature = "mainnet-accumulator"))]
+fn mainnet_accumulator() -> Result<PreMergeAccumulator, AccumulatorError> {
+    eprintln!("No embedded accumulator, pass an --accumulator or enable the mainnet-accumulator cargo feature");
+    std::process::exit(1)
+}
+
+/// Opens the flat files to serve, from the folder or store URL `dir`. An index is needed to
+/// serve from a store, local folders are indexed on startup without one
+#[cfg(any(feature = "serve", feature = "firehose"))]
+fn open_blocks(dir: &str, index: Option<String>) -> Box<dyn BlockLookup> {
+    let index = match index {
+        Some(index) => BlockIndex::load(Path::new(&index)).expect("Failed to load index"),
+        None if is_store_url(dir) => {
+            eprintln!("Serving flat files from a store URL needs an --index");
+            std::process::exit(1)
+        }
+        None => BlockIndex::build(Path::new(dir)).expect("Failed to index files"),
+    };
+    println!("Indexed blocks: {}", index.len());
+
+    if is_store_url(dir) {
+        let (store, prefix) = open_store(dir).expect("Failed to open store");
+        Box::new(RemoteBlockStore::new(store, prefix, index))
+    } else {
+        Box::new(BlockReader::new(index))
     }
 }
+
+#[cfg(not(feature = "firehose"))]
+fn firehose_unsupported() -> ! {
+    eprintln!("Firehose input is not supported, enable the firehose cargo feature");
+    std::process::exit(1)
+}
@@ -0,0 +1,46 @@
+use crate::compression::error::CompressionError;
+use crate::convert::error::ConvertError;
+use crate::era1::error::Era1Error;
+use crate::output::OutputFormat;
+use crate::receipts::error::ReceiptError;
+use crate::rlp::error::RlpError;
+use crate::rpc::error::RpcError;
+use crate::store::error::StoreError;
+use crate::transactions::error::TransactionError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OutputError {
+    #[error("Write error: {0}")]
+    WriteError(#[from] std::io::Error),
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Compression Error: {0}")]
+    CompressionError(#[from] CompressionError),
+    #[error("RPC conversion error: {0}")]
+    RpcError(#[from] RpcError),
+    #[error("Invalid transaction: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Invalid receipt: {0}")]
+    ReceiptError(#[from] ReceiptError),
+    #[error("Missing header")]
+    MissingHeader,
+    #[error("Invalid field: {0}")]
+    InvalidField(#[from] ConvertError),
+    #[error("Era1 Error: {0}")]
+    Era1Error(#[from] Era1Error),
+    #[error("RLP encoding error: {0}")]
+    RlpError(#[from] RlpError),
+    #[error("Store error: {0}")]
+    StoreError(#[from] StoreError),
+    #[cfg(feature = "parquet")]
+    #[error("Arrow Error: {0}")]
+    ArrowError(#[from] arrow::error::ArrowError),
+    #[cfg(feature = "parquet")]
+    #[error("Parquet Error: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+    #[error("Unknown output format: {0}")]
+    UnknownFormat(String),
+    #[error("Output format {0} is not supported, enable its cargo feature")]
+    Unsupported(OutputFormat),
+}
@@ -0,0 +1,556 @@
+pub mod error;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+use crate::compression::{Compression, Encoder};
+use crate::era1::Era1Sink;
+use crate::output::error::OutputError;
+use crate::rlp::RlpBlock;
+use crate::rpc::{block_receipts_to_rpc, block_to_rpc};
+use crate::store::{LocalStore, ObjectStore};
+use sf_protos::ethereum::r#type::v2::Block;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// `BlockSink` is where decoded blocks are written to.
+pub trait BlockSink {
+    /// Writes a single block, blocks are written in the order they are decoded
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError>;
+
+    /// Flushes and closes any open output, must be called once all blocks are written
+    fn finish(&mut self) -> Result<(), OutputError>;
+}
+
+impl<S: BlockSink + ?Sized> BlockSink for Box<S> {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        (**self).write_block(block)
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        (**self).finish()
+    }
+}
+
+/// `OutputFormat` selects the [`BlockSink`] decoded blocks are written with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One `block-<number>.json` file per block, see [`JsonFileSink`]
+    #[default]
+    Json,
+    /// One block per line in `blocks-<first block number>.jsonl` files, see [`JsonLinesSink`]
+    Jsonl,
+    /// JSON-RPC shaped `block-<number>.json` and `receipts-<number>.json` files, see [`RpcFileSink`]
+    Rpc,
+    /// `blocks`, `transactions`, `logs` and `traces` Parquet datasets, see `ParquetSink`.
+    /// Only available with the `parquet` feature
+    Parquet,
+    /// Pre-merge blocks in era1 archives of 8192 blocks, see [`Era1Sink`]
+    Era1,
+    /// Canonical RLP encoded blocks and receipts in `blocks-<first block number>.rlp` and
+    /// `receipts-<first block number>.rlp` files, see [`RlpSink`]
+    Rlp,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Rpc => "rpc",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Era1 => "era1",
+            OutputFormat::Rlp => "rlp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = OutputError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "rpc" => Ok(OutputFormat::Rpc),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "era1" => Ok(OutputFormat::Era1),
+            "rlp" => Ok(OutputFormat::Rlp),
+            _ => Err(OutputError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// `SinkOptions` are the options shared by the sinks created with [`open_sink`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SinkOptions {
+    /// Compression of the output files, only used by [`OutputFormat::Jsonl`] and [`OutputFormat::Rlp`]
+    pub compression: Compression,
+    /// Number of blocks per output file, ignored by [`OutputFormat::Json`] and [`OutputFormat::Rpc`].
+    /// `None` writes a single JSON lines file, or the default partition size of Parquet datasets.
+    /// `0` is taken as `1`
+    pub blocks_per_file: Option<usize>,
+}
+
+/// Creates the [`BlockSink`] writing blocks in `format` to the directory `dir`.
+pub fn open_sink(
+    format: OutputFormat,
+    dir: impl Into<PathBuf>,
+    options: SinkOptions,
+) -> Result<Box<dyn BlockSink>, OutputError> {
+    match format {
+        OutputFormat::Json => Ok(Box::new(JsonFileSink::new(dir)?)),
+        OutputFormat::Jsonl => Ok(Box::new(JsonLinesSink::new(
+            dir,
+            options.compression,
+            options.blocks_per_file,
+        )?)),
+        OutputFormat::Rpc => Ok(Box::new(RpcFileSink::new(dir)?)),
+        OutputFormat::Era1 => Ok(Box::new(Era1Sink::new(dir)?)),
+        OutputFormat::Rlp => Ok(Box::new(RlpSink::new(
+            dir,
+            options.compression,
+            options.blocks_per_file,
+        )?)),
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => Ok(Box::new(parquet::ParquetSink::new(
+            dir,
+            options.blocks_per_file,
+        )?)),
+        #[allow(unreachable_patterns)]
+        unsupported => Err(OutputError::Unsupported(unsupported)),
+    }
+}
+
+/// Writes each block to its own `block-<number>.json` file in a directory.
+pub struct JsonFileSink {
+    dir: PathBuf,
+}
+
+impl JsonFileSink {
+    /// Creates the sink, and `dir` if it doesn't exist yet
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, OutputError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl BlockSink for JsonFileSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        let file_name = self.dir.join(format!("block-{}.json", block.number));
+        let mut out_file = BufWriter::new(File::create(file_name)?);
+
+        serde_json::to_writer(&mut out_file, block)?;
+        out_file.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// Writes each block as returned by `eth_getBlockByNumber` with full transactions to
+/// `block-<number>.json`, and its receipts as returned by `eth_getBlockReceipts` to
+/// `receipts-<number>.json`. See [`crate::rpc`].
+pub struct RpcFileSink {
+    dir: PathBuf,
+}
+
+impl RpcFileSink {
+    /// Creates the sink, and `dir` if it doesn't exist yet
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, OutputError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl BlockSink for RpcFileSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        let rpc_block = block_to_rpc(block, true)?;
+        let file_name = self.dir.join(format!("block-{}.json", block.number));
+        let mut out_file = BufWriter::new(File::create(file_name)?);
+        serde_json::to_writer(&mut out_file, &rpc_block)?;
+        out_file.flush()?;
+
+        let receipts = block_receipts_to_rpc(block)?;
+        let file_name = self.dir.join(format!("receipts-{}.json", block.number));
+        let mut out_file = BufWriter::new(File::create(file_name)?);
+        serde_json::to_writer(&mut out_file, &receipts)?;
+        out_file.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// Writes blocks as [JSON lines](https://jsonlines.org), one block per line.
+///
+/// Files are named after the number of their first block, `blocks-<number>.jsonl`, followed by the
+/// extension of the compression if any (e.g. `blocks-0017686312.jsonl.zst`). A new file is started
+/// every `blocks_per_file` blocks, or never if it is `None`.
+pub struct JsonLinesSink {
+    blocks: RollingFile,
+}
+
+impl JsonLinesSink {
+    /// Creates the sink, and `dir` if it doesn't exist yet
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        compression: Compression,
+        blocks_per_file: Option<usize>,
+    ) -> Result<Self, OutputError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            blocks: RollingFile::new(dir, "blocks", "jsonl", compression, blocks_per_file),
+        })
+    }
+}
+
+impl BlockSink for JsonLinesSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        let writer = self.blocks.writer(block.number)?;
+        serde_json::to_writer(&mut *writer, block)?;
+        writer.write_all(b"\n")?;
+        self.blocks.block_written()
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        self.blocks.close()
+    }
+}
+
+/// Writes blocks with their canonical RLP encodings, see [`RlpBlock`].
+///
+/// Blocks, `[header, transactions, uncles]`, are concatenated in `blocks-<number>.rlp` files
+/// as imported by `geth import` and `reth import`, and the RLP list of receipts of each block
+/// in `receipts-<number>.rlp`. Files are named, compressed and rolled as with [`JsonLinesSink`].
+///
+/// Only blocks before the Shanghai fork can be written, as flat files don't hold withdrawals.
+pub struct RlpSink {
+    blocks: RollingFile,
+    receipts: RollingFile,
+}
+
+impl RlpSink {
+    /// Creates the sink, and `dir` if it doesn't exist yet
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        compression: Compression,
+        blocks_per_file: Option<usize>,
+    ) -> Result<Self, OutputError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            blocks: RollingFile::new(dir.clone(), "blocks", "rlp", compression, blocks_per_file),
+            receipts: RollingFile::new(dir, "receipts", "rlp", compression, blocks_per_file),
+        })
+    }
+}
+
+impl BlockSink for RlpSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        let rlp_block = RlpBlock::try_from(block)?;
+
+        self.blocks
+            .writer(block.number)?
+            .write_all(&rlp_block.block_rlp())?;
+        self.blocks.block_written()?;
+
+        self.receipts
+            .writer(block.number)?
+            .write_all(rlp_block.receipts_rlp())?;
+        self.receipts.block_written()
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        // Both files are closed even if the first fails, so none is left unfinished
+        let blocks = self.blocks.close();
+        let receipts = self.receipts.close();
+        blocks.and(receipts)
+    }
+}
+
+/// Writes blocks in any [`OutputFormat`] to an [`ObjectStore`].
+///
+/// The sink of the format writes to a local staging folder, whose files are uploaded under
+/// `prefix` once all blocks are written, and the staging folder removed.
+pub struct StoreSink {
+    inner: Box<dyn BlockSink>,
+    staging: PathBuf,
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+}
+
+impl StoreSink {
+    pub fn new(
+        format: OutputFormat,
+        store: Box<dyn ObjectStore>,
+        prefix: impl Into<String>,
+        options: SinkOptions,
+    ) -> Result<Self, OutputError> {
+        static STAGING_DIRS: AtomicUsize = AtomicUsize::new(0);
+        let staging = std::env::temp_dir().join(format!(
+            "decoder-staging-{}-{}",
+            std::process::id(),
+            STAGING_DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        Ok(Self {
+            inner: open_sink(format, &staging, options)?,
+            staging,
+            store,
+            prefix: prefix.into().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// The store blocks are written to
+    pub fn store(&self) -> &dyn ObjectStore {
+        self.store.as_ref()
+    }
+}
+
+impl BlockSink for StoreSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        self.inner.write_block(block)
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        self.inner.finish()?;
+
+        let staging = LocalStore::new(&self.staging);
+        for key in staging.list("")? {
+            let object = if self.prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}/{}", self.prefix, key)
+            };
+            self.store.put(&object, staging.get(&key)?)?;
+        }
+        fs::remove_dir_all(&self.staging)?;
+        Ok(())
+    }
+}
+
+impl Drop for StoreSink {
+    fn drop(&mut self) {
+        // the staging folder is left behind if the sink isn't finished
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+/// An output file holding a range of blocks, named `<prefix>-<first block number>.<extension>`
+/// and started again every `blocks_per_file` blocks, at least one.
+struct RollingFile {
+    dir: PathBuf,
+    prefix: &'static str,
+    extension: &'static str,
+    compression: Compression,
+    blocks_per_file: Option<usize>,
+    writer: Option<Encoder<BufWriter<File>>>,
+    blocks_in_file: usize,
+}
+
+impl RollingFile {
+    fn new(
+        dir: PathBuf,
+        prefix: &'static str,
+        extension: &'static str,
+        compression: Compression,
+        blocks_per_file: Option<usize>,
+    ) -> Self {
+        Self {
+            dir,
+            prefix,
+            extension,
+            compression,
+            blocks_per_file: blocks_per_file.map(|blocks| blocks.max(1)),
+            writer: None,
+            blocks_in_file: 0,
+        }
+    }
+
+    /// The writer of the current file, opening a new one starting at `block_number` if needed
+    fn writer(&mut self, block_number: u64) -> Result<&mut Encoder<BufWriter<File>>, OutputError> {
+        let writer = match self.writer {
+            Some(ref mut writer) => writer,
+            None => {
+                let writer = self.open(block_number)?;
+                self.writer.insert(writer)
+            }
+        };
+        Ok(writer)
+    }
+
+    fn open(&self, first_block: u64) -> Result<Encoder<BufWriter<File>>, OutputError> {
+        let mut file_name = format!("{}-{:010}.{}", self.prefix, first_block, self.extension);
+        if let Some(extension) = self.compression.extension() {
+            file_name = format!("{}.{}", file_name, extension);
+        }
+        let file = BufWriter::new(File::create(self.dir.join(file_name))?);
+        Ok(self.compression.encoder(file)?)
+    }
+
+    /// Counts a block written to the current file, closing it once it holds `blocks_per_file` blocks
+    fn block_written(&mut self) -> Result<(), OutputError> {
+        self.blocks_in_file += 1;
+        if self.blocks_per_file == Some(self.blocks_in_file) {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the compressed stream of the current file and flushes it
+    fn close(&mut self) -> Result<(), OutputError> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        self.blocks_in_file = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockSink, JsonLinesSink};
+    use crate::compression::Compression;
+    use crate::handle_file;
+    use sf_protos::ethereum::r#type::v2::Block;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::path::PathBuf;
+
+    /// Writes the first 100 mainnet blocks to a single JSON lines file compressed with
+    /// `compression`, and checks they decode back once the sink is finished
+    fn check_jsonl_round_trip(compression: Compression) {
+        let dir = std::env::temp_dir().join(format!("decoder-test-jsonl-{}", compression));
+        let _ = fs::remove_dir_all(&dir);
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        let mut sink = JsonLinesSink::new(&dir, compression, None).unwrap();
+        for block in &blocks {
+            sink.write_block(block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let mut file_name = "blocks-0000000000.jsonl".to_string();
+        if let Some(extension) = compression.extension() {
+            file_name = format!("{}.{}", file_name, extension);
+        }
+        let file = fs::File::open(dir.join(file_name)).unwrap();
+        let reader = BufReader::new(compression.decoder(BufReader::new(file)).unwrap());
+        let lines: Vec<serde_json::Value> = reader
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        let expected: Vec<serde_json::Value> = blocks
+            .iter()
+            .map(|block| serde_json::to_value(block).unwrap())
+            .collect();
+        assert_eq!(lines, expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        check_jsonl_round_trip(Compression::None);
+        check_jsonl_round_trip(Compression::Zstd);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_jsonl_gzip_round_trip() {
+        check_jsonl_round_trip(Compression::Gzip);
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_jsonl_xz_round_trip() {
+        check_jsonl_round_trip(Compression::Xz);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_jsonl_lz4_round_trip() {
+        check_jsonl_round_trip(Compression::Lz4);
+    }
+
+    #[test]
+    fn test_jsonl_rolling() {
+        let dir = std::env::temp_dir().join("decoder-test-jsonl-rolling");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut sink = JsonLinesSink::new(&dir, Compression::Zstd, Some(2)).unwrap();
+        for number in 10..15 {
+            let block = Block {
+                number,
+                ..Default::default()
+            };
+            sink.write_block(&block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        let mut files: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "blocks-0000000010.jsonl.zst",
+                "blocks-0000000012.jsonl.zst",
+                "blocks-0000000014.jsonl.zst"
+            ]
+        );
+
+        let file = fs::File::open(dir.join("blocks-0000000012.jsonl.zst")).unwrap();
+        let reader = BufReader::new(Compression::Zstd.decoder(BufReader::new(file)).unwrap());
+        let numbers: Vec<u64> = reader
+            .lines()
+            .map(|line| {
+                let block: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                block["number"].as_u64().unwrap()
+            })
+            .collect();
+        assert_eq!(numbers, vec![12, 13]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl_rolling_zero() {
+        let dir = std::env::temp_dir().join("decoder-test-jsonl-rolling-zero");
+        let _ = fs::remove_dir_all(&dir);
+
+        // a single block per file, as parquet partitions do
+        let mut sink = JsonLinesSink::new(&dir, Compression::None, Some(0)).unwrap();
+        for number in 10..13 {
+            let block = Block {
+                number,
+                ..Default::default()
+            };
+            sink.write_block(&block).unwrap();
+        }
+        sink.finish().unwrap();
+
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,618 @@
+use crate::convert::timestamp;
+use crate::output::error::OutputError;
+use crate::output::BlockSink;
+use crate::receipts::logs::map_logs;
+use crate::receipts::receipt::FullReceipt;
+use crate::rpc::effective_gas_price;
+use crate::transactions::{bigint_to_u128, trace_to_signed};
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, FixedSizeBinaryArray, StringArray, UInt32Array,
+    UInt64Array, UInt8Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression as ParquetCompression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use reth_primitives::{Address, B256};
+use sf_protos::ethereum::r#type::v2::{Block, TransactionTrace};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Number of blocks per Parquet file when no partition size is given.
+pub const DEFAULT_BLOCKS_PER_PARTITION: usize = 1000;
+
+/// Writes blocks as four [Apache Parquet](https://parquet.apache.org) datasets, `blocks`,
+/// `transactions`, `logs` and `traces`, each in its own sub-directory of `dir`.
+///
+/// Datasets are partitioned by block range: blocks are grouped in ranges of `blocks_per_file`
+/// blocks aligned on multiples of it, and each range is written to
+/// `<dataset>/<dataset>-<first block>-<last block>.parquet` once complete.
+/// Hashes and addresses are fixed size binary columns, wei amounts are decimal strings.
+pub struct ParquetSink {
+    dir: PathBuf,
+    blocks_per_file: u64,
+    partition: Option<Partition>,
+}
+
+impl ParquetSink {
+    /// Creates the sink, and `dir` if it doesn't exist yet
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: the directory the datasets are written to.
+    /// * `blocks_per_file`: size of the block ranges, [`DEFAULT_BLOCKS_PER_PARTITION`] if `None`.
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        blocks_per_file: Option<usize>,
+    ) -> Result<Self, OutputError> {
+        let dir = dir.into();
+        for dataset in Dataset::ALL {
+            fs::create_dir_all(dir.join(dataset.name()))?;
+        }
+        Ok(Self {
+            dir,
+            blocks_per_file: blocks_per_file
+                .unwrap_or(DEFAULT_BLOCKS_PER_PARTITION)
+                .max(1) as u64,
+            partition: None,
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), OutputError> {
+        let partition = match self.partition.take() {
+            Some(partition) => partition,
+            None => return Ok(()),
+        };
+
+        for dataset in Dataset::ALL {
+            let batch = partition.record_batch(dataset)?;
+            let file_name = format!(
+                "{}-{:010}-{:010}.parquet",
+                dataset.name(),
+                partition.first_block,
+                partition.last_block
+            );
+            let file = File::create(self.dir.join(dataset.name()).join(file_name))?;
+            let properties = WriterProperties::builder()
+                .set_compression(ParquetCompression::ZSTD(ZstdLevel::default()))
+                .build();
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+            writer.write(&batch)?;
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+impl BlockSink for ParquetSink {
+    fn write_block(&mut self, block: &Block) -> Result<(), OutputError> {
+        let key = block.number / self.blocks_per_file;
+        if self.partition.as_ref().map(|partition| partition.key) != Some(key) {
+            self.flush()?;
+        }
+
+        self.partition
+            .get_or_insert_with(|| Partition::new(key, block.number))
+            .push(block)
+    }
+
+    fn finish(&mut self) -> Result<(), OutputError> {
+        self.flush()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Dataset {
+    Blocks,
+    Transactions,
+    Logs,
+    Traces,
+}
+
+impl Dataset {
+    const ALL: [Dataset; 4] = [
+        Dataset::Blocks,
+        Dataset::Transactions,
+        Dataset::Logs,
+        Dataset::Traces,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Dataset::Blocks => "blocks",
+            Dataset::Transactions => "transactions",
+            Dataset::Logs => "logs",
+            Dataset::Traces => "traces",
+        }
+    }
+}
+
+struct BlockRow {
+    number: u64,
+    hash: Vec<u8>,
+    parent_hash: Vec<u8>,
+    timestamp: u64,
+    miner: Vec<u8>,
+    state_root: Vec<u8>,
+    transactions_root: Vec<u8>,
+    receipts_root: Vec<u8>,
+    gas_limit: u64,
+    gas_used: u64,
+    base_fee_per_gas: Option<String>,
+    difficulty: String,
+    size: u64,
+    transaction_count: u32,
+}
+
+struct TransactionRow {
+    block_number: u64,
+    transaction_index: u32,
+    hash: B256,
+    from: Vec<u8>,
+    to: Option<Address>,
+    nonce: u64,
+    value: String,
+    gas_limit: u64,
+    gas_price: String,
+    max_fee_per_gas: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+    input: Vec<u8>,
+    tx_type: u8,
+    gas_used: u64,
+    cumulative_gas_used: u64,
+    success: bool,
+}
+
+struct LogRow {
+    block_number: u64,
+    transaction_index: u32,
+    transaction_hash: B256,
+    log_index: u32,
+    address: Address,
+    topics: Vec<B256>,
+    data: Vec<u8>,
+}
+
+struct TraceRow {
+    block_number: u64,
+    transaction_index: u32,
+    transaction_hash: B256,
+    call_index: u32,
+    parent_index: u32,
+    depth: u32,
+    call_type: &'static str,
+    from: Vec<u8>,
+    to: Vec<u8>,
+    value: Option<String>,
+    gas_limit: u64,
+    gas_used: u64,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    failed: bool,
+    reverted: bool,
+    failure_reason: String,
+}
+
+/// The rows of the blocks of one block range
+struct Partition {
+    key: u64,
+    first_block: u64,
+    last_block: u64,
+    blocks: Vec<BlockRow>,
+    transactions: Vec<TransactionRow>,
+    logs: Vec<LogRow>,
+    traces: Vec<TraceRow>,
+}
+
+impl Partition {
+    fn new(key: u64, first_block: u64) -> Self {
+        Self {
+            key,
+            first_block,
+            last_block: first_block,
+            blocks: Vec::new(),
+            transactions: Vec::new(),
+            logs: Vec::new(),
+            traces: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, block: &Block) -> Result<(), OutputError> {
+        let header = block.header.as_ref().ok_or(OutputError::MissingHeader)?;
+        let base_fee_per_gas = header
+            .base_fee_per_gas
+            .clone()
+            .map(bigint_to_u128)
+            .transpose()?;
+        let difficulty = match header.difficulty.clone() {
+            Some(difficulty) => bigint_to_u128(difficulty)?,
+            None => 0,
+        };
+
+        self.blocks.push(BlockRow {
+            number: block.number,
+            hash: block.hash.clone(),
+            parent_hash: header.parent_hash.clone(),
+            timestamp: timestamp(header)?,
+            miner: header.coinbase.clone(),
+            state_root: header.state_root.clone(),
+            transactions_root: header.transactions_root.clone(),
+            receipts_root: header.receipt_root.clone(),
+            gas_limit: header.gas_limit,
+            gas_used: header.gas_used,
+            base_fee_per_gas: base_fee_per_gas.map(|base_fee| base_fee.to_string()),
+            difficulty: difficulty.to_string(),
+            size: block.size,
+            transaction_count: block.transaction_traces.len() as u32,
+        });
+
+        for trace in &block.transaction_traces {
+            self.push_transaction(block.number, base_fee_per_gas, trace)?;
+        }
+
+        self.first_block = self.first_block.min(block.number);
+        self.last_block = self.last_block.max(block.number);
+        Ok(())
+    }
+
+    fn push_transaction(
+        &mut self,
+        block_number: u64,
+        base_fee_per_gas: Option<u128>,
+        trace: &TransactionTrace,
+    ) -> Result<(), OutputError> {
+        let signed = trace_to_signed(trace)?;
+        let transaction = &signed.transaction;
+        let receipt = FullReceipt::try_from(trace)?;
+        let max_priority_fee_per_gas = transaction.max_priority_fee_per_gas();
+
+        self.transactions.push(TransactionRow {
+            block_number,
+            transaction_index: trace.index,
+            hash: signed.hash,
+            from: trace.from.clone(),
+            to: transaction.to(),
+            nonce: transaction.nonce(),
+            value: transaction.value().to_string(),
+            gas_limit: transaction.gas_limit(),
+            gas_price: effective_gas_price(transaction, base_fee_per_gas).to_string(),
+            max_fee_per_gas: max_priority_fee_per_gas
+                .map(|_| transaction.max_fee_per_gas().to_string()),
+            max_priority_fee_per_gas: max_priority_fee_per_gas.map(|fee| fee.to_string()),
+            input: transaction.input().to_vec(),
+            tx_type: u8::from(transaction.tx_type()),
+            gas_used: trace.gas_used,
+            cumulative_gas_used: receipt.receipt.receipt.cumulative_gas_used,
+            success: receipt.receipt.receipt.success,
+        });
+
+        if let Some(trace_receipt) = &trace.receipt {
+            let logs = map_logs(&trace_receipt.logs)?;
+            for (log, block_log) in logs.into_iter().zip(&trace_receipt.logs) {
+                self.logs.push(LogRow {
+                    block_number,
+                    transaction_index: trace.index,
+                    transaction_hash: signed.hash,
+                    log_index: block_log.block_index,
+                    address: log.address,
+                    topics: log.data.topics().to_vec(),
+                    data: log.data.data.to_vec(),
+                });
+            }
+        }
+
+        for call in &trace.calls {
+            self.traces.push(TraceRow {
+                block_number,
+                transaction_index: trace.index,
+                transaction_hash: signed.hash,
+                call_index: call.index,
+                parent_index: call.parent_index,
+                depth: call.depth,
+                call_type: call.call_type().as_str_name(),
+                from: call.caller.clone(),
+                to: call.address.clone(),
+                value: call
+                    .value
+                    .clone()
+                    .map(bigint_to_u128)
+                    .transpose()?
+                    .map(|value| value.to_string()),
+                gas_limit: call.gas_limit,
+                gas_used: call.gas_consumed,
+                input: call.input.clone(),
+                output: call.return_data.clone(),
+                failed: call.status_failed,
+                reverted: call.status_reverted,
+                failure_reason: call.failure_reason.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn record_batch(&self, dataset: Dataset) -> Result<RecordBatch, ArrowError> {
+        match dataset {
+            Dataset::Blocks => self.blocks_batch(),
+            Dataset::Transactions => self.transactions_batch(),
+            Dataset::Logs => self.logs_batch(),
+            Dataset::Traces => self.traces_batch(),
+        }
+    }
+
+    fn blocks_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let rows = &self.blocks;
+        let schema = schema(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("block_hash", DataType::FixedSizeBinary(32), false),
+            Field::new("parent_hash", DataType::FixedSizeBinary(32), false),
+            Field::new("timestamp", DataType::UInt64, false),
+            Field::new("miner", DataType::FixedSizeBinary(20), false),
+            Field::new("state_root", DataType::FixedSizeBinary(32), false),
+            Field::new("transactions_root", DataType::FixedSizeBinary(32), false),
+            Field::new("receipts_root", DataType::FixedSizeBinary(32), false),
+            Field::new("gas_limit", DataType::UInt64, false),
+            Field::new("gas_used", DataType::UInt64, false),
+            Field::new("base_fee_per_gas", DataType::Utf8, true),
+            Field::new("difficulty", DataType::Utf8, false),
+            Field::new("size", DataType::UInt64, false),
+            Field::new("transaction_count", DataType::UInt32, false),
+        ]);
+        RecordBatch::try_new(
+            schema,
+            vec![
+                uint64(rows.iter().map(|row| row.number)),
+                fixed_binary(32, rows.iter().map(|row| Some(&row.hash)))?,
+                fixed_binary(32, rows.iter().map(|row| Some(&row.parent_hash)))?,
+                uint64(rows.iter().map(|row| row.timestamp)),
+                fixed_binary(20, rows.iter().map(|row| Some(&row.miner)))?,
+                fixed_binary(32, rows.iter().map(|row| Some(&row.state_root)))?,
+                fixed_binary(32, rows.iter().map(|row| Some(&row.transactions_root)))?,
+                fixed_binary(32, rows.iter().map(|row| Some(&row.receipts_root)))?,
+                uint64(rows.iter().map(|row| row.gas_limit)),
+                uint64(rows.iter().map(|row| row.gas_used)),
+                string(rows.iter().map(|row| row.base_fee_per_gas.as_deref())),
+                string(rows.iter().map(|row| Some(row.difficulty.as_str()))),
+                uint64(rows.iter().map(|row| row.size)),
+                uint32(rows.iter().map(|row| row.transaction_count)),
+            ],
+        )
+    }
+
+    fn transactions_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let rows = &self.transactions;
+        let schema = schema(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("transaction_hash", DataType::FixedSizeBinary(32), false),
+            Field::new("from", DataType::FixedSizeBinary(20), false),
+            Field::new("to", DataType::FixedSizeBinary(20), true),
+            Field::new("nonce", DataType::UInt64, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new("gas_limit", DataType::UInt64, false),
+            Field::new("gas_price", DataType::Utf8, false),
+            Field::new("max_fee_per_gas", DataType::Utf8, true),
+            Field::new("max_priority_fee_per_gas", DataType::Utf8, true),
+            Field::new("input", DataType::Binary, false),
+            Field::new("type", DataType::UInt8, false),
+            Field::new("gas_used", DataType::UInt64, false),
+            Field::new("cumulative_gas_used", DataType::UInt64, false),
+            Field::new("success", DataType::Boolean, false),
+        ]);
+        RecordBatch::try_new(
+            schema,
+            vec![
+                uint64(rows.iter().map(|row| row.block_number)),
+                uint32(rows.iter().map(|row| row.transaction_index)),
+                fixed_binary(32, rows.iter().map(|row| Some(row.hash)))?,
+                fixed_binary(20, rows.iter().map(|row| Some(&row.from)))?,
+                fixed_binary(20, rows.iter().map(|row| row.to))?,
+                uint64(rows.iter().map(|row| row.nonce)),
+                string(rows.iter().map(|row| Some(row.value.as_str()))),
+                uint64(rows.iter().map(|row| row.gas_limit)),
+                string(rows.iter().map(|row| Some(row.gas_price.as_str()))),
+                string(rows.iter().map(|row| row.max_fee_per_gas.as_deref())),
+                string(
+                    rows.iter()
+                        .map(|row| row.max_priority_fee_per_gas.as_deref()),
+                ),
+                binary(rows.iter().map(|row| &row.input)),
+                Arc::new(UInt8Array::from_iter_values(
+                    rows.iter().map(|row| row.tx_type),
+                )),
+                uint64(rows.iter().map(|row| row.gas_used)),
+                uint64(rows.iter().map(|row| row.cumulative_gas_used)),
+                Arc::new(BooleanArray::from(
+                    rows.iter().map(|row| row.success).collect::<Vec<_>>(),
+                )),
+            ],
+        )
+    }
+
+    fn logs_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let rows = &self.logs;
+        let schema = schema(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("transaction_hash", DataType::FixedSizeBinary(32), false),
+            Field::new("log_index", DataType::UInt32, false),
+            Field::new("address", DataType::FixedSizeBinary(20), false),
+            Field::new("topic0", DataType::FixedSizeBinary(32), true),
+            Field::new("topic1", DataType::FixedSizeBinary(32), true),
+            Field::new("topic2", DataType::FixedSizeBinary(32), true),
+            Field::new("topic3", DataType::FixedSizeBinary(32), true),
+            Field::new("data", DataType::Binary, false),
+        ]);
+        let topic =
+            |index: usize| fixed_binary(32, rows.iter().map(move |row| row.topics.get(index)));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                uint64(rows.iter().map(|row| row.block_number)),
+                uint32(rows.iter().map(|row| row.transaction_index)),
+                fixed_binary(32, rows.iter().map(|row| Some(row.transaction_hash)))?,
+                uint32(rows.iter().map(|row| row.log_index)),
+                fixed_binary(20, rows.iter().map(|row| Some(row.address)))?,
+                topic(0)?,
+                topic(1)?,
+                topic(2)?,
+                topic(3)?,
+                binary(rows.iter().map(|row| &row.data)),
+            ],
+        )
+    }
+
+    fn traces_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let rows = &self.traces;
+        let schema = schema(vec![
+            Field::new("block_number", DataType::UInt64, false),
+            Field::new("transaction_index", DataType::UInt32, false),
+            Field::new("transaction_hash", DataType::FixedSizeBinary(32), false),
+            Field::new("call_index", DataType::UInt32, false),
+            Field::new("parent_index", DataType::UInt32, false),
+            Field::new("depth", DataType::UInt32, false),
+            Field::new("call_type", DataType::Utf8, false),
+            Field::new("from", DataType::FixedSizeBinary(20), false),
+            Field::new("to", DataType::FixedSizeBinary(20), false),
+            Field::new("value", DataType::Utf8, true),
+            Field::new("gas_limit", DataType::UInt64, false),
+            Field::new("gas_used", DataType::UInt64, false),
+            Field::new("input", DataType::Binary, false),
+            Field::new("output", DataType::Binary, false),
+            Field::new("failed", DataType::Boolean, false),
+            Field::new("reverted", DataType::Boolean, false),
+            Field::new("failure_reason", DataType::Utf8, false),
+        ]);
+        RecordBatch::try_new(
+            schema,
+            vec![
+                uint64(rows.iter().map(|row| row.block_number)),
+                uint32(rows.iter().map(|row| row.transaction_index)),
+                fixed_binary(32, rows.iter().map(|row| Some(row.transaction_hash)))?,
+                uint32(rows.iter().map(|row| row.call_index)),
+                uint32(rows.iter().map(|row| row.parent_index)),
+                uint32(rows.iter().map(|row| row.depth)),
+                string(rows.iter().map(|row| Some(row.call_type))),
+                fixed_binary(20, rows.iter().map(|row| Some(&row.from)))?,
+                fixed_binary(20, rows.iter().map(|row| Some(&row.to)))?,
+                string(rows.iter().map(|row| row.value.as_deref())),
+                uint64(rows.iter().map(|row| row.gas_limit)),
+                uint64(rows.iter().map(|row| row.gas_used)),
+                binary(rows.iter().map(|row| &row.input)),
+                binary(rows.iter().map(|row| &row.output)),
+                Arc::new(BooleanArray::from(
+                    rows.iter().map(|row| row.failed).collect::<Vec<_>>(),
+                )),
+                Arc::new(BooleanArray::from(
+                    rows.iter().map(|row| row.reverted).collect::<Vec<_>>(),
+                )),
+                string(rows.iter().map(|row| Some(row.failure_reason.as_str()))),
+            ],
+        )
+    }
+}
+
+fn schema(fields: Vec<Field>) -> SchemaRef {
+    Arc::new(Schema::new(fields))
+}
+
+fn uint64(values: impl Iterator<Item = u64>) -> ArrayRef {
+    Arc::new(UInt64Array::from_iter_values(values))
+}
+
+fn uint32(values: impl Iterator<Item = u32>) -> ArrayRef {
+    Arc::new(UInt32Array::from_iter_values(values))
+}
+
+fn string<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    Arc::new(values.collect::<StringArray>())
+}
+
+fn binary<'a>(values: impl Iterator<Item = &'a Vec<u8>>) -> ArrayRef {
+    Arc::new(BinaryArray::from_iter_values(values))
+}
+
+/// Fails if a value isn't `size` bytes long, which validates the hashes and addresses
+/// that are copied as is from the block
+fn fixed_binary<U: AsRef<[u8]>>(
+    size: i32,
+    values: impl Iterator<Item = Option<U>>,
+) -> Result<ArrayRef, ArrowError> {
+    Ok(Arc::new(
+        FixedSizeBinaryArray::try_from_sparse_iter_with_size(values, size)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParquetSink;
+    use crate::convert::error::ConvertError;
+    use crate::handle_file;
+    use crate::output::error::OutputError;
+    use crate::output::BlockSink;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parquet_datasets() {
+        let dir = std::env::temp_dir().join("decoder-test-parquet");
+        let _ = fs::remove_dir_all(&dir);
+
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let block = blocks.first().unwrap();
+
+        let mut sink = ParquetSink::new(&dir, None).unwrap();
+        sink.write_block(block).unwrap();
+        sink.finish().unwrap();
+
+        let rows = |dataset: &str| {
+            let file = dir
+                .join(dataset)
+                .join(format!("{}-0017686312-0017686312.parquet", dataset));
+            let reader = SerializedFileReader::new(File::open(file).unwrap()).unwrap();
+            reader.metadata().file_metadata().num_rows() as usize
+        };
+
+        let logs: usize = block
+            .transaction_traces
+            .iter()
+            .map(|trace| trace.receipt.as_ref().unwrap().logs.len())
+            .sum();
+        let calls: usize = block
+            .transaction_traces
+            .iter()
+            .map(|trace| trace.calls.len())
+            .sum();
+
+        assert_eq!(rows("blocks"), 1);
+        assert_eq!(rows("transactions"), block.transaction_traces.len());
+        assert_eq!(rows("logs"), logs);
+        assert_eq!(rows("traces"), calls);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parquet_missing_timestamp() {
+        let dir = std::env::temp_dir().join("decoder-test-parquet-timestamp");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        blocks[0].header.as_mut().unwrap().timestamp = None;
+
+        let mut sink = ParquetSink::new(&dir, None).unwrap();
+        assert!(matches!(
+            sink.write_block(&blocks[0]),
+            Err(OutputError::InvalidField(ConvertError::MissingField(
+                "timestamp"
+            )))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,7 +9,7 @@ use reth_primitives::{hex, B256};
 use reth_trie_common::root::ordered_trie_root_with_encoder;
 use sf_protos::ethereum::r#type::v2::Block;
 
-const BYZANTINUM_FORK_BLOCK: u64 = 4_370_000;
+pub const BYZANTINUM_FORK_BLOCK: u64 = 4_370_000;
 
 /// Verifies the receipt root in a given block's header against a
 /// computed receipt root from the block's body.
@@ -74,14 +74,45 @@ fn get_encoder(block: &Block) -> fn(&FullReceipt, &mut Vec<u8>) {
     if block.number >= BYZANTINUM_FORK_BLOCK {
         |r: &FullReceipt, out: &mut Vec<u8>| r.receipt.encode_inner(out, false)
     } else {
-        |r: &FullReceipt, out: &mut Vec<u8>| {
-            receipt_rlp_header(r).encode(out);
-            r.state_root.as_slice().encode(out);
-            r.receipt.receipt.cumulative_gas_used.encode(out);
-            r.receipt.bloom.encode(out);
-            r.receipt.receipt.logs.encode(out);
+        encode_pre_byzantium
+    }
+}
+
+fn encode_pre_byzantium(r: &FullReceipt, out: &mut Vec<u8>) {
+    receipt_rlp_header(r).encode(out);
+    r.state_root.as_slice().encode(out);
+    r.receipt.receipt.cumulative_gas_used.encode(out);
+    r.receipt.bloom.encode(out);
+    r.receipt.receipt.logs.encode(out);
+}
+
+/// Encodes the receipts of a block as an RLP list, as found in `eth` protocol receipts
+/// messages and era1 archives.
+///
+/// Unlike the encoding used for the receipt trie, typed receipts are wrapped in an RLP string.
+/// Receipts of blocks before the Byzantium fork have a state root instead of a status.
+///
+/// # Arguments
+///
+/// * `receipts` the receipts of the block, in transaction order
+/// * `block_number` the number of the block the receipts belong to
+/// * `out` the buffer the list is written to
+pub fn encode_receipts(receipts: &[FullReceipt], block_number: u64, out: &mut Vec<u8>) {
+    let mut payload = Vec::new();
+    for receipt in receipts {
+        if block_number >= BYZANTINUM_FORK_BLOCK {
+            receipt.receipt.encode_inner(&mut payload, true);
+        } else {
+            encode_pre_byzantium(receipt, &mut payload);
         }
     }
+
+    Header {
+        list: true,
+        payload_length: payload.len(),
+    }
+    .encode(out);
+    out.extend_from_slice(&payload);
 }
 
 /// Encodes receipt header using [RLP serialization](https://ethereum.org/en/developers/docs/data-structures-and-encoding/rlp)
@@ -0,0 +1,20 @@
+use crate::headers::error::BlockHeaderError;
+use crate::receipts::error::ReceiptError;
+use crate::transactions::error::TransactionError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RlpError {
+    #[error("Missing header")]
+    MissingHeader,
+    #[error("Header hash {0} doesn't match block hash {1}")]
+    MismatchedHash(String, String),
+    #[error("Block {0} has withdrawals, which flat files don't hold")]
+    MissingWithdrawals(u64),
+    #[error("Invalid header: {0}")]
+    BlockHeaderError(#[from] BlockHeaderError),
+    #[error("Invalid transaction: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Invalid receipt: {0}")]
+    ReceiptError(#[from] ReceiptError),
+}
@@ -0,0 +1,168 @@
+pub mod error;
+
+use crate::headers::block_header_to_header;
+use crate::receipts::encode_receipts;
+use crate::receipts::receipt::FullReceipt;
+use crate::rlp::error::RlpError;
+use crate::transactions::trace_to_signed;
+use alloy_rlp::Encodable;
+use reth_primitives::{hex, Header, TransactionSigned};
+use sf_protos::ethereum::r#type::v2::Block;
+
+/// `RlpBlock` holds the typed header, body and receipts of a block, to be encoded with the
+/// canonical RLP encodings used by execution clients.
+///
+/// Transactions and receipts are the same values the transaction and receipt roots are
+/// computed from, see [`crate::transactions::check_transaction_root`] and
+/// [`crate::receipts::check_receipt_root`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RlpBlock {
+    pub header: Header,
+    pub transactions: Vec<TransactionSigned>,
+    pub uncles: Vec<Header>,
+    /// The RLP encoded list of receipts, see [`encode_receipts`]
+    pub receipts: Vec<u8>,
+}
+
+impl RlpBlock {
+    /// The RLP encoded header, whose keccak hash is the block hash
+    pub fn header_rlp(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.header.encode(&mut out);
+        out
+    }
+
+    /// The RLP encoded body, `[transactions, uncles]`
+    pub fn body_rlp(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_body(&self.transactions, &self.uncles, &mut out);
+        out
+    }
+
+    /// The RLP encoded list of receipts, see [`encode_receipts`]
+    pub fn receipts_rlp(&self) -> &[u8] {
+        &self.receipts
+    }
+
+    /// The RLP encoded block, `[header, transactions, uncles]`, as imported by
+    /// `geth import` and `reth import`
+    pub fn block_rlp(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        alloy_rlp::Header {
+            list: true,
+            payload_length: self.header.length()
+                + self.transactions.length()
+                + self.uncles.length(),
+        }
+        .encode(&mut out);
+        self.header.encode(&mut out);
+        self.transactions.encode(&mut out);
+        self.uncles.encode(&mut out);
+        out
+    }
+}
+
+impl TryFrom<&Block> for RlpBlock {
+    type Error = RlpError;
+
+    /// Converts a verified Firehose block, checking that its header hashes to the block hash.
+    ///
+    /// Blocks from the Shanghai fork on are rejected, as their bodies hold withdrawals
+    /// which aren't part of flat files.
+    fn try_from(block: &Block) -> Result<Self, Self::Error> {
+        let block_header = block.header.as_ref().ok_or(RlpError::MissingHeader)?;
+        let header = block_header_to_header(block_header)?;
+        let hash = header.hash_slow();
+        if hash.as_slice() != block.hash.as_slice() {
+            return Err(RlpError::MismatchedHash(
+                hex::encode(hash),
+                hex::encode(&block.hash),
+            ));
+        }
+        if header.withdrawals_root.is_some() {
+            return Err(RlpError::MissingWithdrawals(block.number));
+        }
+
+        let transactions = block
+            .transaction_traces
+            .iter()
+            .map(trace_to_signed)
+            .collect::<Result<Vec<TransactionSigned>, _>>()?;
+        let uncles = block
+            .uncles
+            .iter()
+            .map(block_header_to_header)
+            .collect::<Result<Vec<Header>, _>>()?;
+        let receipts = block
+            .transaction_traces
+            .iter()
+            .map(FullReceipt::try_from)
+            .collect::<Result<Vec<FullReceipt>, _>>()?;
+        let mut receipts_rlp = Vec::new();
+        encode_receipts(&receipts, header.number, &mut receipts_rlp);
+
+        Ok(Self {
+            header,
+            transactions,
+            uncles,
+            receipts: receipts_rlp,
+        })
+    }
+}
+
+/// Encodes a block body, `[transactions, uncles]`, in `out`.
+pub fn encode_body(transactions: &[TransactionSigned], uncles: &[Header], out: &mut Vec<u8>) {
+    alloy_rlp::Header {
+        list: true,
+        payload_length: alloy_rlp::list_length::<_, TransactionSigned>(transactions)
+            + alloy_rlp::list_length::<_, Header>(uncles),
+    }
+    .encode(out);
+    alloy_rlp::encode_list::<_, TransactionSigned>(transactions, out);
+    alloy_rlp::encode_list::<_, Header>(uncles, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RlpBlock;
+    use crate::handle_file;
+    use crate::rlp::error::RlpError;
+    use alloy_rlp::Decodable;
+    use reth_primitives::{keccak256, Header};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_rlp_block() {
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        for block in &blocks {
+            let rlp_block = RlpBlock::try_from(block).unwrap();
+
+            let header_rlp = rlp_block.header_rlp();
+            assert_eq!(keccak256(&header_rlp).as_slice(), block.hash.as_slice());
+
+            let mut block_rlp = rlp_block.block_rlp().as_slice();
+            let list = alloy_rlp::Header::decode(&mut block_rlp).unwrap();
+            assert!(list.list);
+            assert_eq!(list.payload_length, block_rlp.len());
+            assert_eq!(Header::decode(&mut block_rlp).unwrap(), rlp_block.header);
+        }
+    }
+
+    #[test]
+    fn test_rlp_block_withdrawals() {
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+
+        assert!(matches!(
+            RlpBlock::try_from(&blocks[0]),
+            Err(RlpError::MissingWithdrawals(17686312))
+        ));
+    }
+}
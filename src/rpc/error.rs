@@ -0,0 +1,18 @@
+use crate::convert::error::ConvertError;
+use crate::receipts::error::ReceiptError;
+use crate::transactions::error::TransactionError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RpcError {
+    #[error("Missing header")]
+    MissingHeader,
+    #[error("Missing receipt")]
+    MissingReceipt,
+    #[error("Invalid field: {0}")]
+    InvalidField(#[from] ConvertError),
+    #[error("Invalid transaction: {0}")]
+    TransactionError(#[from] TransactionError),
+    #[error("Invalid receipt: {0}")]
+    ReceiptError(#[from] ReceiptError),
+}
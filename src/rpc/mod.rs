@@ -0,0 +1,425 @@
+pub mod error;
+
+use crate::convert::{bigint_to_u256, optional_b256, timestamp, to_address, to_b256, to_bloom};
+use crate::receipts::logs::map_logs;
+use crate::receipts::receipt::FullReceipt;
+use crate::receipts::BYZANTINUM_FORK_BLOCK;
+use crate::rpc::error::RpcError;
+use crate::transactions::{bigint_to_u128, trace_to_signed};
+use alloy_primitives::{Address, Bloom, Bytes, B256, B64, U256, U64};
+use reth_primitives::{AccessList, Transaction, TxType};
+use serde::Serialize;
+use sf_protos::ethereum::r#type::v2::{Block, TransactionTrace};
+
+/// A block as returned by `eth_getBlockByNumber` and `eth_getBlockByHash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlock {
+    pub hash: B256,
+    pub parent_hash: B256,
+    #[serde(rename = "sha3Uncles")]
+    pub uncles_hash: B256,
+    pub miner: Address,
+    pub state_root: B256,
+    pub transactions_root: B256,
+    pub receipts_root: B256,
+    pub logs_bloom: Bloom,
+    pub difficulty: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_difficulty: Option<U256>,
+    pub number: U64,
+    pub gas_limit: U64,
+    pub gas_used: U64,
+    pub timestamp: U64,
+    pub extra_data: Bytes,
+    pub mix_hash: B256,
+    pub nonce: B64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawals_root: Option<B256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<B256>,
+    pub size: U64,
+    pub transactions: BlockTransactions,
+    pub uncles: Vec<B256>,
+}
+
+/// The transactions of an [`RpcBlock`], either their hashes or the full transaction objects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum BlockTransactions {
+    Hashes(Vec<B256>),
+    Full(Vec<RpcTransaction>),
+}
+
+/// A transaction as returned by `eth_getTransactionByHash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransaction {
+    pub hash: B256,
+    pub nonce: U64,
+    pub block_hash: B256,
+    pub block_number: U64,
+    pub transaction_index: U64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    /// The effective gas price for EIP-1559 transactions
+    pub gas_price: U256,
+    pub gas: U64,
+    pub input: Bytes,
+    #[serde(rename = "type")]
+    pub tx_type: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<AccessList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub v: U64,
+    pub r: U256,
+    pub s: U256,
+}
+
+/// A receipt as returned by `eth_getTransactionReceipt`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcReceipt {
+    pub transaction_hash: B256,
+    pub transaction_index: U64,
+    pub block_hash: B256,
+    pub block_number: U64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub cumulative_gas_used: U64,
+    pub gas_used: U64,
+    pub contract_address: Option<Address>,
+    pub logs: Vec<RpcLog>,
+    pub logs_bloom: Bloom,
+    #[serde(rename = "type")]
+    pub tx_type: U64,
+    /// Set from the Byzantium fork on, [`RpcReceipt::root`] is set instead before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<B256>,
+    pub effective_gas_price: U256,
+}
+
+/// A log as returned in receipts and by `eth_getLogs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+    pub block_hash: B256,
+    pub block_number: U64,
+    pub transaction_hash: B256,
+    pub transaction_index: U64,
+    pub log_index: U64,
+    pub removed: bool,
+}
+
+/// Fields of a block shared by its transactions, receipts and logs
+struct BlockContext {
+    hash: B256,
+    number: u64,
+    base_fee_per_gas: Option<u128>,
+}
+
+impl BlockContext {
+    fn new(block: &Block) -> Result<Self, RpcError> {
+        let header = block.header.as_ref().ok_or(RpcError::MissingHeader)?;
+        let base_fee_per_gas = header
+            .base_fee_per_gas
+            .clone()
+            .map(bigint_to_u128)
+            .transpose()?;
+
+        Ok(Self {
+            hash: to_b256("hash", &block.hash)?,
+            number: block.number,
+            base_fee_per_gas,
+        })
+    }
+}
+
+/// Converts a block into its JSON-RPC representation.
+///
+/// # Arguments
+///
+/// * `block`: The [`Block`] to convert, it must have a header.
+/// * `full_transactions`: Whether to include the full transaction objects, or only their hashes.
+///
+pub fn block_to_rpc(block: &Block, full_transactions: bool) -> Result<RpcBlock, RpcError> {
+    let header = block.header.as_ref().ok_or(RpcError::MissingHeader)?;
+    let context = BlockContext::new(block)?;
+
+    let transactions = if full_transactions {
+        BlockTransactions::Full(
+            block
+                .transaction_traces
+                .iter()
+                .map(|trace| rpc_transaction(&context, trace))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    } else {
+        BlockTransactions::Hashes(
+            block
+                .transaction_traces
+                .iter()
+                .map(|trace| to_b256("transaction hash", &trace.hash))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    };
+
+    let uncles = block
+        .uncles
+        .iter()
+        .map(|uncle| to_b256("uncle hash", &uncle.hash))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(RpcBlock {
+        hash: context.hash,
+        parent_hash: to_b256("parent_hash", &header.parent_hash)?,
+        uncles_hash: to_b256("uncle_hash", &header.uncle_hash)?,
+        miner: to_address("coinbase", &header.coinbase)?,
+        state_root: to_b256("state_root", &header.state_root)?,
+        transactions_root: to_b256("transactions_root", &header.transactions_root)?,
+        receipts_root: to_b256("receipt_root", &header.receipt_root)?,
+        logs_bloom: to_bloom("logs_bloom", &header.logs_bloom)?,
+        difficulty: bigint_to_u256("difficulty", header.difficulty.as_ref())?,
+        total_difficulty: header
+            .total_difficulty
+            .as_ref()
+            .map(|total_difficulty| bigint_to_u256("total_difficulty", Some(total_difficulty)))
+            .transpose()?,
+        number: U64::from(context.number),
+        gas_limit: U64::from(header.gas_limit),
+        gas_used: U64::from(header.gas_used),
+        timestamp: U64::from(timestamp(header)?),
+        extra_data: Bytes::copy_from_slice(&header.extra_data),
+        mix_hash: to_b256("mix_hash", &header.mix_hash)?,
+        nonce: B64::from(header.nonce.to_be_bytes()),
+        base_fee_per_gas: context.base_fee_per_gas.map(U256::from),
+        withdrawals_root: optional_b256("withdrawals_root", &header.withdrawals_root)?,
+        blob_gas_used: header.blob_gas_used.map(U64::from),
+        excess_blob_gas: header.excess_blob_gas.map(U64::from),
+        parent_beacon_block_root: optional_b256("parent_beacon_root", &header.parent_beacon_root)?,
+        size: U64::from(block.size),
+        transactions,
+        uncles,
+    })
+}
+
+/// Converts a transaction of `block` into its JSON-RPC representation.
+pub fn transaction_to_rpc(
+    block: &Block,
+    trace: &TransactionTrace,
+) -> Result<RpcTransaction, RpcError> {
+    rpc_transaction(&BlockContext::new(block)?, trace)
+}
+
+/// Converts the receipt of a transaction of `block` into its JSON-RPC representation.
+pub fn receipt_to_rpc(block: &Block, trace: &TransactionTrace) -> Result<RpcReceipt, RpcError> {
+    rpc_receipt(&BlockContext::new(block)?, trace)
+}
+
+/// Converts the receipts of all transactions of `block`, as returned by `eth_getBlockReceipts`.
+pub fn block_receipts_to_rpc(block: &Block) -> Result<Vec<RpcReceipt>, RpcError> {
+    let context = BlockContext::new(block)?;
+    block
+        .transaction_traces
+        .iter()
+        .map(|trace| rpc_receipt(&context, trace))
+        .collect()
+}
+
+fn rpc_transaction(
+    context: &BlockContext,
+    trace: &TransactionTrace,
+) -> Result<RpcTransaction, RpcError> {
+    let signed = trace_to_signed(trace)?;
+    let transaction = &signed.transaction;
+    let tx_type = transaction.tx_type();
+
+    let v = match tx_type {
+        TxType::Legacy => signed.signature.v(transaction.chain_id()),
+        _ => u64::from(signed.signature.odd_y_parity),
+    };
+    let max_priority_fee_per_gas = transaction.max_priority_fee_per_gas();
+
+    Ok(RpcTransaction {
+        hash: signed.hash,
+        nonce: U64::from(transaction.nonce()),
+        block_hash: context.hash,
+        block_number: U64::from(context.number),
+        transaction_index: U64::from(trace.index),
+        from: to_address("from", &trace.from)?,
+        to: transaction.to(),
+        value: transaction.value(),
+        gas_price: U256::from(effective_gas_price(transaction, context.base_fee_per_gas)),
+        gas: U64::from(transaction.gas_limit()),
+        input: transaction.input().clone(),
+        tx_type: U64::from(u8::from(tx_type)),
+        chain_id: transaction.chain_id().map(U64::from),
+        access_list: transaction.access_list().cloned(),
+        max_fee_per_gas: max_priority_fee_per_gas
+            .map(|_| U256::from(transaction.max_fee_per_gas())),
+        max_priority_fee_per_gas: max_priority_fee_per_gas.map(U256::from),
+        v: U64::from(v),
+        r: signed.signature.r,
+        s: signed.signature.s,
+    })
+}
+
+fn rpc_receipt(context: &BlockContext, trace: &TransactionTrace) -> Result<RpcReceipt, RpcError> {
+    let signed = trace_to_signed(trace)?;
+    let full_receipt = FullReceipt::try_from(trace)?;
+    let trace_receipt = trace.receipt.as_ref().ok_or(RpcError::MissingReceipt)?;
+    let transaction_index = U64::from(trace.index);
+
+    let logs = map_logs(&trace_receipt.logs)?
+        .into_iter()
+        .zip(&trace_receipt.logs)
+        .map(|(log, block_log)| RpcLog {
+            address: log.address,
+            topics: log.data.topics().to_vec(),
+            data: log.data.data,
+            block_hash: context.hash,
+            block_number: U64::from(context.number),
+            transaction_hash: signed.hash,
+            transaction_index,
+            log_index: U64::from(block_log.block_index),
+            removed: false,
+        })
+        .collect();
+
+    let contract_address = match signed.transaction.to() {
+        Some(_) => None,
+        None => trace
+            .calls
+            .first()
+            .map(|call| to_address("contract address", &call.address))
+            .transpose()?,
+    };
+
+    let (status, root) = if context.number >= BYZANTINUM_FORK_BLOCK {
+        let success = full_receipt.receipt.receipt.success;
+        (Some(U64::from(u8::from(success))), None)
+    } else {
+        (None, Some(to_b256("state_root", &full_receipt.state_root)?))
+    };
+
+    Ok(RpcReceipt {
+        transaction_hash: signed.hash,
+        transaction_index,
+        block_hash: context.hash,
+        block_number: U64::from(context.number),
+        from: to_address("from", &trace.from)?,
+        to: signed.transaction.to(),
+        cumulative_gas_used: U64::from(full_receipt.receipt.receipt.cumulative_gas_used),
+        gas_used: U64::from(trace.gas_used),
+        contract_address,
+        logs,
+        logs_bloom: full_receipt.receipt.bloom,
+        tx_type: U64::from(u8::from(signed.transaction.tx_type())),
+        status,
+        root,
+        effective_gas_price: U256::from(effective_gas_price(
+            &signed.transaction,
+            context.base_fee_per_gas,
+        )),
+    })
+}
+
+/// The price per gas paid by a transaction, `min(max fee, base fee + max priority fee)`
+/// for EIP-1559 transactions and the gas price for the others.
+pub(crate) fn effective_gas_price(
+    transaction: &Transaction,
+    base_fee_per_gas: Option<u128>,
+) -> u128 {
+    match (transaction.max_priority_fee_per_gas(), base_fee_per_gas) {
+        (Some(max_priority_fee_per_gas), Some(base_fee_per_gas)) => transaction
+            .max_fee_per_gas()
+            .min(base_fee_per_gas.saturating_add(max_priority_fee_per_gas)),
+        _ => transaction.max_fee_per_gas(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_receipts_to_rpc, block_to_rpc, BlockTransactions};
+    use crate::handle_file;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_block_to_rpc() {
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let block = blocks.first().unwrap();
+
+        let rpc_block = block_to_rpc(block, true).unwrap();
+        let json = serde_json::to_value(&rpc_block).unwrap();
+
+        assert_eq!(json["number"], format!("{:#x}", 17686312));
+        assert_eq!(json["logsBloom"].as_str().unwrap().len(), 2 + 2 * 256);
+        assert_eq!(json["nonce"].as_str().unwrap().len(), 2 + 2 * 8);
+        assert!(json["baseFeePerGas"].is_string());
+
+        let transaction = &json["transactions"][0];
+        assert_eq!(
+            transaction["hash"],
+            "0x5d8438a6c6336b90ca42a73c4e4ea8985fdfc3e2526af38592894353fd9d0d39"
+        );
+        assert_eq!(transaction["type"], "0x2");
+        assert_eq!(transaction["nonce"], "0xedf");
+        assert_eq!(transaction["gas"], "0x246ca");
+        assert_eq!(
+            transaction["to"],
+            "0x7a250d5630b4cf539739df2c5dacb4c659f2488d"
+        );
+
+        let hashes = match block_to_rpc(block, false).unwrap().transactions {
+            BlockTransactions::Hashes(hashes) => hashes,
+            BlockTransactions::Full(_) => panic!("expected transaction hashes"),
+        };
+        assert_eq!(hashes.len(), block.transaction_traces.len());
+    }
+
+    #[test]
+    fn test_block_receipts_to_rpc() {
+        let blocks =
+            handle_file(&PathBuf::from("example0017686312.dbin"), None, None, None).unwrap();
+        let block = blocks.first().unwrap();
+
+        let receipts = block_receipts_to_rpc(block).unwrap();
+        assert_eq!(receipts.len(), block.transaction_traces.len());
+
+        let rpc_block = block_to_rpc(block, true).unwrap();
+        let transactions = match rpc_block.transactions {
+            BlockTransactions::Full(transactions) => transactions,
+            BlockTransactions::Hashes(_) => panic!("expected full transactions"),
+        };
+        for (receipt, transaction) in receipts.iter().zip(&transactions) {
+            assert_eq!(receipt.transaction_hash, transaction.hash);
+            assert_eq!(receipt.effective_gas_price, transaction.gas_price);
+            assert!(receipt.status.is_some());
+            assert!(receipt.root.is_none());
+        }
+
+        let last = receipts.last().unwrap();
+        assert_eq!(
+            last.cumulative_gas_used, rpc_block.gas_used,
+            "the last receipt's cumulative gas is the block's gas"
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use crate::error::DecodeError;
+use crate::rpc::error::RpcError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Decode error: {0}")]
+    DecodeError(#[from] DecodeError),
+    #[error("RPC conversion error: {0}")]
+    RpcError(#[from] RpcError),
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Method not found: {0}")]
+    MethodNotFound(String),
+    #[error("Invalid params: {0}")]
+    InvalidParams(String),
+}
+
+impl ServerError {
+    /// The JSON-RPC error code of the error
+    pub fn code(&self) -> i64 {
+        match self {
+            ServerError::MethodNotFound(_) => -32601,
+            ServerError::InvalidParams(_) | ServerError::JsonError(_) => -32602,
+            _ => -32603,
+        }
+    }
+}
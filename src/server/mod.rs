@@ -0,0 +1,466 @@
+pub mod error;
+
+pub use crate::index::BlockIndex;
+
+use crate::index::{BlockLookup, BlockReader};
+use crate::rpc::{block_receipts_to_rpc, block_to_rpc, receipt_to_rpc, transaction_to_rpc, RpcLog};
+use crate::server::error::ServerError;
+use alloy_primitives::{Address, B256, U64};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sf_protos::ethereum::r#type::v2::{Block, TransactionTrace};
+use simple_log::log;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Maximum number of blocks `eth_getLogs` can be queried over at once.
+pub const MAX_LOGS_RANGE: u64 = 10_000;
+
+/// `RpcServer` answers a read-only subset of the Ethereum JSON-RPC API from flat files:
+///
+/// * `eth_blockNumber` and `eth_chainId`
+/// * `eth_getBlockByNumber` and `eth_getBlockByHash`
+/// * `eth_getTransactionByHash` and `eth_getTransactionReceipt`
+/// * `eth_getLogs`, over at most [`MAX_LOGS_RANGE`] blocks
+///
+/// Blocks are located with a [`BlockIndex`] and read with a [`BlockReader`], or any other
+/// [`BlockLookup`] like a [`crate::index::remote::RemoteBlockStore`].
+pub struct RpcServer {
+    reader: Box<dyn BlockLookup>,
+    chain_id: u64,
+}
+
+impl RpcServer {
+    pub fn new(index: BlockIndex, chain_id: u64) -> Self {
+        Self::with_lookup(Box::new(BlockReader::new(index)), chain_id)
+    }
+
+    /// Serves the blocks read by `reader`
+    pub fn with_lookup(reader: Box<dyn BlockLookup>, chain_id: u64) -> Self {
+        Self { reader, chain_id }
+    }
+
+    /// Answers a JSON-RPC request, or a batch of requests
+    pub fn handle(&self, request: Value) -> Value {
+        match request {
+            Value::Array(requests) => Value::Array(
+                requests
+                    .into_iter()
+                    .map(|request| self.handle_one(request))
+                    .collect(),
+            ),
+            request => self.handle_one(request),
+        }
+    }
+
+    fn handle_one(&self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str);
+        let params = request
+            .get("params")
+            .cloned()
+            .unwrap_or_else(|| Value::Array(Vec::new()));
+
+        let result = match method {
+            Some(method) => self.call(method, params),
+            None => Err(ServerError::InvalidParams("missing method".to_string())),
+        };
+        match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(err) => {
+                log::debug!("Request failed: {}", err);
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": err.code(), "message": err.to_string()},
+                })
+            }
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, ServerError> {
+        let params: Vec<Value> = serde_json::from_value(params)?;
+        match method {
+            "eth_chainId" => Ok(json!(U64::from(self.chain_id))),
+            "eth_blockNumber" => Ok(json!(self.reader.index().latest().map(U64::from))),
+            "eth_getBlockByNumber" => {
+                let number: String = param(&params, 0)?;
+                let full: Option<bool> = param(&params, 1)?;
+                match self.block_number(&number)? {
+                    Some(number) => self.block_json(number, full.unwrap_or_default()),
+                    None => Ok(Value::Null),
+                }
+            }
+            "eth_getBlockByHash" => {
+                let hash: B256 = param(&params, 0)?;
+                let full: Option<bool> = param(&params, 1)?;
+                match self.reader.index().block_number(&hash) {
+                    Some(number) => self.block_json(number, full.unwrap_or_default()),
+                    None => Ok(Value::Null),
+                }
+            }
+            "eth_getTransactionByHash" => {
+                let hash: B256 = param(&params, 0)?;
+                self.transaction_json(&hash, |block, trace| {
+                    Ok(json!(transaction_to_rpc(block, trace)?))
+                })
+            }
+            "eth_getTransactionReceipt" => {
+                let hash: B256 = param(&params, 0)?;
+                self.transaction_json(&hash, |block, trace| {
+                    Ok(json!(receipt_to_rpc(block, trace)?))
+                })
+            }
+            "eth_getLogs" => {
+                let filter: LogFilter = param(&params, 0)?;
+                Ok(json!(self.logs(&filter)?))
+            }
+            _ => Err(ServerError::MethodNotFound(method.to_string())),
+        }
+    }
+
+    /// Resolves a block number or tag, `None` if the block isn't indexed
+    fn block_number(&self, number: &str) -> Result<Option<u64>, ServerError> {
+        Ok(self
+            .parse_block(number)?
+            .filter(|number| self.reader.index().location(*number).is_some()))
+    }
+
+    /// Parses a block number, whether it is indexed or not, or resolves a tag with the index,
+    /// `None` if no block is indexed
+    fn parse_block(&self, number: &str) -> Result<Option<u64>, ServerError> {
+        match number {
+            "latest" | "safe" | "finalized" | "pending" => Ok(self.reader.index().latest()),
+            "earliest" => Ok(self.reader.index().earliest()),
+            number => number
+                .strip_prefix("0x")
+                .and_then(|number| u64::from_str_radix(number, 16).ok())
+                .map(Some)
+                .ok_or_else(|| {
+                    ServerError::InvalidParams(format!("invalid block number {}", number))
+                }),
+        }
+    }
+
+    /// Reads block `number` from its flat file
+    pub fn block(&self, number: u64) -> Result<Option<Block>, ServerError> {
+        Ok(self.reader.block(number)?)
+    }
+
+    fn block_json(&self, number: u64, full_transactions: bool) -> Result<Value, ServerError> {
+        match self.block(number)? {
+            Some(block) => Ok(json!(block_to_rpc(&block, full_transactions)?)),
+            None => Ok(Value::Null),
+        }
+    }
+
+    /// Converts the transaction `hash` of its indexed block with `to_json`, `null` if it isn't
+    /// indexed or no longer found where the index locates it, e.g. with an outdated index
+    fn transaction_json(
+        &self,
+        hash: &B256,
+        to_json: impl Fn(&Block, &TransactionTrace) -> Result<Value, ServerError>,
+    ) -> Result<Value, ServerError> {
+        let (number, index) = match self.reader.index().transaction(hash) {
+            Some(location) => location,
+            None => return Ok(Value::Null),
+        };
+        let block = match self.block(number)? {
+            Some(block) => block,
+            None => return Ok(Value::Null),
+        };
+        match block
+            .transaction_traces
+            .get(index)
+            .filter(|trace| trace.hash.as_slice() == hash.as_slice())
+        {
+            Some(trace) => to_json(&block, trace),
+            None => {
+                log::warn!(
+                    "Transaction {} isn't at its indexed position {} of block {}",
+                    hash,
+                    index,
+                    number
+                );
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    /// Logs matching `filter`, whose range is limited to the indexed blocks
+    fn logs(&self, filter: &LogFilter) -> Result<Vec<RpcLog>, ServerError> {
+        let index = self.reader.index();
+        let (earliest, latest) = match (index.earliest(), index.latest()) {
+            (Some(earliest), Some(latest)) => (earliest, latest),
+            _ => return Ok(Vec::new()),
+        };
+        let (from, to) = match filter.block_hash {
+            Some(hash) => match index.block_number(&hash) {
+                Some(number) => (number, number),
+                None => return Ok(Vec::new()),
+            },
+            None => {
+                let from = filter.from_block.as_deref().unwrap_or("latest");
+                let to = filter.to_block.as_deref().unwrap_or("latest");
+                (
+                    self.parse_block(from)?.unwrap_or(latest),
+                    self.parse_block(to)?.unwrap_or(latest),
+                )
+            }
+        };
+        if to < from || to - from >= MAX_LOGS_RANGE {
+            return Err(ServerError::InvalidParams(format!(
+                "invalid range {}..={}, at most {} blocks can be queried",
+                from, to, MAX_LOGS_RANGE
+            )));
+        }
+
+        let mut logs = Vec::new();
+        for number in from.max(earliest)..=to.min(latest) {
+            let block = match self.block(number)? {
+                Some(block) => block,
+                None => continue,
+            };
+            for receipt in block_receipts_to_rpc(&block)? {
+                logs.extend(receipt.logs.into_iter().filter(|log| filter.matches(log)));
+            }
+        }
+        Ok(logs)
+    }
+}
+
+/// Deserializes the positional parameter `index`, missing parameters being `null`
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T, ServerError> {
+    let param = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(param)
+        .map_err(|err| ServerError::InvalidParams(format!("parameter {}: {}", index, err)))
+}
+
+/// The filter of `eth_getLogs`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogFilter {
+    from_block: Option<String>,
+    to_block: Option<String>,
+    block_hash: Option<B256>,
+    address: Option<OneOrMany<Address>>,
+    /// Topics by position, `None` matching any topic
+    #[serde(default)]
+    topics: Vec<Option<OneOrMany<B256>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T: PartialEq> OneOrMany<T> {
+    fn contains(&self, value: &T) -> bool {
+        match self {
+            OneOrMany::One(one) => one == value,
+            OneOrMany::Many(many) => many.contains(value),
+        }
+    }
+}
+
+impl LogFilter {
+    fn matches(&self, log: &RpcLog) -> bool {
+        if let Some(address) = &self.address {
+            if !address.contains(&log.address) {
+                return false;
+            }
+        }
+        self.topics
+            .iter()
+            .enumerate()
+            .all(|(position, topics)| match topics {
+                Some(topics) => log
+                    .topics
+                    .get(position)
+                    .is_some_and(|topic| topics.contains(topic)),
+                None => true,
+            })
+    }
+}
+
+/// Serves JSON-RPC requests over HTTP on `addr`, until the server fails.
+pub async fn serve(server: RpcServer, addr: SocketAddr) -> Result<(), ServerError> {
+    let app = Router::new()
+        .route("/", post(handle_request))
+        .with_state(Arc::new(server));
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("Serving JSON-RPC on {}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn handle_request(
+    State(server): State<Arc<RpcServer>>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    // decoding flat files is blocking
+    let response = tokio::task::spawn_blocking(move || server.handle(request)).await;
+    Json(response.unwrap_or_else(|err| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {"code": -32603, "message": err.to_string()},
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockIndex, RpcServer};
+    use crate::handle_file;
+    use reth_primitives::hex;
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+
+    fn server() -> RpcServer {
+        RpcServer::new(BlockIndex::build(Path::new("tests")).unwrap(), 1)
+    }
+
+    #[test]
+    fn test_get_block() {
+        let server = server();
+        let blocks = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let hash = format!("0x{}", hex::encode(&blocks[5].hash));
+
+        let response = server.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getBlockByNumber",
+            "params": ["0x5", false],
+        }));
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["hash"], hash);
+
+        let response = server.handle(json!([
+            {"jsonrpc": "2.0", "id": 2, "method": "eth_getBlockByHash", "params": [hash, true]},
+            {"jsonrpc": "2.0", "id": 3, "method": "eth_blockNumber"},
+            {"jsonrpc": "2.0", "id": 4, "method": "eth_getBlockByNumber", "params": ["0x1000"]},
+            {"jsonrpc": "2.0", "id": 5, "method": "eth_sendRawTransaction", "params": ["0x00"]},
+        ]));
+        assert_eq!(response[0]["result"]["number"], "0x5");
+        assert_eq!(response[1]["result"], "0x63");
+        assert!(response[2]["result"].is_null());
+        assert_eq!(response[3]["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn test_get_logs_out_of_index() {
+        let server = server();
+
+        // explicit numbers aren't resolved through the index, the range is clamped to it
+        let response = server.handle(json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "eth_getLogs", "params": [{"fromBlock": "0x200", "toBlock": "0x300"}]},
+            {"jsonrpc": "2.0", "id": 2, "method": "eth_getLogs", "params": [{"fromBlock": "0x50", "toBlock": "0x200"}]},
+            {"jsonrpc": "2.0", "id": 3, "method": "eth_getLogs", "params": [{"fromBlock": "0x300", "toBlock": "0x200"}]},
+            {"jsonrpc": "2.0", "id": 4, "method": "eth_getLogs", "params": [{"fromBlock": "0x0", "toBlock": "0x100000"}]},
+        ]));
+        assert_eq!(response[0]["result"], json!([]));
+        assert_eq!(response[1]["result"], json!([]));
+        assert_eq!(response[2]["error"]["code"], -32602);
+        assert_eq!(response[3]["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn test_get_transaction_outdated_index() {
+        let dir = std::env::temp_dir().join("decoder-test-rpc-outdated-index");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0017686312.dbin");
+        std::fs::copy("example0017686312.dbin", &path).unwrap();
+
+        let index = BlockIndex::build(&dir).unwrap();
+        let block = handle_file(&path, None, None, None).unwrap().remove(0);
+        let hash = format!("0x{}", hex::encode(&block.transaction_traces[1].hash));
+
+        // the indexed file is replaced by one whose first block has no transaction
+        let file = std::fs::read("tests/0000000000.dbin.zst").unwrap();
+        std::fs::write(&path, zstd::decode_all(file.as_slice()).unwrap()).unwrap();
+
+        let server = RpcServer::new(index, 1);
+        for method in ["eth_getTransactionByHash", "eth_getTransactionReceipt"] {
+            let response = server.handle(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": [hash],
+            }));
+            assert_eq!(response["id"], 1);
+            assert_eq!(response["result"], Value::Null);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_transaction_and_logs() {
+        let dir = std::env::temp_dir().join("decoder-test-rpc-server");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("example0017686312.dbin", dir.join("example0017686312.dbin")).unwrap();
+
+        let server = RpcServer::new(BlockIndex::build(&dir).unwrap(), 1);
+        let block = server.block(17686312).unwrap().unwrap();
+        let trace = block
+            .transaction_traces
+            .iter()
+            .find(|trace| {
+                trace
+                    .receipt
+                    .as_ref()
+                    .is_some_and(|receipt| !receipt.logs.is_empty())
+            })
+            .unwrap();
+        let hash = format!("0x{}", hex::encode(&trace.hash));
+
+        let response = server.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getTransactionByHash",
+            "params": [hash],
+        }));
+        assert_eq!(response["result"]["hash"], hash);
+
+        let response = server.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getTransactionReceipt",
+            "params": [hash],
+        }));
+        let receipt_logs = response["result"]["logs"].as_array().unwrap().clone();
+        assert!(!receipt_logs.is_empty());
+
+        let address = receipt_logs[0]["address"].clone();
+        let topic = receipt_logs[0]["topics"][0].clone();
+        let response = server.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getLogs",
+            "params": [{"fromBlock": "0x10ddf28", "toBlock": "latest", "address": address, "topics": [[topic]]}],
+        }));
+        let logs = response["result"].as_array().unwrap();
+        assert!(logs.contains(&receipt_logs[0]));
+        assert!(logs
+            .iter()
+            .all(|log| log["address"] == address && log["topics"][0] == topic));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
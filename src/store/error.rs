@@ -0,0 +1,20 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[cfg(any(feature = "s3", feature = "gcs", feature = "http"))]
+    #[error("Object store error: {0}")]
+    ObjectStoreError(#[from] object_store::Error),
+    #[error("Object not found: {0}")]
+    NotFound(String),
+    #[error("Invalid range {1:?} of {0}")]
+    InvalidRange(String, std::ops::Range<u64>),
+    #[error("Invalid store URL: {0}")]
+    InvalidUrl(String),
+    #[error("Store {0} is not supported, enable its cargo feature")]
+    Unsupported(String),
+    #[error("Store runtime stopped")]
+    RuntimeStopped,
+}
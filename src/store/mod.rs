@@ -0,0 +1,422 @@
+pub mod error;
+#[cfg(any(feature = "s3", feature = "gcs", feature = "http"))]
+pub mod remote;
+
+use crate::compression::Compression;
+use crate::dbin::DbinReaderOptions;
+use crate::error::DecodeError;
+use crate::output::BlockSink;
+use crate::store::error::StoreError;
+use crate::{handle_reader_with_sink, is_flat_file};
+use bytes::Bytes;
+use sf_protos::ethereum::r#type::v2::Block;
+use simple_log::log;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// `ObjectStore` holds flat files and decoded outputs as objects, named by `/` separated keys.
+///
+/// Listing by prefix works on whole key segments: the prefix `a/b` matches `a/b/c` but not `a/bc`.
+pub trait ObjectStore: Send + Sync {
+    /// Reads a whole object
+    fn get(&self, key: &str) -> Result<Bytes, StoreError>;
+
+    /// Reads the bytes of `range` of an object
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, StoreError>;
+
+    /// Writes an object, replacing it if it exists
+    fn put(&self, key: &str, bytes: Bytes) -> Result<(), StoreError>;
+
+    /// Keys of the objects under `prefix`, sorted
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StoreError>;
+}
+
+/// Opens the store of `url`, and returns it with the prefix of the URL.
+///
+/// * `s3://<bucket>/<prefix>` for S3-compatible storage, needs the `s3` feature. See [`remote::RemoteStore::s3`].
+/// * `gs://<bucket>/<prefix>` for Google Cloud Storage, needs the `gcs` feature. See [`remote::RemoteStore::gcs`].
+/// * `http://<host>/<path>` or `https://<host>/<path>` for files served over HTTP, read only, with an
+///   empty prefix. Needs the `http` feature. See [`remote::RemoteStore::http`].
+/// * `file://<path>` or a plain path for a local folder, with an empty prefix.
+pub fn open_store(url: &str) -> Result<(Box<dyn ObjectStore>, String), StoreError> {
+    let (scheme, location) = match url.split_once("://") {
+        Some((scheme, location)) => (scheme, location),
+        None => return Ok((Box::new(LocalStore::new(url)), String::new())),
+    };
+    match scheme {
+        "file" => return Ok((Box::new(LocalStore::new(location)), String::new())),
+        #[cfg(feature = "http")]
+        "http" | "https" => return Ok((Box::new(remote::RemoteStore::http(url)?), String::new())),
+        #[cfg(not(feature = "http"))]
+        "http" | "https" => return Err(StoreError::Unsupported(scheme.to_string())),
+        _ => {}
+    }
+
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    if bucket.is_empty() {
+        return Err(StoreError::InvalidUrl(url.to_string()));
+    }
+    let prefix = prefix.trim_end_matches('/').to_string();
+    match scheme {
+        #[cfg(feature = "s3")]
+        "s3" => Ok((Box::new(remote::RemoteStore::s3(bucket)?), prefix)),
+        #[cfg(feature = "gcs")]
+        "gs" => Ok((Box::new(remote::RemoteStore::gcs(bucket)?), prefix)),
+        #[allow(unreachable_patterns)]
+        "s3" | "gs" => Err(StoreError::Unsupported(scheme.to_string())),
+        _ => Err(StoreError::InvalidUrl(url.to_string())),
+    }
+}
+
+/// Whether `location` is a store URL rather than a local path, see [`open_store`]
+pub fn is_store_url(location: &str) -> bool {
+    location.contains("://")
+}
+
+/// Whether `key` is under `prefix`, see [`ObjectStore::list`]
+fn has_prefix(key: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    prefix.is_empty()
+        || key
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// `LocalStore` is an [`ObjectStore`] of the files of a local folder, keyed by their path
+/// relative to the folder.
+#[derive(Debug, Clone)]
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    fn list_dir(&self, dir: &Path, keys: &mut Vec<String>) -> Result<(), StoreError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.list_dir(&path, keys)?;
+            } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                let segments: Vec<_> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect();
+                keys.push(segments.join("/"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ObjectStore for LocalStore {
+    fn get(&self, key: &str) -> Result<Bytes, StoreError> {
+        match fs::read(self.path(key)) {
+            Ok(bytes) => Ok(Bytes::from(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(StoreError::NotFound(key.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, StoreError> {
+        let mut file = match File::open(self.path(key)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(StoreError::NotFound(key.to_string()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if range.end < range.start || range.end > file.metadata()?.len() {
+            return Err(StoreError::InvalidRange(key.to_string(), range));
+        }
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut bytes = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(Bytes::from(bytes))
+    }
+
+    fn put(&self, key: &str, bytes: Bytes) -> Result<(), StoreError> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        let mut keys = Vec::new();
+        if self.root.is_dir() {
+            self.list_dir(&self.root, &mut keys)?;
+        }
+        keys.retain(|key| has_prefix(key, prefix));
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// `MemoryStore` is an [`ObjectStore`] kept in memory, for tests and short-lived outputs.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    objects: RwLock<BTreeMap<String, Bytes>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Bytes, StoreError> {
+        let objects = self.objects.read().unwrap_or_else(|err| err.into_inner());
+        objects
+            .get(key)
+            .cloned()
+            .ok_or_else(|| StoreError::NotFound(key.to_string()))
+    }
+
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, StoreError> {
+        let bytes = self.get(key)?;
+        if range.end < range.start || range.end > bytes.len() as u64 {
+            return Err(StoreError::InvalidRange(key.to_string(), range));
+        }
+        Ok(bytes.slice(range.start as usize..range.end as usize))
+    }
+
+    fn put(&self, key: &str, bytes: Bytes) -> Result<(), StoreError> {
+        let mut objects = self.objects.write().unwrap_or_else(|err| err.into_inner());
+        objects.insert(key.to_string(), bytes);
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        let objects = self.objects.read().unwrap_or_else(|err| err.into_inner());
+        Ok(objects
+            .keys()
+            .filter(|key| has_prefix(key, prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Decodes and verifies the flat files of a store, the same way [`crate::decode_flat_files_with_sink`]
+/// does for a local folder.
+///
+/// Flat files are decoded in the order of their keys. Files failing to decode are logged and
+/// skipped, unless blocks are written to a sink, in which case the error is returned.
+///
+/// # Arguments
+///
+/// * `store`: The [`ObjectStore`] holding the flat files.
+/// * `prefix`: The prefix of the keys of the flat files, see [`ObjectStore::list`].
+/// * `sink`: An optional [`BlockSink`] the decoded blocks are written to, see [`crate::output::open_sink`].
+/// * `headers_dir`: An [`Option<&str>`] specifying the directory containing header files for verification.
+/// * `compression`: An [`Option<Compression>`] specifying how the flat files are compressed, detected from each file if `None`.
+/// * `options`: The [`DbinReaderOptions`] the flat files are read with.
+pub fn decode_store(
+    store: &dyn ObjectStore,
+    prefix: &str,
+    mut sink: Option<&mut (dyn BlockSink + '_)>,
+    headers_dir: Option<&str>,
+    compression: Option<Compression>,
+    options: DbinReaderOptions,
+) -> Result<Vec<Block>, DecodeError> {
+    let mut keys = store.list(prefix)?;
+    keys.retain(|key| is_flat_file(Path::new(key)));
+
+    let mut blocks: Vec<Block> = vec![];
+    for key in keys {
+        println!("Processing file: {}", key);
+        let bytes = store.get(&key)?;
+        match handle_reader_with_sink(
+            bytes.as_ref(),
+            sink.as_deref_mut(),
+            headers_dir,
+            compression,
+            options,
+        ) {
+            Ok(file_blocks) => {
+                blocks.extend(file_blocks);
+            }
+            Err(err) if sink.is_some() => {
+                log::error!("Failed to process file {}: {}", key, err);
+                return Err(err);
+            }
+            Err(err) => {
+                log::error!("Failed to process file {}: {}", key, err);
+            }
+        }
+    }
+
+    if let Some(sink) = sink {
+        sink.finish()?;
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_store, LocalStore, MemoryStore, ObjectStore};
+    use crate::dbin::DbinReaderOptions;
+    use crate::handle_file;
+    use crate::output::{OutputFormat, SinkOptions, StoreSink};
+    use bytes::Bytes;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        let file = fs::read("tests/0000000000.dbin.zst").unwrap();
+        store
+            .put("flat/0000000000.dbin.zst", Bytes::from(file.clone()))
+            .unwrap();
+        store.put("flat/notes.txt", Bytes::from("notes")).unwrap();
+        store.put("flatter/0000000100.dbin", Bytes::new()).unwrap();
+
+        assert_eq!(
+            store.list("flat/").unwrap(),
+            vec!["flat/0000000000.dbin.zst", "flat/notes.txt"]
+        );
+        assert_eq!(
+            store.get_range("flat/notes.txt", 1..3).unwrap(),
+            Bytes::from("ot")
+        );
+        assert!(store.get_range("flat/notes.txt", 1..6).is_err());
+
+        let blocks = decode_store(
+            &store,
+            "flat",
+            None,
+            None,
+            None,
+            DbinReaderOptions::default(),
+        )
+        .unwrap();
+        let expected = handle_file(
+            &PathBuf::from("tests/0000000000.dbin.zst"),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn test_decode_store_corrupt_file() {
+        let input = MemoryStore::new();
+        input
+            .put(
+                "0000000000.dbin.zst",
+                Bytes::from(fs::read("tests/0000000000.dbin.zst").unwrap()),
+            )
+            .unwrap();
+        input
+            .put("0000000100.dbin", Bytes::from("dbin corrupt"))
+            .unwrap();
+
+        let blocks =
+            decode_store(&input, "", None, None, None, DbinReaderOptions::default()).unwrap();
+        assert_eq!(blocks.len(), 100);
+
+        let mut sink = StoreSink::new(
+            OutputFormat::Jsonl,
+            Box::new(MemoryStore::new()),
+            "decoded",
+            SinkOptions::default(),
+        )
+        .unwrap();
+        assert!(decode_store(
+            &input,
+            "",
+            Some(&mut sink),
+            None,
+            None,
+            DbinReaderOptions::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_local_store() {
+        let dir = std::env::temp_dir().join("decoder-test-local-store");
+        let _ = fs::remove_dir_all(&dir);
+        let store = LocalStore::new(&dir);
+
+        store.put("a/b/c.json", Bytes::from("{}")).unwrap();
+        store.put("a/bc.json", Bytes::from("[]")).unwrap();
+        assert_eq!(store.list("a/b").unwrap(), vec!["a/b/c.json"]);
+        assert_eq!(store.list("").unwrap(), vec!["a/b/c.json", "a/bc.json"]);
+        assert_eq!(store.get("a/bc.json").unwrap(), Bytes::from("[]"));
+        assert_eq!(
+            store.get_range("a/b/c.json", 1..2).unwrap(),
+            Bytes::from("}")
+        );
+        assert!(store.get("a/missing.json").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_sink() {
+        let input = MemoryStore::new();
+        input
+            .put(
+                "0000000000.dbin.zst",
+                Bytes::from(fs::read("tests/0000000000.dbin.zst").unwrap()),
+            )
+            .unwrap();
+
+        let options = SinkOptions {
+            blocks_per_file: Some(50),
+            ..Default::default()
+        };
+        let mut sink = StoreSink::new(
+            OutputFormat::Jsonl,
+            Box::new(MemoryStore::new()),
+            "decoded",
+            options,
+        )
+        .unwrap();
+        decode_store(
+            &input,
+            "",
+            Some(&mut sink),
+            None,
+            None,
+            DbinReaderOptions::default(),
+        )
+        .unwrap();
+
+        let output = sink.store();
+        assert_eq!(
+            output.list("decoded").unwrap(),
+            vec![
+                "decoded/blocks-0000000000.jsonl",
+                "decoded/blocks-0000000050.jsonl"
+            ]
+        );
+        let lines = output.get("decoded/blocks-0000000050.jsonl").unwrap();
+        assert_eq!(
+            lines
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .count(),
+            50
+        );
+    }
+}
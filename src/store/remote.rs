@@ -0,0 +1,192 @@
+use crate::store::error::StoreError;
+use crate::store::{has_prefix, ObjectStore};
+use bytes::Bytes;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::PutPayload;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::{mpsc, Arc};
+use tokio::runtime::Runtime;
+
+/// `RemoteStore` is an [`ObjectStore`] backed by S3-compatible storage, Google Cloud Storage
+/// or an HTTP server, through the `object_store` crate.
+///
+/// Requests run on a runtime owned by the store, so that it can be used from blocking code
+/// as well as from async tasks.
+pub struct RemoteStore {
+    store: Arc<dyn object_store::ObjectStore>,
+    runtime: Option<Runtime>,
+}
+
+impl RemoteStore {
+    pub fn new(store: Arc<dyn object_store::ObjectStore>) -> Result<Self, StoreError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            store,
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Opens an S3 bucket, configured by the `AWS_*` environment variables: credentials with
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, and for S3-compatible
+    /// storage like MinIO `AWS_ENDPOINT` and `AWS_ALLOW_HTTP`.
+    #[cfg(feature = "s3")]
+    pub fn s3(bucket: &str) -> Result<Self, StoreError> {
+        let store = object_store::aws::AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()?;
+        Self::new(Arc::new(store))
+    }
+
+    /// Opens a Google Cloud Storage bucket, configured by the `GOOGLE_*` environment variables,
+    /// like `GOOGLE_SERVICE_ACCOUNT` for the path of the service account credentials.
+    #[cfg(feature = "gcs")]
+    pub fn gcs(bucket: &str) -> Result<Self, StoreError> {
+        let store = object_store::gcp::GoogleCloudStorageBuilder::from_env()
+            .with_bucket_name(bucket)
+            .build()?;
+        Self::new(Arc::new(store))
+    }
+
+    /// Opens the files served under `url` by an HTTP server, read with `GET` requests. Range
+    /// reads use `Range` headers, which the server must answer with `206 Partial Content`.
+    ///
+    /// Listing needs a WebDAV server, and writing a server accepting `PUT` requests.
+    #[cfg(feature = "http")]
+    pub fn http(url: &str) -> Result<Self, StoreError> {
+        let store = object_store::http::HttpBuilder::new()
+            .with_url(url)
+            .with_client_options(object_store::ClientOptions::new().with_allow_http(true))
+            .build()?;
+        Self::new(Arc::new(store))
+    }
+
+    /// Runs `request` on the runtime of the store, blocking until it completes
+    fn block_on<T, F>(&self, request: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T, object_store::Error>> + Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().ok_or(StoreError::RuntimeStopped)?;
+        let (sender, receiver) = mpsc::channel();
+        runtime.spawn(async move {
+            let _ = sender.send(request.await);
+        });
+        match receiver.recv() {
+            Ok(Err(object_store::Error::NotFound { path, .. })) => Err(StoreError::NotFound(path)),
+            Ok(result) => Ok(result?),
+            Err(_) => Err(StoreError::RuntimeStopped),
+        }
+    }
+}
+
+impl ObjectStore for RemoteStore {
+    fn get(&self, key: &str) -> Result<Bytes, StoreError> {
+        let store = self.store.clone();
+        let path = Path::from(key);
+        self.block_on(async move { store.get(&path).await?.bytes().await })
+    }
+
+    fn get_range(&self, key: &str, range: Range<u64>) -> Result<Bytes, StoreError> {
+        if range.end < range.start {
+            return Err(StoreError::InvalidRange(key.to_string(), range));
+        }
+        let store = self.store.clone();
+        let path = Path::from(key);
+        let bytes = range.start as usize..range.end as usize;
+        let object = self.block_on(async move { store.get_range(&path, bytes).await })?;
+        if object.len() as u64 != range.end - range.start {
+            return Err(StoreError::InvalidRange(key.to_string(), range));
+        }
+        Ok(object)
+    }
+
+    fn put(&self, key: &str, bytes: Bytes) -> Result<(), StoreError> {
+        let store = self.store.clone();
+        let path = Path::from(key);
+        self.block_on(async move {
+            store.put(&path, PutPayload::from(bytes)).await?;
+            Ok(())
+        })
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        let store = self.store.clone();
+        let path = Some(prefix.trim_end_matches('/'))
+            .filter(|prefix| !prefix.is_empty())
+            .map(Path::from);
+        let objects =
+            self.block_on(async move { store.list(path.as_ref()).try_collect::<Vec<_>>().await })?;
+
+        let mut keys: Vec<String> = objects
+            .into_iter()
+            .map(|object| object.location.to_string())
+            .collect();
+        keys.retain(|key| has_prefix(key, prefix));
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+impl Drop for RemoteStore {
+    fn drop(&mut self) {
+        // dropping a runtime blocks, which isn't allowed from async code
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoteStore;
+    use crate::dbin::DbinReaderOptions;
+    use crate::store::{decode_store, ObjectStore};
+    use bytes::Bytes;
+    use object_store::memory::InMemory;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn check_store(store: &RemoteStore, prefix: &str) {
+        let file = Bytes::from(fs::read("tests/0000000000.dbin.zst").unwrap());
+        let key = format!("{}/0000000000.dbin.zst", prefix);
+        store.put(&key, file.clone()).unwrap();
+
+        assert_eq!(store.list(prefix).unwrap(), vec![key.clone()]);
+        assert_eq!(store.get_range(&key, 4..12).unwrap(), file.slice(4..12));
+        assert!(store.get(&format!("{}/missing.dbin", prefix)).is_err());
+
+        let blocks = decode_store(
+            store,
+            prefix,
+            None,
+            None,
+            None,
+            DbinReaderOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 100);
+    }
+
+    #[test]
+    fn test_remote_store() {
+        let store = RemoteStore::new(Arc::new(InMemory::new())).unwrap();
+        check_store(&store, "flat");
+    }
+
+    /// Needs a MinIO server with a `decoder` bucket, e.g. `docker run -p 9000:9000 minio/minio server /data`:
+    ///
+    /// `AWS_ENDPOINT=http://localhost:9000 AWS_ALLOW_HTTP=true AWS_REGION=us-east-1
+    /// AWS_ACCESS_KEY_ID=minioadmin AWS_SECRET_ACCESS_KEY=minioadmin cargo test --features s3 -- --ignored`
+    #[cfg(feature = "s3")]
+    #[test]
+    #[ignore]
+    fn test_s3_store() {
+        let store = RemoteStore::s3("decoder").unwrap();
+        check_store(&store, "decoder-test");
+    }
+}
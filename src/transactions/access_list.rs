@@ -15,7 +15,12 @@ pub(crate) fn compute_access_list(
 }
 
 pub fn atuple_to_alist_item(tuple: &AccessTuple) -> Result<AccessListItem, TransactionError> {
-    let address: Address = Address::from_slice(tuple.address.as_slice());
+    let address: [u8; 20] = tuple
+        .address
+        .as_slice()
+        .try_into()
+        .map_err(|_| TransactionError::InvalidAddress(hex::encode(&tuple.address)))?;
+    let address = Address::from(address);
     let storage_keys = tuple
         .storage_keys
         .iter()
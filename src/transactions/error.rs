@@ -8,6 +8,8 @@ pub enum TransactionError {
     MismatchedRoot(String, String),
     #[error("Missing call field")]
     MissingCall,
+    #[error("Invalid Address: {0}")]
+    InvalidAddress(String),
     #[error("Invalid Storage Key: {0}")]
     InvalidStorageKey(String),
     #[error("Invalid BigInt")]
@@ -10,7 +10,7 @@ use crate::transactions::error::TransactionError;
 use reth_primitives::{hex, proofs::calculate_transaction_root, TransactionSigned, U128};
 use sf_protos::ethereum::r#type::v2::{BigInt, Block};
 
-use self::transaction_signed::trace_to_signed;
+pub use self::transaction_signed::trace_to_signed;
 
 pub fn check_transaction_root(block: &Block) -> Result<(), TransactionError> {
     let mut transactions: Vec<TransactionSigned> = Vec::new();
@@ -3,7 +3,7 @@ use crate::transactions::error::TransactionError;
 use crate::transactions::tx_type::map_tx_type;
 use alloy_primitives::{TxKind, Uint};
 use reth_primitives::{
-    Address, Bytes, ChainId, Transaction, TxEip1559, TxEip2930, TxLegacy, TxType,
+    hex, Address, Bytes, ChainId, Transaction, TxEip1559, TxEip2930, TxLegacy, TxType,
 };
 use sf_protos::ethereum::r#type::v2::{BigInt, CallType, TransactionTrace};
 
@@ -94,7 +94,7 @@ pub fn trace_to_transaction(trace: &TransactionTrace) -> Result<Transaction, Tra
                 input,
             })
         }
-        TxType::Eip4844 => unimplemented!(),
+        TxType::Eip4844 => return Err(TransactionError::EIP4844NotSupported),
     };
 
     Ok(transaction)
@@ -108,7 +108,11 @@ pub fn get_tx_kind(trace: &TransactionTrace) -> Result<TxKind, TransactionError>
     if call_type == CallType::Create {
         Ok(TxKind::Create)
     } else {
-        let address = Address::from_slice(trace.to.as_slice());
-        Ok(TxKind::Call(address))
+        let address: [u8; 20] = trace
+            .to
+            .as_slice()
+            .try_into()
+            .map_err(|_| TransactionError::InvalidAddress(hex::encode(&trace.to)))?;
+        Ok(TxKind::Call(Address::from(address)))
     }
 }